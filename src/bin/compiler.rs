@@ -9,20 +9,18 @@ use failure::{err_msg, Error};
 
 use gc_arena::rootless_arena;
 
-use luster::compiler::compile_chunk;
-use luster::parser::parse_chunk;
+use luster::compiler::compile;
 
 fn main() -> Result<(), Error> {
     let mut args = env::args();
     args.next();
-    let file = File::open(
-        args.next()
-            .ok_or_else(|| err_msg("no file argument given"))?,
-    )?;
+    let path = args
+        .next()
+        .ok_or_else(|| err_msg("no file argument given"))?;
+    let file = File::open(&path)?;
 
     rootless_arena(|mc| -> Result<(), Error> {
-        let chunk = parse_chunk(file)?;
-        let function = compile_chunk(mc, &chunk)?;
+        let function = compile(mc, path.as_bytes(), file)?;
         println!("output: {:#?}", function);
         Ok(())
     })?;
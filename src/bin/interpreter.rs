@@ -2,35 +2,86 @@ extern crate failure;
 extern crate luster;
 
 use std::env;
+use std::ffi::OsStr;
 use std::fs::File;
+use std::panic;
+use std::process;
 
 use failure::{err_msg, Error};
 
-use luster::compiler::compile_chunk;
+use luster::compiler::{compile_chunk, CompilerOptions};
 use luster::function::Closure;
 use luster::io::buffered_read;
 use luster::lua::Lua;
 use luster::parser::parse_chunk;
 use luster::sequence::SequenceExt;
+use luster::string::String;
+use luster::table::Table;
+use luster::value::Value;
 
-fn main() -> Result<(), Error> {
-    let mut args = env::args();
-    args.next();
-    let file = File::open(
-        args.next()
-            .ok_or_else(|| err_msg("no file argument given"))?,
-    )?;
+fn main() {
+    // Some runtime errors in the VM still panic rather than returning an error, so a panic is also
+    // treated as an uncaught error.  Either way, exit with status 1 like the reference interpreter.
+    match panic::catch_unwind(run) {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            eprintln!("luster: {}", err);
+            process::exit(1);
+        }
+        Err(_) => process::exit(1),
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let mut args = env::args_os();
+    let program = arg_bytes(&args.next().unwrap_or_default());
+    let script_path = args
+        .next()
+        .ok_or_else(|| err_msg("no file argument given"))?;
+    let script = arg_bytes(&script_path);
+    let script_args: Vec<Vec<u8>> = args.map(|a| arg_bytes(&a)).collect();
+
+    let file = File::open(script_path)?;
     let chunk = parse_chunk(buffered_read(file)?)?;
 
     let mut lua = Lua::new();
     lua.sequence(move |mc, lc| {
+        // Like the reference interpreter, the `arg` table holds the script name at index 0, the
+        // program name at index -1, and the script arguments from index 1.
+        let arg = Table::new(mc);
+        arg.set(
+            mc,
+            Value::Integer(-1),
+            Value::String(String::new(mc, &program)),
+        )?;
+        arg.set(
+            mc,
+            Value::Integer(0),
+            Value::String(String::new(mc, &script)),
+        )?;
+        let mut varargs = Vec::new();
+        for (i, a) in script_args.iter().enumerate() {
+            let a = Value::String(String::new(mc, a));
+            arg.set(mc, Value::Integer(i as i64 + 1), a)?;
+            varargs.push(a);
+        }
+        lc.globals.set(
+            mc,
+            Value::String(String::new(mc, b"arg")),
+            Value::Table(arg),
+        )?;
+
         Ok(Box::new(
             lc.main_thread
                 .call_function(
                     mc,
-                    Closure::new(mc, compile_chunk(mc, &chunk)?, Some(lc.globals))?,
-                    &[],
-                    64,
+                    Closure::new(
+                        mc,
+                        compile_chunk(mc, &script, &chunk, &CompilerOptions::default())?,
+                        Some(lc.globals),
+                    )?,
+                    &varargs,
+                    lc.granularity,
                 )
                 .map(|_, r| {
                     println!("results: {:?}", r);
@@ -41,3 +92,14 @@ fn main() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(unix)]
+fn arg_bytes(arg: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    arg.as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn arg_bytes(arg: &OsStr) -> Vec<u8> {
+    arg.to_string_lossy().into_owned().into_bytes()
+}
@@ -0,0 +1,93 @@
+extern crate failure;
+extern crate luster;
+
+use std::io::{self, BufRead, Read, Write};
+
+use failure::Error;
+
+use luster::compiler::{compile_session_chunk, CompilerOptions};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::parser::{is_unexpected_end, parse_chunk};
+use luster::sequence::{sequence_fn, SequenceExt};
+use luster::string::String;
+use luster::table::Table;
+use luster::value::Value;
+
+const SESSION_KEY: &[u8] = b"repl_session";
+
+fn main() -> Result<(), Error> {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, lc| {
+        lc.registry.set(
+            mc,
+            Value::String(String::Static(SESSION_KEY)),
+            Value::Table(Table::new(mc)),
+        )?;
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })?;
+
+    let mut session_locals = Vec::new();
+    let mut input = Vec::new();
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { ">> " });
+        io::stdout().flush()?;
+
+        if stdin.read_until(b'\n', &mut input)? == 0 {
+            break;
+        }
+
+        // Try the input as an expression first so that its value is printed without needing an
+        // explicit `return`, and only then as a sequence of statements.  An expression which is
+        // cut short may continue on the next line.
+        let chunk = match parse_chunk(Read::chain(&b"return "[..], &input[..])) {
+            Ok(chunk) => chunk,
+            Err(ref err) if is_unexpected_end(err) => continue,
+            Err(_) => match parse_chunk(&input[..]) {
+                Ok(chunk) => chunk,
+                Err(ref err) if is_unexpected_end(err) => continue,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    input.clear();
+                    continue;
+                }
+            },
+        };
+        input.clear();
+
+        let session_locals = &mut session_locals;
+        let res = lua.sequence(move |mc, lc| {
+            let proto = compile_session_chunk(
+                mc,
+                b"stdin",
+                &chunk,
+                session_locals,
+                &CompilerOptions::default(),
+            )?;
+            let session = lc.registry.get(Value::String(String::Static(SESSION_KEY)));
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(mc, proto, Some(lc.globals))?,
+                        &[session],
+                        lc.granularity,
+                    )
+                    .map(|_, r| {
+                        if !r.is_empty() {
+                            println!("{:?}", r);
+                        }
+                        Ok(())
+                    }),
+            ))
+        });
+
+        if let Err(err) = res {
+            eprintln!("error: {}", err);
+        }
+    }
+
+    Ok(())
+}
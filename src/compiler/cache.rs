@@ -0,0 +1,243 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use gc_arena::{Gc, MutationContext};
+
+use crate::function::{FunctionProto, LocalVariable, UpValueDescriptor};
+use crate::opcode::OpCode;
+use crate::string::String;
+use crate::table::Table;
+use crate::types::{LineNumber, RegisterIndex};
+use crate::value::Value;
+
+use super::{compile_source, CompileError, CompilerOptions};
+
+/// A cache of compiled chunks, for applications which compile the same source many times, such as
+/// configuration or UI scripts which are evaluated repeatedly.
+///
+/// Chunks are looked up by a hash of their source, their chunk name and the compiler options, and
+/// are stored as `CachedProto`s, which do not depend on any GC arena.  A cached chunk can be used
+/// from any arena, and skips both parsing and compiling.
+#[derive(Debug, Default)]
+pub struct CompilationCache {
+    entries: HashMap<u64, CacheEntry>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    chunk_name: Box<[u8]>,
+    source: Box<[u8]>,
+    options: CompilerOptions,
+    proto: CachedProto,
+}
+
+impl CompilationCache {
+    pub fn new() -> CompilationCache {
+        CompilationCache::default()
+    }
+
+    /// Compiles the source like `compile` but with the given options, returning the cached result
+    /// if the same source was already compiled with the same chunk name and options.  Chunks which
+    /// fail to compile are not cached.
+    pub fn compile<'gc>(
+        &mut self,
+        mc: MutationContext<'gc, '_>,
+        chunk_name: &[u8],
+        source: &[u8],
+        options: &CompilerOptions,
+    ) -> Result<FunctionProto<'gc>, CompileError> {
+        let mut hasher = DefaultHasher::new();
+        chunk_name.hash(&mut hasher);
+        source.hash(&mut hasher);
+        options.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // The hash is only used to find the entry, the key itself is compared in full so that a
+        // hash collision compiles the source rather than returning the wrong chunk.
+        if let Some(entry) = self.entries.get(&hash) {
+            if &*entry.chunk_name == chunk_name
+                && &*entry.source == source
+                && entry.options == *options
+            {
+                return Ok(entry.proto.rehydrate(mc));
+            }
+        }
+
+        let proto = compile_source(mc, chunk_name, source, options)?;
+        self.entries.insert(
+            hash,
+            CacheEntry {
+                chunk_name: chunk_name.into(),
+                source: source.into(),
+                options: options.clone(),
+                proto: CachedProto::new(&proto),
+            },
+        );
+        Ok(proto)
+    }
+
+    /// The number of chunks in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A copy of a `FunctionProto` which does not depend on the GC arena, so it can outlive the arena it
+/// was compiled in and be turned back into a `FunctionProto` in any other.
+#[derive(Debug, Clone)]
+pub struct CachedProto {
+    chunk_name: Box<[u8]>,
+    fixed_params: u8,
+    has_varargs: bool,
+    stack_size: u8,
+    constants: Vec<CachedConstant>,
+    opcodes: Vec<OpCode>,
+    opcode_lines: Vec<(usize, LineNumber)>,
+    local_variables: Vec<CachedLocalVariable>,
+    upvalues: Vec<UpValueDescriptor>,
+    prototypes: Vec<CachedProto>,
+    // The entries of each switch table, in order of traversal
+    switches: Vec<Vec<(CachedConstant, CachedConstant)>>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedLocalVariable {
+    name: Box<[u8]>,
+    register: RegisterIndex,
+    start: usize,
+    end: usize,
+}
+
+// The compiler only produces constants which are literals, so no constant is a table or function
+#[derive(Debug, Clone)]
+enum CachedConstant {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(Box<[u8]>),
+}
+
+impl CachedProto {
+    pub fn new(proto: &FunctionProto) -> CachedProto {
+        CachedProto {
+            chunk_name: proto.chunk_name.as_bytes().into(),
+            fixed_params: proto.fixed_params,
+            has_varargs: proto.has_varargs,
+            stack_size: proto.stack_size,
+            constants: proto
+                .constants
+                .iter()
+                .map(|&c| CachedConstant::new(c))
+                .collect(),
+            opcodes: proto.opcodes.clone(),
+            opcode_lines: proto.opcode_lines.clone(),
+            local_variables: proto
+                .local_variables
+                .iter()
+                .map(|local| CachedLocalVariable {
+                    name: local.name.as_bytes().into(),
+                    register: local.register,
+                    start: local.start,
+                    end: local.end,
+                })
+                .collect(),
+            upvalues: proto.upvalues.clone(),
+            prototypes: proto
+                .prototypes
+                .iter()
+                .map(|p| CachedProto::new(p))
+                .collect(),
+            switches: proto
+                .switches
+                .iter()
+                .map(|&table| {
+                    let mut entries = Vec::new();
+                    let mut key = Value::Nil;
+                    while let Some((k, v)) = table.next(key).unwrap() {
+                        entries.push((CachedConstant::new(k), CachedConstant::new(v)));
+                        key = k;
+                    }
+                    entries
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates a `FunctionProto` in the given arena which is identical to the one this was created
+    /// from.
+    pub fn rehydrate<'gc>(&self, mc: MutationContext<'gc, '_>) -> FunctionProto<'gc> {
+        FunctionProto {
+            chunk_name: String::new(mc, &self.chunk_name),
+            fixed_params: self.fixed_params,
+            has_varargs: self.has_varargs,
+            stack_size: self.stack_size,
+            constants: self.constants.iter().map(|c| c.rehydrate(mc)).collect(),
+            opcodes: self.opcodes.clone(),
+            opcode_lines: self.opcode_lines.clone(),
+            local_variables: self
+                .local_variables
+                .iter()
+                .map(|local| LocalVariable {
+                    name: String::new(mc, &local.name),
+                    register: local.register,
+                    start: local.start,
+                    end: local.end,
+                })
+                .collect(),
+            upvalues: self.upvalues.clone(),
+            prototypes: self
+                .prototypes
+                .iter()
+                .map(|p| Gc::allocate(mc, p.rehydrate(mc)))
+                .collect(),
+            switches: self
+                .switches
+                .iter()
+                .map(|entries| {
+                    let table = Table::new(mc);
+                    for (k, v) in entries {
+                        table
+                            .set(mc, k.rehydrate(mc), v.rehydrate(mc))
+                            .expect("switch keys are never nil or NaN");
+                    }
+                    table
+                })
+                .collect(),
+        }
+    }
+}
+
+impl CachedConstant {
+    fn new(value: Value) -> CachedConstant {
+        match value {
+            Value::Nil => CachedConstant::Nil,
+            Value::Boolean(b) => CachedConstant::Boolean(b),
+            Value::Integer(i) => CachedConstant::Integer(i),
+            Value::Number(n) => CachedConstant::Number(n),
+            Value::String(s) => CachedConstant::String(s.as_bytes().into()),
+            Value::Table(_) | Value::Closure(_) => {
+                panic!("compiled constants are never tables or functions")
+            }
+        }
+    }
+
+    fn rehydrate<'gc>(&self, mc: MutationContext<'gc, '_>) -> Value<'gc> {
+        match self {
+            CachedConstant::Nil => Value::Nil,
+            CachedConstant::Boolean(b) => Value::Boolean(*b),
+            CachedConstant::Integer(i) => Value::Integer(*i),
+            CachedConstant::Number(n) => Value::Number(*n),
+            CachedConstant::String(s) => Value::String(String::new(mc, s)),
+        }
+    }
+}
@@ -1,75 +1,36 @@
-use std::hash::{Hash, Hasher};
-use std::mem;
-
+use crate::function::Closure;
+use crate::string::String;
+use crate::table::Table;
 use crate::value::Value;
 
-/// Value which implements Hash and Eq, where values are equal only when they are bit for bit
-/// identical.
-pub struct ConstantValue<'gc>(pub Value<'gc>);
-
-impl<'gc> PartialEq for ConstantValue<'gc> {
-    fn eq(&self, other: &ConstantValue<'gc>) -> bool {
-        match (self.0, other.0) {
-            (Value::Nil, Value::Nil) => true,
-            (Value::Nil, _) => false,
-
-            (Value::Boolean(a), Value::Boolean(b)) => a == b,
-            (Value::Boolean(_), _) => false,
-
-            (Value::Integer(a), Value::Integer(b)) => a == b,
-            (Value::Integer(_), _) => false,
-
-            (Value::Number(a), Value::Number(b)) => float_bytes(a) == float_bytes(b),
-            (Value::Number(_), _) => false,
-
-            (Value::String(a), Value::String(b)) => a == b,
-            (Value::String(_), _) => false,
-
-            (Value::Table(a), Value::Table(b)) => a == b,
-            (Value::Table(_), _) => false,
-
-            (Value::Closure(a), Value::Closure(b)) => a == b,
-            (Value::Closure(_), _) => false,
-        }
-    }
+/// The key that constants are deduplicated by, where values are equal only when they are bit for
+/// bit identical.
+///
+/// Unlike `Value` equality, the integer `1` and the float `1.0` are distinct keys, as are `0.0` and
+/// `-0.0`, so a constant always keeps its exact type and representation.  Every NaN with the same
+/// bit pattern is the same key.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ConstantKey<'gc> {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    // The bits of the float, as given by `f64::to_bits`
+    Number(u64),
+    String(String<'gc>),
+    Table(Table<'gc>),
+    Closure(Closure<'gc>),
 }
 
-impl<'gc> Eq for ConstantValue<'gc> {}
-
-impl<'gc> Hash for ConstantValue<'gc> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        match &self.0 {
-            Value::Nil => {
-                Hash::hash(&0, state);
-            }
-            Value::Boolean(b) => {
-                Hash::hash(&1, state);
-                b.hash(state);
-            }
-            Value::Integer(i) => {
-                Hash::hash(&2, state);
-                i.hash(state);
-            }
-            Value::Number(n) => {
-                Hash::hash(&3, state);
-                float_bytes(*n).hash(state);
-            }
-            Value::String(s) => {
-                Hash::hash(&4, state);
-                s.hash(state);
-            }
-            Value::Table(t) => {
-                Hash::hash(&5, state);
-                t.hash(state);
-            }
-            Value::Closure(c) => {
-                Hash::hash(&6, state);
-                c.hash(state);
-            }
+impl<'gc> From<Value<'gc>> for ConstantKey<'gc> {
+    fn from(value: Value<'gc>) -> ConstantKey<'gc> {
+        match value {
+            Value::Nil => ConstantKey::Nil,
+            Value::Boolean(b) => ConstantKey::Boolean(b),
+            Value::Integer(i) => ConstantKey::Integer(i),
+            Value::Number(n) => ConstantKey::Number(n.to_bits()),
+            Value::String(s) => ConstantKey::String(s),
+            Value::Table(t) => ConstantKey::Table(t),
+            Value::Closure(c) => ConstantKey::Closure(c),
         }
     }
 }
-
-fn float_bytes(f: f64) -> u64 {
-    unsafe { mem::transmute(f) }
-}
@@ -0,0 +1,94 @@
+use failure::Fail;
+
+use gc_arena::MutationContext;
+
+use crate::parser::{
+    Expression, ExpressionChunk, HeadExpression, PrimaryExpression, SimpleExpression,
+};
+use crate::string::String;
+use crate::value::Value;
+
+use super::operators::{
+    categorize_binop, comparison_binop_const_fold, simple_binop_const_fold, unop_const_fold,
+    BinOpCategory, ShortCircuitBinOp,
+};
+
+#[derive(Fail, Debug)]
+pub enum ConstantError {
+    #[fail(display = "expression is not constant")]
+    NotConstant,
+    #[fail(display = "operator cannot be evaluated at compile time")]
+    Operator,
+}
+
+/// Evaluates an expression made up only of literals and operators, using the same constant folding
+/// that the compiler does, without compiling or running any code.
+///
+/// Any variable reference, call, vararg, table constructor or function is `NotConstant`, and an
+/// operator which the compiler cannot fold for its operands is an `Operator` error.
+pub fn evaluate_constant<'gc>(
+    mc: MutationContext<'gc, '_>,
+    expression: &ExpressionChunk,
+) -> Result<Value<'gc>, ConstantError> {
+    evaluate_expression(mc, &expression.expression)
+}
+
+fn evaluate_expression<'gc>(
+    mc: MutationContext<'gc, '_>,
+    expression: &Expression,
+) -> Result<Value<'gc>, ConstantError> {
+    let mut value = match &*expression.head {
+        HeadExpression::Simple(simple) => evaluate_simple(mc, simple)?,
+        HeadExpression::UnaryOperator(unop, expr) => {
+            unop_const_fold(*unop, evaluate_expression(mc, expr)?).ok_or(ConstantError::Operator)?
+        }
+    };
+
+    for (binop, right) in &expression.tail {
+        value = match categorize_binop(*binop) {
+            BinOpCategory::Simple(op) => {
+                simple_binop_const_fold(op, value, evaluate_expression(mc, right)?)
+                    .ok_or(ConstantError::Operator)?
+            }
+            BinOpCategory::Comparison(op) => {
+                comparison_binop_const_fold(op, value, evaluate_expression(mc, right)?)
+                    .ok_or(ConstantError::Operator)?
+            }
+            BinOpCategory::ShortCircuit(op) => {
+                // The right operand must still be constant even when it is not evaluated, matching
+                // what the compiler would accept.
+                let right = evaluate_expression(mc, right)?;
+                match op {
+                    ShortCircuitBinOp::And if value.as_bool() => right,
+                    ShortCircuitBinOp::Or if !value.as_bool() => right,
+                    _ => value,
+                }
+            }
+            BinOpCategory::Concat => Value::concat(mc, &[value, evaluate_expression(mc, right)?])
+                .ok_or(ConstantError::Operator)?,
+        };
+    }
+
+    Ok(value)
+}
+
+fn evaluate_simple<'gc>(
+    mc: MutationContext<'gc, '_>,
+    simple_expression: &SimpleExpression,
+) -> Result<Value<'gc>, ConstantError> {
+    Ok(match simple_expression {
+        SimpleExpression::Float(f) => Value::Number(*f),
+        SimpleExpression::Integer(i) => Value::Integer(*i),
+        SimpleExpression::String(s) => Value::String(String::new(mc, s)),
+        SimpleExpression::Nil => Value::Nil,
+        SimpleExpression::True => Value::Boolean(true),
+        SimpleExpression::False => Value::Boolean(false),
+        SimpleExpression::Suffixed(suffixed) if suffixed.suffixes.is_empty() => {
+            match &suffixed.primary {
+                PrimaryExpression::GroupedExpression(expr) => evaluate_expression(mc, expr)?,
+                PrimaryExpression::Name(_) => return Err(ConstantError::NotConstant),
+            }
+        }
+        _ => return Err(ConstantError::NotConstant),
+    })
+}
@@ -1,31 +1,44 @@
-use std::collections::HashMap;
-use std::{iter, mem};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::{fmt, iter, mem, slice};
 
-use failure::Fail;
+use failure::{Error, Fail};
 use num_traits::cast;
 
 use gc_arena::{Gc, MutationContext};
 
-use crate::function::{FunctionProto, UpValueDescriptor};
+use crate::function::{FunctionProto, LocalVariable, UpValueDescriptor};
+use crate::interner::{Interner, Symbol};
 use crate::opcode::OpCode;
 use crate::parser::{
-    AssignmentStatement, AssignmentTarget, BinaryOperator, Block, CallSuffix, Chunk, Expression,
-    FieldSuffix, ForStatement, FunctionCallStatement, FunctionDefinition, FunctionStatement,
-    HeadExpression, IfStatement, LocalStatement, PrimaryExpression, RepeatStatement,
-    ReturnStatement, SimpleExpression, Statement, SuffixPart, SuffixedExpression, TableConstructor,
-    UnaryOperator, WhileStatement,
+    parse_chunk, AssignmentStatement, AssignmentTarget, BinaryOperator, Block, CallSuffix, Chunk,
+    ConstructorField, Expression, ExpressionChunk, FieldSuffix, ForStatement,
+    FunctionCallStatement, FunctionDefinition, FunctionStatement, HeadExpression, IfStatement,
+    LocalStatement, PrimaryExpression, RecordKey, RepeatStatement, ReturnStatement,
+    SimpleExpression, Statement, SuffixPart, SuffixedExpression, TableConstructor, UnaryOperator,
+    WhileStatement,
 };
+use crate::scope::{analyze_chunk, ReferenceKind};
 use crate::string::String;
+use crate::table::Table;
 use crate::types::{
-    ConstantIndex16, ConstantIndex8, Opt254, PrototypeIndex, RegisterIndex, UpValueIndex, VarCount,
+    ConstantIndex16, ConstantIndex8, LineNumber, Opt254, PrototypeIndex, RegisterIndex,
+    SwitchIndex, UpValueIndex, VarCount,
 };
 use crate::value::Value;
 
+mod cache;
 mod constant;
+mod evaluate;
 mod operators;
+mod options;
 mod register_allocator;
 
-use self::constant::ConstantValue;
+pub use self::cache::{CachedProto, CompilationCache};
+pub use self::evaluate::{evaluate_constant, ConstantError};
+pub use self::options::{CompilerOptions, OptimizationLevel};
+
+use self::constant::ConstantKey;
 use self::operators::{
     categorize_binop, comparison_binop_const_fold, comparison_binop_opcode,
     simple_binop_const_fold, simple_binop_opcode, unop_const_fold, unop_opcode, BinOpCategory,
@@ -34,68 +47,399 @@ use self::operators::{
 use self::register_allocator::RegisterAllocator;
 
 #[derive(Fail, Debug)]
-pub enum CompilerError {
-    #[fail(display = "insufficient available registers")]
-    Registers,
-    #[fail(display = "too many upvalues")]
-    UpValues,
+pub enum CompilerErrorKind {
+    #[fail(display = "insufficient available registers, the limit is {}", _0)]
+    Registers(u8),
+    #[fail(display = "too many upvalues, capturing '{}'", _0)]
+    UpValues(std::string::String),
     #[fail(display = "too many fixed parameters")]
     FixedParameters,
+    #[fail(display = "cannot use '...' outside a vararg function")]
+    VarArgs,
     #[fail(display = "too many inner functions")]
     Functions,
-    #[fail(display = "too many constants")]
-    Constants,
+    #[fail(display = "too many constants, the limit is {}", _0)]
+    Constants(usize),
+    #[fail(display = "functions nested too deeply, the limit is {}", _0)]
+    FunctionDepth(usize),
+    #[fail(
+        display = "assignment to global '{}' inside of a function in strict mode",
+        _0
+    )]
+    StrictGlobal(std::string::String),
+    #[fail(
+        display = "statements or expressions nested too deeply, the limit is {}",
+        _0
+    )]
+    RecursionLimit(usize),
     #[fail(display = "too many opcodes")]
     OpCodes,
-    #[fail(display = "label defined multiple times")]
-    DuplicateLabel,
-    #[fail(display = "goto target label not found")]
-    GotoInvalid,
-    #[fail(display = "jump into new scope of new local variable")]
-    JumpLocal,
+    #[fail(display = "label '{}' defined multiple times", _0)]
+    DuplicateLabel(std::string::String),
+    #[fail(display = "no visible label '{}' for goto", label)]
+    GotoInvalid {
+        label: std::string::String,
+        line: Option<LineNumber>,
+    },
+    #[fail(display = "break outside of a loop")]
+    BreakOutsideLoop { line: Option<LineNumber> },
+    #[fail(display = "goto '{}' jumps into the scope of local '{}'", label, local)]
+    JumpLocal {
+        label: std::string::String,
+        local: std::string::String,
+        line: Option<LineNumber>,
+    },
     #[fail(display = "jump offset overflow")]
     JumpOverflow,
+    #[fail(
+        display = "local function name '{}' cannot have fields or a method",
+        _0
+    )]
+    LocalFunctionName(std::string::String),
+}
+
+/// Error returned when a chunk fails to compile, located at the chunk name and the line being
+/// compiled when the error occurred.
+#[derive(Debug)]
+pub struct CompilerError {
+    pub chunk_name: std::string::String,
+    pub line: Option<LineNumber>,
+    pub kind: CompilerErrorKind,
+}
+
+impl CompilerErrorKind {
+    // The line of the statement which caused the error, if it is not the line being compiled when
+    // the error occurred
+    fn line(&self) -> Option<LineNumber> {
+        match *self {
+            CompilerErrorKind::GotoInvalid { line, .. }
+            | CompilerErrorKind::BreakOutsideLoop { line }
+            | CompilerErrorKind::JumpLocal { line, .. } => line,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{}:{}: {}", self.chunk_name, line.0, self.kind)
+        } else {
+            write!(f, "{}: {}", self.chunk_name, self.kind)
+        }
+    }
+}
+
+impl Fail for CompilerError {}
+
+/// Error returned by `compile`, from either parsing or compiling the source.
+#[derive(Debug)]
+pub enum CompileError {
+    Parse {
+        chunk_name: std::string::String,
+        error: Error,
+    },
+    Compiler(CompilerError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Parse { chunk_name, error } => write!(f, "{}: {}", chunk_name, error),
+            CompileError::Compiler(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Fail for CompileError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            CompileError::Parse { error, .. } => Some(error.as_fail()),
+            CompileError::Compiler(error) => Some(error),
+        }
+    }
+}
+
+impl From<CompilerError> for CompileError {
+    fn from(error: CompilerError) -> CompileError {
+        CompileError::Compiler(error)
+    }
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CompilerWarningKind {
+    UnusedLocal(std::string::String),
+    ShadowedLocal(std::string::String),
+    GlobalTypo {
+        global: std::string::String,
+        local: std::string::String,
+    },
+}
+
+impl fmt::Display for CompilerWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompilerWarningKind::UnusedLocal(name) => write!(f, "unused local '{}'", name),
+            CompilerWarningKind::ShadowedLocal(name) => {
+                write!(f, "local '{}' shadows an earlier local", name)
+            }
+            CompilerWarningKind::GlobalTypo { global, local } => write!(
+                f,
+                "assignment to global '{}', did you mean local '{}'?",
+                global, local
+            ),
+        }
+    }
+}
+
+/// A likely mistake found while compiling a chunk which does not prevent it from compiling,
+/// located the same way as a `CompilerError`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CompilerWarning {
+    pub chunk_name: std::string::String,
+    pub line: Option<LineNumber>,
+    pub kind: CompilerWarningKind,
+}
+
+impl fmt::Display for CompilerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "{}:{}: {}", self.chunk_name, line.0, self.kind)
+        } else {
+            write!(f, "{}: {}", self.chunk_name, self.kind)
+        }
+    }
+}
+
+/// Compiles a parsed chunk into a function prototype.  The chunk name identifies the source of the
+/// chunk in compiler errors and in runtime errors raised by the resulting function, and `options`
+/// sets the limits the compiled code must stay within.
 pub fn compile_chunk<'gc>(
     mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
+    chunk: &Chunk,
+    options: &CompilerOptions,
+) -> Result<FunctionProto<'gc>, CompilerError> {
+    let mut compiler = Compiler::new(mc, chunk_name, &chunk.interner, options);
+    compiler.written_names = Some(written_names(chunk));
+    compiler.compile(&[], true, |compiler| compiler.block(&chunk.block))
+}
+
+/// Parses and compiles a chunk of source code with the default compiler options.  The source may be
+/// a byte slice (`source.as_bytes()` for a `&str`) or any other `Read`.  Use `parse_chunk` and
+/// `compile_chunk` directly to compile with other options.
+pub fn compile<'gc, R: Read>(
+    mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
+    source: R,
+) -> Result<FunctionProto<'gc>, CompileError> {
+    compile_source(mc, chunk_name, source, &CompilerOptions::default())
+}
+
+fn compile_source<'gc, R: Read>(
+    mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
+    source: R,
+    options: &CompilerOptions,
+) -> Result<FunctionProto<'gc>, CompileError> {
+    let chunk = parse_chunk(source).map_err(|error| CompileError::Parse {
+        chunk_name: std::string::String::from_utf8_lossy(chunk_name).into_owned(),
+        error,
+    })?;
+    Ok(compile_chunk(mc, chunk_name, &chunk, options)?)
+}
+
+/// Compiles a parsed chunk like `compile_chunk`, and appends a warning to `warnings` for every local
+/// which is never read, every local which shadows another visible local, and every assignment to a
+/// global whose name is a single typo away from a visible local.  Warnings are produced even if
+/// compilation ultimately fails.
+pub fn compile_chunk_with_warnings<'gc>(
+    mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
+    chunk: &Chunk,
+    options: &CompilerOptions,
+    warnings: &mut Vec<CompilerWarning>,
+) -> Result<FunctionProto<'gc>, CompilerError> {
+    let mut compiler = Compiler::new(mc, chunk_name, &chunk.interner, options);
+    compiler.written_names = Some(written_names(chunk));
+    compiler.warnings = Some(Vec::new());
+    let res = compiler.compile(&[], true, |compiler| compiler.block(&chunk.block));
+    warnings.extend(compiler.warnings.unwrap());
+    res
+}
+
+/// Compiles the block of a parsed chunk as the body of a function with the given parameter names,
+/// rather than as a main function which only takes varargs.  This lets embedders compile callbacks
+/// whose arguments are available by name, such as a handler with `request` and `response`
+/// parameters.  Using `...` in the body is an error unless `has_varargs` is set.
+pub fn compile_function<'gc>(
+    mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
+    parameters: &[&[u8]],
+    has_varargs: bool,
+    chunk: &Chunk,
+    options: &CompilerOptions,
+) -> Result<FunctionProto<'gc>, CompilerError> {
+    // Parameters which are never mentioned in the block still need a symbol
+    let mut interner = chunk.interner.clone();
+    let parameters = parameters
+        .iter()
+        .map(|name| interner.intern(name))
+        .collect::<Vec<_>>();
+    let mut compiler = Compiler::new(mc, chunk_name, &interner, options);
+    compiler.written_names = Some(written_names(chunk));
+    compiler.compile(&parameters, has_varargs, |compiler| {
+        compiler.block(&chunk.block)
+    })
+}
+
+/// Compiles a single expression into a function which returns every value the expression produces,
+/// as though it were the chunk `return <expression>`.
+pub fn compile_expression<'gc>(
+    mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
+    expression: &ExpressionChunk,
+    options: &CompilerOptions,
+) -> Result<FunctionProto<'gc>, CompilerError> {
+    let mut compiler = Compiler::new(mc, chunk_name, &expression.interner, options);
+    compiler.compile(&[], true, |compiler| {
+        compiler.enter_block();
+        compiler.return_values(slice::from_ref(&expression.expression))?;
+        compiler.exit_block()
+    })
+}
+
+/// Compiles a chunk as part of an interactive session, where locals declared at the top level of
+/// the chunk stay visible to every chunk compiled after it in the same session.
+///
+/// Top-level locals do not live in registers, instead they are stored as fields of a session table
+/// which must be passed to the resulting function as its first argument, and the same table must be
+/// passed to every chunk in the session.  `session_locals` holds the names of the locals declared
+/// by earlier chunks, and is updated with any new ones if compilation succeeds.
+///
+/// A session local that is re-declared replaces the previous local of the same name, rather than
+/// shadowing it, so closures which captured the earlier local will observe the new value.
+pub fn compile_session_chunk<'gc>(
+    mc: MutationContext<'gc, '_>,
+    chunk_name: &[u8],
     chunk: &Chunk,
+    session_locals: &mut Vec<Box<[u8]>>,
+    options: &CompilerOptions,
 ) -> Result<FunctionProto<'gc>, CompilerError> {
-    let mut compiler = Compiler {
-        mutation_context: mc,
-        current_function: CompilerFunction::start(&[], true)?,
-        upper_functions: Vec::new(),
-    };
-    compiler.block(&chunk.block)?;
-    compiler.current_function.finish(mc)
+    let mut compiler = Compiler::new(mc, chunk_name, &chunk.interner, options);
+    compiler.session_locals = Some(session_locals.clone());
+    compiler.written_names = Some(written_names(chunk));
+    let proto = compiler.compile(&[], true, |compiler| {
+        let session = compiler
+            .current_function
+            .register_allocator
+            .push(1)
+            .ok_or_else(|| compiler.registers_error())?;
+        compiler.current_function.fixed_params = 1;
+        compiler
+            .current_function
+            .declare_local(SESSION_TABLE, session);
+        compiler.session_block(&chunk.block)
+    })?;
+    *session_locals = compiler.session_locals.unwrap();
+    Ok(proto)
+}
+
+// Returns true if the two names differ by exactly one substituted, inserted, deleted or adjacent
+// transposed character.  Names shorter than three characters are too likely to be similar by chance,
+// so they never match.
+fn is_single_typo(a: &[u8], b: &[u8]) -> bool {
+    if a.len() < 3 || b.len() < 3 || a == b {
+        return false;
+    }
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    if a.len() == b.len() {
+        a[1..] == b[1..] || (a.len() >= 2 && a[0] == b[1] && a[1] == b[0] && a[2..] == b[2..])
+    } else if a.len() == b.len() + 1 {
+        a[1..] == *b
+    } else if b.len() == a.len() + 1 {
+        b[1..] == *a
+    } else {
+        false
+    }
 }
 
+// The name of the hidden parameter holding the session table in session chunks
+const SESSION_TABLE: Symbol = Symbol::HIDDEN;
+
+// The maximum number of array entries in a table constructor which are kept in registers before
+// they are set in the table
+const FIELDS_PER_FLUSH: u8 = 50;
+
+// The deepest that statements and expressions may be nested.  The parser has the same limit, so
+// this is only reached by syntax trees which were not produced by the parser.
+const MAX_RECURSION: usize = 200;
+
+// The fewest conditions in an if statement which will be compiled to a Switch rather than a chain
+// of comparisons
+const SWITCH_MIN_CASES: usize = 3;
+
+// How far a jump may be from its target before a statement starts by emitting a jump island.  This
+// is half the range of a jump offset, leaving room for the statement which follows the island.
+const JUMP_ISLAND_DISTANCE: usize = 1 << 14;
+
 struct Compiler<'gc, 'a> {
     mutation_context: MutationContext<'gc, 'a>,
-    current_function: CompilerFunction<'gc, 'a>,
-    upper_functions: Vec<CompilerFunction<'gc, 'a>>,
+    chunk_name: String<'gc>,
+    interner: &'a Interner,
+    options: &'a CompilerOptions,
+    current_function: CompilerFunction<'gc>,
+    upper_functions: Vec<CompilerFunction<'gc>>,
+    // The names of the locals stored in the session table, only present when compiling a session
+    // chunk
+    session_locals: Option<Vec<Box<[u8]>>>,
+    // Every name which is assigned to anywhere in the chunk, used to find locals which always hold
+    // their initial constant value.  If this is not present, no locals are treated as constant.
+    written_names: Option<HashSet<Symbol>>,
+    // Warnings produced so far, only present if warnings were requested
+    warnings: Option<Vec<CompilerWarning>>,
+    // The number of statements and expressions currently being compiled inside of each other
+    recursion_depth: usize,
 }
 
 #[derive(Default)]
-struct CompilerFunction<'gc, 'a> {
+struct CompilerFunction<'gc> {
     constants: Vec<Value<'gc>>,
-    constant_table: HashMap<ConstantValue<'gc>, ConstantIndex16>,
+    constant_table: HashMap<ConstantKey<'gc>, ConstantIndex16>,
 
-    upvalues: Vec<(&'a [u8], UpValueDescriptor)>,
+    upvalues: Vec<(Symbol, UpValueDescriptor)>,
     prototypes: Vec<FunctionProto<'gc>>,
 
     register_allocator: RegisterAllocator,
 
     has_varargs: bool,
     fixed_params: u8,
-    locals: Vec<(&'a [u8], RegisterIndex)>,
+    locals: Vec<(Symbol, RegisterIndex)>,
+    // Every local declared in the function in order of declaration, including those no longer in
+    // scope
+    local_variables: Vec<LocalDescriptor>,
+    // Locals which are initialized with a constant and never assigned to, reads of which are
+    // replaced with the constant itself.
+    constant_locals: HashMap<RegisterIndex, Value<'gc>>,
 
     blocks: Vec<BlockDescriptor>,
     unique_jump_id: u64,
-    jump_targets: Vec<JumpTarget<'a>>,
-    pending_jumps: Vec<PendingJump<'a>>,
+    jump_targets: Vec<JumpTarget>,
+    pending_jumps: Vec<PendingJump>,
+    // The instruction of the most recently placed jump target, later instructions are never the
+    // destination of a forward jump
+    last_jump_target: Option<usize>,
+
+    // Registers holding globals which are read more than once in the statement being compiled
+    global_cache: Vec<CachedGlobal>,
+    // How many short circuit operands which may not be evaluated are currently being compiled
+    conditional_depth: usize,
 
     opcodes: Vec<OpCode>,
+    // The index of the first opcode generated for each line, in increasing opcode order
+    opcode_lines: Vec<(usize, LineNumber)>,
+    switches: Vec<Table<'gc>>,
 }
 
 #[derive(Debug)]
@@ -108,9 +452,13 @@ enum ExprDescriptor<'gc, 'a> {
     Value(Value<'gc>),
     VarArgs,
     Not(Box<ExprDescriptor<'gc, 'a>>),
+    // Arguments are not compiled until the call itself, so that they are evaluated after the
+    // function is placed at the top of the stack.  For a method call, `func` is the table that the
+    // method is looked up in.
     FunctionCall {
         func: Box<ExprDescriptor<'gc, 'a>>,
-        args: Vec<ExprDescriptor<'gc, 'a>>,
+        method: Option<Symbol>,
+        args: &'a [Expression],
     },
     Comparison {
         left: Box<ExprDescriptor<'gc, 'a>>,
@@ -135,17 +483,55 @@ enum ExprDestination {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum JumpLabel<'a> {
+enum JumpLabel {
     Unique(u64),
-    Named(&'a [u8]),
+    Named(Symbol),
     Break,
 }
 
 #[derive(Debug)]
-enum VariableDescriptor<'a> {
+enum VariableDescriptor {
     Local(RegisterIndex),
     UpValue(UpValueIndex),
-    Global(&'a [u8]),
+    Global(Symbol),
+    SessionLocal(Symbol),
+}
+
+#[derive(Debug, Copy, Clone)]
+struct LocalDescriptor {
+    name: Symbol,
+    register: RegisterIndex,
+    start: usize,
+    // The opcode at which the local goes out of scope, not present while the local is still in
+    // scope
+    end: Option<usize>,
+    // The line the local was declared on, if known
+    line: Option<LineNumber>,
+    // Whether the local is ever read, either directly or as an upvalue
+    read: bool,
+    // Whether a warning should be produced if the local is never read.  Parameters and hidden
+    // locals are never reported.
+    report_unused: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct CachedGlobal {
+    name: Symbol,
+    register: RegisterIndex,
+    state: CachedGlobalState,
+}
+
+// The register of a cached global is written at most once per statement, so that every expression
+// which refers to the register sees the same value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CachedGlobalState {
+    // The global has not been read outside of any short circuit operand yet
+    Empty,
+    // The register holds the current value of the global
+    Loaded,
+    // Something which may change the value of a global was compiled after the global was loaded,
+    // so every later read must look up the global again
+    Stale,
 }
 
 #[derive(Debug)]
@@ -161,8 +547,8 @@ struct BlockDescriptor {
 }
 
 #[derive(Debug, Copy, Clone)]
-struct JumpTarget<'a> {
-    label: JumpLabel<'a>,
+struct JumpTarget {
+    label: JumpLabel,
     // The target instruction that will be jumped to
     instruction: usize,
     // The valid local variables in scope at the target location
@@ -172,8 +558,8 @@ struct JumpTarget<'a> {
 }
 
 #[derive(Debug, Copy, Clone)]
-struct PendingJump<'a> {
-    target: JumpLabel<'a>,
+struct PendingJump {
+    target: JumpLabel,
     // The index of the placeholder jump instruction
     instruction: usize,
     // These are the expected block index and stack top *after* the jump takes place.  These start
@@ -183,10 +569,62 @@ struct PendingJump<'a> {
     stack_top: u8,
     // Whether there are any upvalues that will go out of scope when the jump takes place.
     close_upvalues: bool,
+    // The line of the jump statement, if known
+    line: Option<LineNumber>,
 }
 
 impl<'gc, 'a> Compiler<'gc, 'a> {
-    fn block(&mut self, block: &'a Block) -> Result<(), CompilerError> {
+    fn new(
+        mc: MutationContext<'gc, 'a>,
+        chunk_name: &[u8],
+        interner: &'a Interner,
+        options: &'a CompilerOptions,
+    ) -> Compiler<'gc, 'a> {
+        Compiler {
+            mutation_context: mc,
+            chunk_name: String::new(mc, chunk_name),
+            interner,
+            options,
+            current_function: CompilerFunction::default(),
+            upper_functions: Vec::new(),
+            session_locals: None,
+            written_names: None,
+            warnings: None,
+            recursion_depth: 0,
+        }
+    }
+
+    // Compiles the main function of a chunk with the given parameters, whose body is produced by
+    // `body`.  Any error is located at the line being compiled when it occurred.
+    fn compile(
+        &mut self,
+        parameters: &[Symbol],
+        has_varargs: bool,
+        body: impl FnOnce(&mut Self) -> Result<(), CompilerErrorKind>,
+    ) -> Result<FunctionProto<'gc>, CompilerError> {
+        let res =
+            CompilerFunction::start(parameters, has_varargs, self.options).and_then(|function| {
+                self.current_function = function;
+                body(self)
+            });
+        let line = self.current_line();
+        res.and_then(|()| {
+            mem::take(&mut self.current_function).finish(
+                self.mutation_context,
+                self.interner,
+                self.chunk_name,
+                self.options,
+            )
+        })
+        .map_err(|kind| CompilerError {
+            chunk_name: std::string::String::from_utf8_lossy(self.chunk_name.as_bytes())
+                .into_owned(),
+            line: kind.line().or(line),
+            kind,
+        })
+    }
+
+    fn block(&mut self, block: &'a Block) -> Result<(), CompilerErrorKind> {
         self.enter_block();
         self.block_statements(block)?;
         self.exit_block()
@@ -200,13 +638,23 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         });
     }
 
-    fn exit_block(&mut self) -> Result<(), CompilerError> {
+    fn exit_block(&mut self) -> Result<(), CompilerErrorKind> {
         let last_block = self.current_function.blocks.pop().unwrap();
 
         while let Some((_, last)) = self.current_function.locals.last() {
             if last.0 >= last_block.stack_bottom {
                 self.current_function.register_allocator.free(*last);
-                self.current_function.locals.pop();
+                self.current_function.constant_locals.remove(last);
+                let local = self.current_function.pop_local().unwrap();
+                if local.report_unused
+                    && !local.read
+                    && !self.interner.name(local.name).starts_with(b"_")
+                {
+                    self.warn(
+                        local.line,
+                        CompilerWarningKind::UnusedLocal(self.name_string(local.name)),
+                    );
+                }
             } else {
                 break;
             }
@@ -220,7 +668,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 offset: 0,
                 close_upvalues: cast(last_block.stack_bottom)
                     .and_then(Opt254::try_some)
-                    .ok_or(CompilerError::Registers)?,
+                    .ok_or_else(|| self.registers_error())?,
             });
         }
 
@@ -243,17 +691,17 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(())
     }
 
-    // Handles the statements inside a block according to the trailing labels rule.  In most blocks,
-    // trailing labels are treated specially by Lua.  All labels at the end of a block are treated
-    // as though they are in a separate scope from the rest of the block, to make it legal to jump
-    // to the end of the block over local variable scope.  This is logically equivalent to an extra
-    // `do end` around the inside of the block not including the trailing labels.
-    fn block_statements(&mut self, block: &'a Block) -> Result<(), CompilerError> {
+    // Compiles the statements inside a block according to the trailing labels rule, and returns
+    // whether the end of the block is reachable.  In most blocks, trailing labels are treated
+    // specially by Lua.  All labels at the end of a block are treated as though they are in a
+    // separate scope from the rest of the block, to make it legal to jump to the end of the block
+    // over local variable scope.  This is logically equivalent to an extra `do end` around the
+    // inside of the block not including the trailing labels.
+    fn block_statements(&mut self, block: &'a Block) -> Result<bool, CompilerErrorKind> {
         if let Some(return_statement) = &block.return_statement {
-            for statement in &block.statements {
-                self.statement(statement)?;
-            }
+            self.reachable_statements(&block.statements, &block.lines)?;
             self.return_statement(return_statement)?;
+            Ok(false)
         } else {
             let mut last = block.statements.len();
             for i in (0..block.statements.len()).rev() {
@@ -266,20 +714,81 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             let trailing_labels = &block.statements[last..block.statements.len()];
 
             self.enter_block();
-            for i in 0..block.statements.len() - trailing_labels.len() {
-                self.statement(&block.statements[i])?;
-            }
+            let reachable =
+                self.reachable_statements(&block.statements[..last], &block.lines[..last])?;
             self.exit_block()?;
 
-            for label_statement in trailing_labels {
+            for (label_statement, &line) in trailing_labels.iter().zip(&block.lines[last..]) {
+                self.set_line(line);
                 self.statement(&label_statement)?;
             }
+            Ok(reachable || !trailing_labels.is_empty())
         }
-        Ok(())
     }
 
-    fn statement(&mut self, statement: &'a Statement) -> Result<(), CompilerError> {
-        match statement {
+    // Compiles a list of statements, skipping any which follow a `break`, a `goto` or a block ending
+    // in `return` and so can never execute.  A label makes the statements after it reachable again,
+    // and local declarations are always compiled so that the scope of every local is unchanged.
+    // Returns whether the end of the statements is reachable.
+    fn reachable_statements(
+        &mut self,
+        statements: &'a [Statement],
+        lines: &[LineNumber],
+    ) -> Result<bool, CompilerErrorKind> {
+        let mut reachable = true;
+        for (statement, &line) in statements.iter().zip(lines) {
+            match statement {
+                Statement::Label(_) => reachable = true,
+                Statement::LocalStatement(_) | Statement::LocalFunction(_) => {}
+                _ if !reachable => continue,
+                _ => {}
+            }
+
+            self.set_line(line);
+            match statement {
+                Statement::Break | Statement::Goto(_) => {
+                    self.statement(statement)?;
+                    reachable = false;
+                }
+                Statement::Do(block) => {
+                    self.enter_block();
+                    reachable = self.block_statements(block)?;
+                    self.exit_block()?;
+                }
+                statement => self.statement(statement)?,
+            }
+        }
+        Ok(reachable)
+    }
+
+    // Compiles the top-level block of a session chunk, where local statements declare session locals
+    // rather than register locals.  Since session locals do not occupy registers, there is no need
+    // for the trailing labels rule.
+    fn session_block(&mut self, block: &'a Block) -> Result<(), CompilerErrorKind> {
+        self.enter_block();
+        for (statement, &line) in block.statements.iter().zip(&block.lines) {
+            self.set_line(line);
+            match statement {
+                Statement::LocalStatement(local_statement) => {
+                    self.session_local_statement(local_statement)?
+                }
+                Statement::LocalFunction(local_function) => {
+                    self.session_local_function(local_function)?
+                }
+                statement => self.statement(statement)?,
+            }
+        }
+        if let Some(return_statement) = &block.return_statement {
+            self.return_statement(return_statement)?;
+        }
+        self.exit_block()
+    }
+
+    fn statement(&mut self, statement: &'a Statement) -> Result<(), CompilerErrorKind> {
+        self.enter_recursion()?;
+        self.jump_island()?;
+        self.cache_statement_globals(statement)?;
+        let res = match statement {
             Statement::If(if_statement) => self.if_statement(if_statement),
             Statement::While(while_statement) => self.while_statement(while_statement),
             Statement::Do(block) => self.block(block),
@@ -289,20 +798,35 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             Statement::LocalFunction(local_function) => self.local_function(local_function),
             Statement::LocalStatement(local_statement) => self.local_statement(local_statement),
             Statement::Label(label_statement) => {
-                self.jump_target(JumpLabel::Named(&label_statement.name))
+                self.jump_target(JumpLabel::Named(label_statement.name))
             }
             Statement::Break => self.jump(JumpLabel::Break),
-            Statement::Goto(goto_statement) => self.jump(JumpLabel::Named(&goto_statement.name)),
+            Statement::Goto(goto_statement) => self.jump(JumpLabel::Named(goto_statement.name)),
             Statement::FunctionCall(function_call) => self.function_call(function_call),
             Statement::Assignment(assignment) => self.assignment(assignment),
-        }
+        };
+        self.clear_global_cache();
+        self.recursion_depth -= 1;
+        res
     }
 
     fn return_statement(
         &mut self,
         return_statement: &'a ReturnStatement,
-    ) -> Result<(), CompilerError> {
-        let ret_len = return_statement.returns.len();
+    ) -> Result<(), CompilerErrorKind> {
+        self.set_line(return_statement.line);
+        let mut reads = HashMap::new();
+        for expression in &return_statement.returns {
+            self.expression_global_reads(expression, &mut reads);
+        }
+        self.cache_globals(reads)?;
+        let res = self.return_values(&return_statement.returns);
+        self.clear_global_cache();
+        res
+    }
+
+    fn return_values(&mut self, returns: &'a [Expression]) -> Result<(), CompilerErrorKind> {
+        let ret_len = returns.len();
 
         if ret_len == 0 {
             self.current_function.opcodes.push(OpCode::Return {
@@ -311,23 +835,44 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             });
         } else {
             let ret_start = cast(self.current_function.register_allocator.stack_top())
-                .ok_or(CompilerError::Registers)?;
+                .ok_or_else(|| self.registers_error())?;
 
-            for i in 0..ret_len - 1 {
-                let expr = self.expression(&return_statement.returns[i])?;
+            for ret in &returns[..ret_len - 1] {
+                let expr = self.expression(ret)?;
                 self.expr_discharge(expr, ExprDestination::PushNew)?;
             }
 
-            let ret_count = match self.expression(&return_statement.returns[ret_len - 1])? {
-                ExprDescriptor::FunctionCall { func, args } => {
-                    self.expr_function_call(*func, args, VarCount::variable())?;
+            let ret_count = match self.expression(&returns[ret_len - 1])? {
+                // A single returned function call becomes a tail call, which replaces the current
+                // function's frame rather than returning to it.
+                ExprDescriptor::FunctionCall { func, method, args } if ret_len == 1 => {
+                    let (top_reg, arg_count) = self.expr_push_call(*func, method, args)?;
+                    self.current_function.opcodes.push(OpCode::TailCall {
+                        func: top_reg,
+                        args: arg_count,
+                    });
+                    self.current_function.register_allocator.pop_to(ret_start);
+                    return Ok(());
+                }
+                ExprDescriptor::FunctionCall { func, method, args } => {
+                    self.expr_function_call(*func, method, args, VarCount::variable())?;
+                    VarCount::variable()
+                }
+                ExprDescriptor::VarArgs => {
+                    self.current_function.opcodes.push(OpCode::VarArgs {
+                        dest: RegisterIndex(
+                            cast(self.current_function.register_allocator.stack_top())
+                                .ok_or_else(|| self.registers_error())?,
+                        ),
+                        count: VarCount::variable(),
+                    });
                     VarCount::variable()
                 }
                 expr => {
                     self.expr_discharge(expr, ExprDestination::PushNew)?;
                     cast(ret_len)
                         .and_then(VarCount::try_constant)
-                        .ok_or(CompilerError::Registers)?
+                        .ok_or_else(|| self.registers_error())?
                 }
             };
 
@@ -343,7 +888,11 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(())
     }
 
-    fn if_statement(&mut self, if_statement: &'a IfStatement) -> Result<(), CompilerError> {
+    fn if_statement(&mut self, if_statement: &'a IfStatement) -> Result<(), CompilerErrorKind> {
+        if let Some((value, cases)) = self.switch_cases(if_statement)? {
+            return self.switch_statement(if_statement, value, cases);
+        }
+
         let end_label = self.unique_jump_label();
         let mut next_label = self.unique_jump_label();
 
@@ -354,9 +903,16 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             self.jump_target(next_label)?;
             next_label = self.unique_jump_label();
 
-            let if_expr = self.expression(if_expr)?;
-            self.expr_test(if_expr, true)?;
-            self.jump(next_label)?;
+            match self.expression(if_expr)? {
+                // A constant condition does not need to be tested, the block is either always or
+                // never entered.
+                ExprDescriptor::Value(value) if value.as_bool() => {}
+                ExprDescriptor::Value(_) => self.jump(next_label)?,
+                if_expr => {
+                    self.expr_test(if_expr, true)?;
+                    self.jump(next_label)?;
+                }
+            }
 
             self.enter_block();
             self.block_statements(block)?;
@@ -376,7 +932,107 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(())
     }
 
-    fn for_statement(&mut self, for_statement: &'a ForStatement) -> Result<(), CompilerError> {
+    // If every condition of an if statement compares the same local against a different integer or
+    // string constant, and there are enough of them, returns the local's register and the constant
+    // of each condition.
+    fn switch_cases(
+        &mut self,
+        if_statement: &'a IfStatement,
+    ) -> Result<Option<(RegisterIndex, Vec<Value<'gc>>)>, CompilerErrorKind> {
+        if self.options.optimization_level() < OptimizationLevel::O1
+            || if_statement.else_if_parts.len() + 1 < SWITCH_MIN_CASES
+            || self.current_function.switches.len() > u8::MAX as usize
+        {
+            return Ok(None);
+        }
+
+        let mut name = None;
+        let mut cases = Vec::new();
+        for (condition, _) in iter::once(&if_statement.if_part).chain(&if_statement.else_if_parts) {
+            match switch_case(condition) {
+                Some((case_name, constant)) if name.is_none() || name == Some(case_name) => {
+                    name = Some(case_name);
+                    cases.push(match *constant {
+                        SimpleExpression::Integer(i) => Value::Integer(i),
+                        SimpleExpression::String(ref s) => {
+                            Value::String(String::new(self.mutation_context, s))
+                        }
+                        _ => unreachable!(),
+                    });
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        match self.find_variable(name.unwrap())? {
+            VariableDescriptor::Local(register)
+                if !self
+                    .current_function
+                    .constant_locals
+                    .contains_key(&register) =>
+            {
+                self.current_function.mark_read(register);
+                Ok(Some((register, cases)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Compiles an if statement whose conditions were matched by `switch_cases` to a Switch opcode,
+    // which jumps directly to the block of the matching condition.
+    fn switch_statement(
+        &mut self,
+        if_statement: &'a IfStatement,
+        value: RegisterIndex,
+        cases: Vec<Value<'gc>>,
+    ) -> Result<(), CompilerErrorKind> {
+        // When a constant appears in more than one condition, only the first can ever match.
+        let table = Table::new(self.mutation_context);
+        for (i, &case) in cases.iter().enumerate() {
+            if table.get(case) == Value::Nil {
+                table
+                    .set(self.mutation_context, case, Value::Integer(i as i64 + 1))
+                    .expect("switch cases are never nil or NaN");
+            }
+        }
+        let table_index = SwitchIndex(self.current_function.switches.len() as u8);
+        self.current_function.switches.push(table);
+        self.current_function.opcodes.push(OpCode::Switch {
+            value,
+            table: table_index,
+        });
+
+        let end_label = self.unique_jump_label();
+        let default_label = self.unique_jump_label();
+        let case_labels: Vec<JumpLabel> = cases.iter().map(|_| self.unique_jump_label()).collect();
+        self.jump(default_label)?;
+        for &case_label in &case_labels {
+            self.jump(case_label)?;
+        }
+
+        for (i, (_, block)) in iter::once(&if_statement.if_part)
+            .chain(&if_statement.else_if_parts)
+            .enumerate()
+        {
+            self.jump_target(case_labels[i])?;
+            self.enter_block();
+            self.block_statements(block)?;
+            if i != if_statement.else_if_parts.len() || if_statement.else_part.is_some() {
+                self.jump(end_label)?;
+            }
+            self.exit_block()?;
+        }
+
+        self.jump_target(default_label)?;
+        if let Some(else_block) = &if_statement.else_part {
+            self.block(else_block)?;
+        }
+
+        self.jump_target(end_label)?;
+
+        Ok(())
+    }
+    fn for_statement(&mut self, for_statement: &'a ForStatement) -> Result<(), CompilerErrorKind> {
         match for_statement {
             ForStatement::Numeric {
                 name,
@@ -385,10 +1041,19 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 step,
                 body,
             } => {
+                fn is_integer(expr: &ExprDescriptor) -> bool {
+                    match expr {
+                        ExprDescriptor::Value(Value::Integer(_)) => true,
+                        _ => false,
+                    }
+                }
+
                 let initial = self.expression(initial)?;
+                let mut integer_loop = is_integer(&initial);
                 let base = self.expr_discharge(initial, ExprDestination::PushNew)?;
 
                 let limit = self.expression(limit)?;
+                integer_loop &= is_integer(&limit);
                 self.expr_discharge(limit, ExprDestination::PushNew)?;
 
                 let step = if let Some(step) = step {
@@ -396,46 +1061,62 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 } else {
                     ExprDescriptor::Value(Value::Integer(1))
                 };
+                integer_loop &= is_integer(&step);
+                if let ExprDescriptor::Value(Value::Integer(0)) = step {
+                    integer_loop = false;
+                }
                 self.expr_discharge(step, ExprDestination::PushNew)?;
 
+                // When every loop parameter is a non-zero integer constant, the loop counter can
+                // never become a float and the VM can skip checking the parameter types.
                 let for_prep_index = self.current_function.opcodes.len();
-                self.current_function
-                    .opcodes
-                    .push(OpCode::NumericForPrep { base, jump: 0 });
+                self.current_function.opcodes.push(if integer_loop {
+                    OpCode::IntegerForPrep { base, jump: 0 }
+                } else {
+                    OpCode::NumericForPrep { base, jump: 0 }
+                });
+
+                // The loop jumps are resolved through labels so that they can pass through jump
+                // islands.  IntegerForPrep skips the loop entirely rather than jumping to the loop
+                // opcode.
+                let loop_label = self.unique_jump_label();
+                let body_label = self.unique_jump_label();
 
                 self.enter_block();
+                self.push_pending_jump(
+                    if integer_loop {
+                        JumpLabel::Break
+                    } else {
+                        loop_label
+                    },
+                    for_prep_index,
+                );
+                self.jump_target(body_label)?;
                 self.enter_block();
 
                 let loop_var = self
                     .current_function
                     .register_allocator
                     .push(1)
-                    .ok_or(CompilerError::Registers)?;
-                self.current_function.locals.push((name, loop_var));
+                    .ok_or_else(|| self.registers_error())?;
+                self.declare_local(*name, loop_var);
 
                 self.block_statements(body)?;
                 self.exit_block()?;
 
-                let for_loop_index = self.current_function.opcodes.len();
-                self.current_function.opcodes.push(OpCode::NumericForLoop {
-                    base: RegisterIndex(base.0),
-                    jump: jump_offset(for_loop_index, for_prep_index + 1)
-                        .ok_or(CompilerError::JumpOverflow)?,
-                });
-                match &mut self.current_function.opcodes[for_prep_index] {
-                    OpCode::NumericForPrep {
-                        base: prep_base,
-                        jump,
-                    } => {
-                        assert!(
-                            *prep_base == base && *jump == 0,
-                            "instruction is not placeholder NumericForPrep"
-                        );
-                        *jump = jump_offset(for_prep_index, for_loop_index)
-                            .ok_or(CompilerError::JumpOverflow)?;
+                self.jump_target(loop_label)?;
+                let loop_jump = self.label_offset(body_label)?;
+                self.current_function.opcodes.push(if integer_loop {
+                    OpCode::IntegerForLoop {
+                        base,
+                        jump: loop_jump,
                     }
-                    _ => panic!("instruction is not placeholder NumericForPrep"),
-                }
+                } else {
+                    OpCode::NumericForLoop {
+                        base,
+                        jump: loop_jump,
+                    }
+                });
 
                 self.jump_target(JumpLabel::Break)?;
                 self.exit_block()?;
@@ -449,6 +1130,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 body,
             } => {
                 let loop_label = self.unique_jump_label();
+                let body_label = self.unique_jump_label();
 
                 assert!(arguments.len() >= 1);
                 let base = if arguments.len() == 1 {
@@ -476,35 +1158,32 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 };
 
                 self.enter_block();
+                self.jump(loop_label)?;
+                self.jump_target(body_label)?;
                 self.enter_block();
 
-                let name_count = cast(names.len()).ok_or(CompilerError::Registers)?;
+                let name_count = cast(names.len()).ok_or_else(|| self.registers_error())?;
                 let names_reg = self
                     .current_function
                     .register_allocator
                     .push(name_count)
-                    .ok_or(CompilerError::Registers)?;
+                    .ok_or_else(|| self.registers_error())?;
                 for i in 0..name_count {
-                    self.current_function
-                        .locals
-                        .push((&names[i as usize], RegisterIndex(names_reg.0 + i)));
+                    self.declare_local(names[i as usize], RegisterIndex(names_reg.0 + i));
                 }
 
-                self.jump(loop_label)?;
-
-                let start_inst = self.current_function.opcodes.len();
                 self.block_statements(body)?;
                 self.exit_block()?;
 
                 self.jump_target(loop_label)?;
                 self.current_function.opcodes.push(OpCode::GenericForCall {
                     base,
-                    var_count: cast(names.len()).ok_or(CompilerError::Registers)?,
+                    var_count: cast(names.len()).ok_or_else(|| self.registers_error())?,
                 });
-                let loop_inst = self.current_function.opcodes.len();
+                let loop_jump = self.label_offset(body_label)?;
                 self.current_function.opcodes.push(OpCode::GenericForLoop {
                     base: RegisterIndex(base.0 + 2),
-                    jump: jump_offset(loop_inst, start_inst).ok_or(CompilerError::JumpOverflow)?,
+                    jump: loop_jump,
                 });
 
                 self.jump_target(JumpLabel::Break)?;
@@ -519,7 +1198,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn while_statement(
         &mut self,
         while_statement: &'a WhileStatement,
-    ) -> Result<(), CompilerError> {
+    ) -> Result<(), CompilerErrorKind> {
         let start_label = self.unique_jump_label();
         let end_label = self.unique_jump_label();
 
@@ -543,7 +1222,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn repeat_statement(
         &mut self,
         repeat_statement: &'a RepeatStatement,
-    ) -> Result<(), CompilerError> {
+    ) -> Result<(), CompilerErrorKind> {
         let start_label = self.unique_jump_label();
 
         self.enter_block();
@@ -553,7 +1232,9 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
         // `repeat` statements do not follow the trailing label rule, because the variables inside
         // the block are in scope for the `until` condition at the end.
-        for statement in &repeat_statement.body.statements {
+        let body = &repeat_statement.body;
+        for (statement, &line) in body.statements.iter().zip(&body.lines) {
+            self.set_line(line);
             self.statement(statement)?;
         }
         if let Some(return_statement) = &repeat_statement.body.return_statement {
@@ -574,27 +1255,31 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn function_statement(
         &mut self,
         function_statement: &'a FunctionStatement,
-    ) -> Result<(), CompilerError> {
-        if !function_statement.name.fields.is_empty() {
-            unimplemented!("no function name fields support");
-        }
-        if function_statement.name.method.is_some() {
-            unimplemented!("no method support");
-        }
-
-        let proto = self.new_prototype(&function_statement.definition)?;
-
-        let mut env = self.get_environment()?;
+    ) -> Result<(), CompilerErrorKind> {
+        let name = &function_statement.name;
+        let proto = self.new_prototype(&function_statement.definition, name.method.is_some())?;
+
+        // For a name like `a.b.c` or `a.b:c`, look up `a` as a variable and index through every
+        // field before `c`, which is the key that the new function is assigned to.
+        let (mut env, key) = match (name.method, name.fields.split_last()) {
+            (None, None) => {
+                self.check_global_assignment(name.name)?;
+                self.check_global_typo(name.name);
+                (self.get_environment()?, name.name)
+            }
+            (Some(method), _) => (self.function_name_table(name.name, &name.fields)?, method),
+            (None, Some((&last, fields))) => (self.function_name_table(name.name, fields)?, last),
+        };
         let mut name = ExprDescriptor::Value(Value::String(String::new(
             self.mutation_context,
-            &*function_statement.name.name,
+            self.interner.name(key),
         )));
 
         let dest = self
             .current_function
             .register_allocator
             .allocate()
-            .ok_or(CompilerError::Registers)?;
+            .ok_or_else(|| self.registers_error())?;
         self.current_function
             .opcodes
             .push(OpCode::Closure { proto, dest });
@@ -612,27 +1297,46 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(())
     }
 
+    // Look up the table which holds a function named with a path of fields, like `a` in `a.b` or
+    // `a.b` in `a.b.c`
+    fn function_name_table(
+        &mut self,
+        name: Symbol,
+        fields: &[Symbol],
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        let mut table = self.name_expression(name)?;
+        for &field in fields {
+            let mut key = ExprDescriptor::Value(Value::String(String::new(
+                self.mutation_context,
+                self.interner.name(field),
+            )));
+            let next = self.get_table(&mut table, &mut key)?;
+            self.expr_discard(table)?;
+            self.expr_discard(key)?;
+            table = next;
+        }
+        Ok(table)
+    }
+
     fn local_statement(
         &mut self,
         local_statement: &'a LocalStatement,
-    ) -> Result<(), CompilerError> {
+    ) -> Result<(), CompilerErrorKind> {
         let name_len = local_statement.names.len();
         let val_len = local_statement.values.len();
 
         if local_statement.values.is_empty() {
-            let count = cast(name_len).ok_or(CompilerError::Registers)?;
+            let count = cast(name_len).ok_or_else(|| self.registers_error())?;
             let dest = self
                 .current_function
                 .register_allocator
                 .push(count)
-                .ok_or(CompilerError::Registers)?;
+                .ok_or_else(|| self.registers_error())?;
             self.current_function
                 .opcodes
                 .push(OpCode::LoadNil { dest, count });
             for i in 0..name_len {
-                self.current_function
-                    .locals
-                    .push((&local_statement.names[i], RegisterIndex(dest.0 + i as u8)));
+                self.declare_local(local_statement.names[i], RegisterIndex(dest.0 + i as u8));
             }
         } else {
             for i in 0..val_len {
@@ -642,20 +1346,26 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     self.expr_discard(expr)?;
                 } else if i == val_len - 1 {
                     let names_left =
-                        cast(1 + name_len - val_len).ok_or(CompilerError::Registers)?;
+                        cast(1 + name_len - val_len).ok_or_else(|| self.registers_error())?;
+                    let constant = self.local_constant(local_statement.names[i], &expr);
                     let dest = self.expr_push_count(expr, names_left)?;
+                    if let (Some(constant), 1) = (constant, names_left) {
+                        self.current_function.constant_locals.insert(dest, constant);
+                    }
 
                     for j in 0..names_left {
-                        self.current_function.locals.push((
-                            &local_statement.names[val_len - 1 + j as usize],
+                        self.declare_local(
+                            local_statement.names[val_len - 1 + j as usize],
                             RegisterIndex(dest.0 + j),
-                        ));
+                        );
                     }
                 } else {
+                    let constant = self.local_constant(local_statement.names[i], &expr);
                     let reg = self.expr_discharge(expr, ExprDestination::PushNew)?;
-                    self.current_function
-                        .locals
-                        .push((&local_statement.names[i], reg));
+                    if let Some(constant) = constant {
+                        self.current_function.constant_locals.insert(reg, constant);
+                    }
+                    self.declare_local(local_statement.names[i], reg);
                 }
             }
         }
@@ -663,100 +1373,292 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(())
     }
 
-    fn function_call(
-        &mut self,
-        function_call: &'a FunctionCallStatement,
-    ) -> Result<(), CompilerError> {
-        let func_expr = self.suffixed_expression(&function_call.head)?;
-        match &function_call.call {
-            CallSuffix::Function(args) => {
-                let arg_exprs = args
-                    .iter()
-                    .map(|arg| self.expression(arg))
-                    .collect::<Result<_, CompilerError>>()?;
-                self.expr_function_call(func_expr, arg_exprs, VarCount::constant(0))?;
+    // Returns the constant value of a local being declared, if it is initialized with a constant
+    // and is never assigned to.
+    fn local_constant(&self, name: Symbol, expr: &ExprDescriptor<'gc, 'a>) -> Option<Value<'gc>> {
+        if self.options.optimization_level() < OptimizationLevel::O2 {
+            return None;
+        }
+        match (expr, &self.written_names) {
+            (&ExprDescriptor::Value(value), Some(written_names))
+                if !written_names.contains(&name) =>
+            {
+                Some(value)
             }
-            CallSuffix::Method(_, _) => unimplemented!("method call unsupported"),
+            _ => None,
         }
-        Ok(())
     }
 
-    fn assignment(&mut self, assignment: &'a AssignmentStatement) -> Result<(), CompilerError> {
-        for (i, target) in assignment.targets.iter().enumerate() {
-            let mut expr = if i < assignment.values.len() {
-                self.expression(&assignment.values[i])?
-            } else {
-                ExprDescriptor::Value(Value::Nil)
+    fn session_local_statement(
+        &mut self,
+        local_statement: &'a LocalStatement,
+    ) -> Result<(), CompilerErrorKind> {
+        // Evaluate the values into new register locals exactly like a normal local statement, then
+        // move each of them into the session table.
+        let first_local = self.current_function.locals.len();
+        self.local_statement(local_statement)?;
+
+        for i in first_local..self.current_function.locals.len() {
+            let (name, register) = self.current_function.locals[i];
+            let mut session = self.get_session()?;
+            let mut key = ExprDescriptor::Value(Value::String(String::new(
+                self.mutation_context,
+                self.interner.name(name),
+            )));
+            let mut value = ExprDescriptor::Register {
+                register,
+                is_temporary: false,
             };
+            self.set_table(&mut session, &mut key, &mut value)?;
+            self.expr_discard(session)?;
+            self.expr_discard(key)?;
+            self.expr_discard(value)?;
+        }
 
-            match target {
-                AssignmentTarget::Name(name) => match self.find_variable(name)? {
-                    VariableDescriptor::Local(dest) => {
-                        self.expr_discharge(expr, ExprDestination::Register(dest))?;
-                    }
-                    VariableDescriptor::UpValue(dest) => {
-                        let source = self.expr_any_register(&mut expr)?;
-                        self.current_function
-                            .opcodes
-                            .push(OpCode::SetUpValue { source, dest });
-                        self.expr_discard(expr)?;
-                    }
-                    VariableDescriptor::Global(name) => {
-                        let mut env = self.get_environment()?;
-                        let mut key = ExprDescriptor::Value(Value::String(String::new(
-                            self.mutation_context,
-                            name,
-                        )));
-                        self.set_table(&mut env, &mut key, &mut expr)?;
-                        self.expr_discard(env)?;
-                        self.expr_discard(key)?;
-                        self.expr_discard(expr)?;
-                    }
-                },
-
-                AssignmentTarget::Field(table, field) => {
-                    let mut table = self.suffixed_expression(table)?;
-                    let mut key = match field {
-                        FieldSuffix::Named(name) => ExprDescriptor::Value(Value::String(
-                            String::new(self.mutation_context, name),
-                        )),
-                        FieldSuffix::Indexed(idx) => self.expression(idx)?,
-                    };
-                    self.set_table(&mut table, &mut key, &mut expr)?;
-                    self.expr_discard(table)?;
-                    self.expr_discard(key)?;
-                    self.expr_discard(expr)?;
+        while self.current_function.locals.len() > first_local {
+            let local = self.current_function.pop_local().unwrap();
+            self.current_function
+                .register_allocator
+                .free(local.register);
+            self.current_function
+                .constant_locals
+                .remove(&local.register);
+            self.declare_session_local(local.name);
+        }
+
+        Ok(())
+    }
+
+    fn session_local_function(
+        &mut self,
+        local_function: &'a FunctionStatement,
+    ) -> Result<(), CompilerErrorKind> {
+        if !local_function.name.fields.is_empty() || local_function.name.method.is_some() {
+            return Err(CompilerErrorKind::LocalFunctionName(
+                self.name_string(local_function.name.name),
+            ));
+        }
+
+        // The local is in scope inside the function body, so declare it first
+        self.declare_session_local(local_function.name.name);
+        let proto = self.new_prototype(&local_function.definition, false)?;
+
+        let mut session = self.get_session()?;
+        let mut name = ExprDescriptor::Value(Value::String(String::new(
+            self.mutation_context,
+            self.interner.name(local_function.name.name),
+        )));
+
+        let dest = self
+            .current_function
+            .register_allocator
+            .allocate()
+            .ok_or_else(|| self.registers_error())?;
+        self.current_function
+            .opcodes
+            .push(OpCode::Closure { proto, dest });
+        let mut closure = ExprDescriptor::Register {
+            register: dest,
+            is_temporary: true,
+        };
+
+        self.set_table(&mut session, &mut name, &mut closure)?;
+
+        self.expr_discard(session)?;
+        self.expr_discard(name)?;
+        self.expr_discard(closure)?;
+
+        Ok(())
+    }
+
+    fn declare_session_local(&mut self, name: Symbol) {
+        let name = self.interner.name(name);
+        let session_locals = self.session_locals.as_mut().unwrap();
+        if !session_locals.iter().any(|l| &**l == name) {
+            session_locals.push(name.into());
+        }
+    }
+
+    fn function_call(
+        &mut self,
+        function_call: &'a FunctionCallStatement,
+    ) -> Result<(), CompilerErrorKind> {
+        let func_expr = self.suffixed_expression(&function_call.head)?;
+        let (method, args) = match &function_call.call {
+            CallSuffix::Function(args) => (None, args),
+            CallSuffix::Method(method, args) => (Some(*method), args),
+        };
+        self.expr_function_call(func_expr, method, args, VarCount::constant(0))?;
+        Ok(())
+    }
+
+    fn assignment(&mut self, assignment: &'a AssignmentStatement) -> Result<(), CompilerErrorKind> {
+        if let ([target], [value]) = (&assignment.targets[..], &assignment.values[..]) {
+            let expr = self.expression(value)?;
+            return match target {
+                AssignmentTarget::Name(name) => self.assign_name(*name, expr),
+                AssignmentTarget::Field(table, field) => {
+                    let table = self.suffixed_expression(table)?;
+                    let key = self.field_key(field)?;
+                    self.assign_field(table, key, expr)
                 }
+            };
+        }
+
+        let top = self.current_function.register_allocator.stack_top();
+
+        // Every expression in a multiple assignment is evaluated before any target is assigned, so
+        // the table and key of each field target are placed in registers ahead of the values.
+        let mut fields = Vec::new();
+        for target in &assignment.targets {
+            if let AssignmentTarget::Field(table, field) = target {
+                let table = self.suffixed_expression(table)?;
+                let table = self.expr_snapshot(table)?;
+                let key = self.field_key(field)?;
+                let key = self.expr_snapshot(key)?;
+                fields.push((table, key));
             }
         }
 
+        // The last value expands to fill all remaining targets, and any values without a target
+        // are evaluated and discarded.
+        let targets_len = assignment.targets.len();
+        let values_len = assignment.values.len();
+        let values_start = self.current_function.register_allocator.stack_top();
+        for (i, value) in assignment.values.iter().enumerate() {
+            let expr = self.expression(value)?;
+            if i >= targets_len {
+                self.expr_discard(expr)?;
+            } else if i == values_len - 1 {
+                let count = cast(targets_len - i).ok_or_else(|| self.registers_error())?;
+                self.expr_push_count(expr, count)?;
+            } else {
+                self.expr_discharge(expr, ExprDestination::PushNew)?;
+            }
+        }
+
+        let mut fields = fields.into_iter();
+        for (i, target) in assignment.targets.iter().enumerate() {
+            let expr = ExprDescriptor::Register {
+                register: RegisterIndex(values_start + i as u8),
+                is_temporary: false,
+            };
+            match target {
+                AssignmentTarget::Name(name) => self.assign_name(*name, expr)?,
+                AssignmentTarget::Field(_, _) => {
+                    let (table, key) = fields.next().unwrap();
+                    self.assign_field(table, key, expr)?;
+                }
+            }
+        }
+
+        self.current_function.register_allocator.pop_to(top);
+
         Ok(())
     }
 
+    fn assign_name(
+        &mut self,
+        name: Symbol,
+        mut expr: ExprDescriptor<'gc, 'a>,
+    ) -> Result<(), CompilerErrorKind> {
+        match self.find_variable(name)? {
+            VariableDescriptor::Local(dest) => {
+                self.expr_discharge(expr, ExprDestination::Register(dest))?;
+            }
+            VariableDescriptor::UpValue(dest) => {
+                let source = self.expr_any_register(&mut expr)?;
+                self.current_function
+                    .opcodes
+                    .push(OpCode::SetUpValue { source, dest });
+                self.invalidate_global_cache();
+                self.expr_discard(expr)?;
+            }
+            VariableDescriptor::Global(name) => {
+                self.check_global_assignment(name)?;
+                self.check_global_typo(name);
+                let env = self.get_environment()?;
+                let key = ExprDescriptor::Value(Value::String(String::new(
+                    self.mutation_context,
+                    self.interner.name(name),
+                )));
+                self.assign_field(env, key, expr)?;
+            }
+            VariableDescriptor::SessionLocal(name) => {
+                let session = self.get_session()?;
+                let key = ExprDescriptor::Value(Value::String(String::new(
+                    self.mutation_context,
+                    self.interner.name(name),
+                )));
+                self.assign_field(session, key, expr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn assign_field(
+        &mut self,
+        mut table: ExprDescriptor<'gc, 'a>,
+        mut key: ExprDescriptor<'gc, 'a>,
+        mut expr: ExprDescriptor<'gc, 'a>,
+    ) -> Result<(), CompilerErrorKind> {
+        self.set_table(&mut table, &mut key, &mut expr)?;
+        self.expr_discard(table)?;
+        self.expr_discard(key)?;
+        self.expr_discard(expr)?;
+        Ok(())
+    }
+
+    fn field_key(
+        &mut self,
+        field: &'a FieldSuffix,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        Ok(match field {
+            FieldSuffix::Named(name) => ExprDescriptor::Value(Value::String(String::new(
+                self.mutation_context,
+                self.interner.name(*name),
+            ))),
+            FieldSuffix::Indexed(idx) => self.expression(idx)?,
+        })
+    }
+
+    // Places a non-constant expression in a new register, so that its value is unaffected by
+    // later assignments. The register is not temporary, and must be freed by the caller.
+    fn expr_snapshot(
+        &mut self,
+        expr: ExprDescriptor<'gc, 'a>,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        Ok(match expr {
+            expr @ ExprDescriptor::Value(_) => expr,
+            expr => ExprDescriptor::Register {
+                register: self.expr_discharge(expr, ExprDestination::PushNew)?,
+                is_temporary: false,
+            },
+        })
+    }
+
     fn local_function(
         &mut self,
         local_function: &'a FunctionStatement,
-    ) -> Result<(), CompilerError> {
-        if !local_function.name.fields.is_empty() {
-            unimplemented!("no function name fields support");
-        }
-        if local_function.name.method.is_some() {
-            unimplemented!("no method support");
+    ) -> Result<(), CompilerErrorKind> {
+        if !local_function.name.fields.is_empty() || local_function.name.method.is_some() {
+            return Err(CompilerErrorKind::LocalFunctionName(
+                self.name_string(local_function.name.name),
+            ));
         }
 
-        let proto = self.new_prototype(&local_function.definition)?;
-
+        // The local is in scope inside the function body, so that the function may refer to itself
         let dest = self
             .current_function
             .register_allocator
             .push(1)
-            .ok_or(CompilerError::Registers)?;
+            .ok_or_else(|| self.registers_error())?;
+        self.declare_local(local_function.name.name, dest);
+
+        let proto = self.new_prototype(&local_function.definition, false)?;
         self.current_function
             .opcodes
             .push(OpCode::Closure { proto, dest });
-        self.current_function
-            .locals
-            .push((&local_function.name.name, dest));
 
         Ok(())
     }
@@ -764,18 +1666,22 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn expression(
         &mut self,
         expression: &'a Expression,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
-        let mut expr = self.head_expression(&expression.head)?;
-        for (binop, right) in &expression.tail {
-            expr = self.binary_operator(expr, *binop, right)?;
-        }
-        Ok(expr)
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        self.enter_recursion()?;
+        let res = self.head_expression(&expression.head).and_then(|mut expr| {
+            for (binop, right) in &expression.tail {
+                expr = self.binary_operator(expr, *binop, right)?;
+            }
+            Ok(expr)
+        });
+        self.recursion_depth -= 1;
+        res
     }
 
     fn head_expression(
         &mut self,
         head_expression: &'a HeadExpression,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         match head_expression {
             HeadExpression::Simple(simple_expression) => self.simple_expression(simple_expression),
             HeadExpression::UnaryOperator(unop, expr) => {
@@ -788,7 +1694,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn simple_expression(
         &mut self,
         simple_expression: &'a SimpleExpression,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         Ok(match simple_expression {
             SimpleExpression::Float(f) => ExprDescriptor::Value(Value::Number(*f)),
             SimpleExpression::Integer(i) => ExprDescriptor::Value(Value::Integer(*i)),
@@ -799,7 +1705,12 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             SimpleExpression::Nil => ExprDescriptor::Value(Value::Nil),
             SimpleExpression::True => ExprDescriptor::Value(Value::Boolean(true)),
             SimpleExpression::False => ExprDescriptor::Value(Value::Boolean(false)),
-            SimpleExpression::VarArgs => ExprDescriptor::VarArgs,
+            SimpleExpression::VarArgs => {
+                if !self.current_function.has_varargs {
+                    return Err(CompilerErrorKind::VarArgs);
+                }
+                ExprDescriptor::VarArgs
+            }
             SimpleExpression::TableConstructor(table_constructor) => {
                 self.table_constructor(table_constructor)?
             }
@@ -811,16 +1722,16 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn table_constructor(
         &mut self,
         table_constructor: &'a TableConstructor,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         if !table_constructor.fields.is_empty() {
-            unimplemented!("only empty table constructors supported");
+            return self.table_constructor_fields(table_constructor);
         }
 
         let dest = self
             .current_function
             .register_allocator
             .allocate()
-            .ok_or(CompilerError::Registers)?;
+            .ok_or_else(|| self.registers_error())?;
         self.current_function
             .opcodes
             .push(OpCode::NewTable { dest });
@@ -831,16 +1742,130 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         })
     }
 
+    // The table is pushed to the top of the stack, followed by the array index of the first pending
+    // array entry and then the pending array entries themselves, which are set with `SetList` in
+    // batches of `FIELDS_PER_FLUSH`.  A function call or `...` as the last field expands to all of
+    // its results.
+    fn table_constructor_fields(
+        &mut self,
+        table_constructor: &'a TableConstructor,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        let base = self
+            .current_function
+            .register_allocator
+            .push(1)
+            .ok_or_else(|| self.registers_error())?;
+        self.current_function
+            .opcodes
+            .push(OpCode::NewTable { dest: base });
+
+        let mut array_index: i64 = 1;
+        let mut pending: u8 = 0;
+        let field_count = table_constructor.fields.len();
+
+        for (i, field) in table_constructor.fields.iter().enumerate() {
+            match field {
+                ConstructorField::Array(value) => {
+                    if pending == 0 {
+                        self.expr_discharge(
+                            ExprDescriptor::Value(Value::Integer(array_index)),
+                            ExprDestination::PushNew,
+                        )?;
+                    }
+
+                    let value = self.expression(value)?;
+                    if i == field_count - 1 {
+                        let expanded = match value {
+                            ExprDescriptor::FunctionCall { func, method, args } => {
+                                self.expr_function_call(*func, method, args, VarCount::variable())?;
+                                true
+                            }
+                            ExprDescriptor::VarArgs => {
+                                let dest =
+                                    cast(self.current_function.register_allocator.stack_top())
+                                        .ok_or_else(|| self.registers_error())?;
+                                self.current_function.opcodes.push(OpCode::VarArgs {
+                                    dest: RegisterIndex(dest),
+                                    count: VarCount::variable(),
+                                });
+                                true
+                            }
+                            value => {
+                                self.expr_discharge(value, ExprDestination::PushNew)?;
+                                false
+                            }
+                        };
+
+                        if expanded {
+                            self.current_function.opcodes.push(OpCode::SetList {
+                                base,
+                                count: VarCount::variable(),
+                            });
+                            self.current_function.register_allocator.pop_to(base.0 + 1);
+                            pending = 0;
+                            continue;
+                        }
+                    } else {
+                        self.expr_discharge(value, ExprDestination::PushNew)?;
+                    }
+
+                    pending += 1;
+                    if pending == FIELDS_PER_FLUSH {
+                        self.set_list(base, pending)?;
+                        array_index += pending as i64;
+                        pending = 0;
+                    }
+                }
+
+                ConstructorField::Record(key, value) => {
+                    let mut key = match key {
+                        RecordKey::Named(name) => ExprDescriptor::Value(Value::String(
+                            String::new(self.mutation_context, self.interner.name(*name)),
+                        )),
+                        RecordKey::Indexed(key) => self.expression(key)?,
+                    };
+                    let mut value = self.expression(value)?;
+                    let mut table = ExprDescriptor::Register {
+                        register: base,
+                        is_temporary: false,
+                    };
+                    self.set_table(&mut table, &mut key, &mut value)?;
+                    self.expr_discard(key)?;
+                    self.expr_discard(value)?;
+                }
+            }
+        }
+
+        if pending != 0 {
+            self.set_list(base, pending)?;
+        }
+
+        Ok(ExprDescriptor::Register {
+            register: base,
+            is_temporary: true,
+        })
+    }
+
+    // Sets `count` pending array entries in a table constructor and frees their registers
+    fn set_list(&mut self, base: RegisterIndex, count: u8) -> Result<(), CompilerErrorKind> {
+        self.current_function.opcodes.push(OpCode::SetList {
+            base,
+            count: VarCount::try_constant(count).ok_or_else(|| self.registers_error())?,
+        });
+        self.current_function.register_allocator.pop_to(base.0 + 1);
+        Ok(())
+    }
+
     fn function_expression(
         &mut self,
         function: &'a FunctionDefinition,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
-        let proto = self.new_prototype(function)?;
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        let proto = self.new_prototype(function, false)?;
         let dest = self
             .current_function
             .register_allocator
             .allocate()
-            .ok_or(CompilerError::Registers)?;
+            .ok_or_else(|| self.registers_error())?;
         self.current_function
             .opcodes
             .push(OpCode::Closure { proto, dest });
@@ -854,14 +1879,14 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn suffixed_expression(
         &mut self,
         suffixed_expression: &'a SuffixedExpression,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         let mut expr = self.primary_expression(&suffixed_expression.primary)?;
         for suffix in &suffixed_expression.suffixes {
             match suffix {
                 SuffixPart::Field(field) => {
                     let mut key = match field {
                         FieldSuffix::Named(name) => ExprDescriptor::Value(Value::String(
-                            String::new(self.mutation_context, name),
+                            String::new(self.mutation_context, self.interner.name(*name)),
                         )),
                         FieldSuffix::Indexed(idx) => self.expression(idx)?,
                     };
@@ -870,19 +1895,17 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     self.expr_discard(key)?;
                     expr = res;
                 }
-                SuffixPart::Call(call_suffix) => match call_suffix {
-                    CallSuffix::Function(args) => {
-                        let args = args
-                            .iter()
-                            .map(|arg| self.expression(arg))
-                            .collect::<Result<_, CompilerError>>()?;
-                        expr = ExprDescriptor::FunctionCall {
-                            func: Box::new(expr),
-                            args,
-                        };
-                    }
-                    CallSuffix::Method(_, _) => unimplemented!("methods not supported yet"),
-                },
+                SuffixPart::Call(call_suffix) => {
+                    let (method, args) = match call_suffix {
+                        CallSuffix::Function(args) => (None, args),
+                        CallSuffix::Method(method, args) => (Some(*method), args),
+                    };
+                    expr = ExprDescriptor::FunctionCall {
+                        func: Box::new(expr),
+                        method,
+                        args,
+                    };
+                }
             }
         }
         Ok(expr)
@@ -891,48 +1914,125 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn primary_expression(
         &mut self,
         primary_expression: &'a PrimaryExpression,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         match primary_expression {
-            PrimaryExpression::Name(name) => Ok(match self.find_variable(name)? {
-                VariableDescriptor::Local(register) => ExprDescriptor::Register {
-                    register,
-                    is_temporary: false,
-                },
-                VariableDescriptor::UpValue(upvalue) => ExprDescriptor::UpValue(upvalue),
-                VariableDescriptor::Global(name) => {
-                    let mut env = self.get_environment()?;
-                    let mut key = ExprDescriptor::Value(Value::String(String::new(
-                        self.mutation_context,
-                        name,
-                    )));
-                    let res = self.get_table(&mut env, &mut key)?;
-                    self.expr_discard(env)?;
-                    self.expr_discard(key)?;
-                    res
+            PrimaryExpression::Name(name) => self.name_expression(*name),
+            PrimaryExpression::GroupedExpression(expr) => match self.expression(expr)? {
+                // Parentheses truncate multiple results to a single value
+                expr @ ExprDescriptor::FunctionCall { .. } | expr @ ExprDescriptor::VarArgs => {
+                    let register = self.expr_discharge(expr, ExprDestination::AllocateNew)?;
+                    Ok(ExprDescriptor::Register {
+                        register,
+                        is_temporary: true,
+                    })
                 }
-            }),
-            PrimaryExpression::GroupedExpression(expr) => self.expression(expr),
+                expr => Ok(expr),
+            },
         }
     }
 
+    fn name_expression(
+        &mut self,
+        name: Symbol,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        // A constant local of an upper function is used directly, rather than being captured as an
+        // upvalue.
+        if let Some(value) = self.upper_constant_local(name) {
+            return Ok(ExprDescriptor::Value(value));
+        }
+
+        Ok(match self.find_variable(name)? {
+            VariableDescriptor::Local(register) => {
+                self.current_function.mark_read(register);
+                match self.current_function.constant_locals.get(&register) {
+                    Some(&value) => ExprDescriptor::Value(value),
+                    None => ExprDescriptor::Register {
+                        register,
+                        is_temporary: false,
+                    },
+                }
+            }
+            VariableDescriptor::UpValue(upvalue) => ExprDescriptor::UpValue(upvalue),
+            VariableDescriptor::Global(name) => {
+                let cached = self
+                    .current_function
+                    .global_cache
+                    .iter()
+                    .position(|c| c.name == name);
+                let cached = cached.and_then(|i| {
+                    let cached = &mut self.current_function.global_cache[i];
+                    match cached.state {
+                        CachedGlobalState::Loaded => Some((cached.register, false)),
+                        CachedGlobalState::Empty
+                            if self.current_function.conditional_depth == 0 =>
+                        {
+                            cached.state = CachedGlobalState::Loaded;
+                            Some((cached.register, true))
+                        }
+                        _ => None,
+                    }
+                });
+                if let Some((register, load)) = cached {
+                    if load {
+                        self.get_global(name, register)?;
+                    }
+                    ExprDescriptor::Register {
+                        register,
+                        is_temporary: false,
+                    }
+                } else {
+                    let dest = self
+                        .current_function
+                        .register_allocator
+                        .allocate()
+                        .ok_or_else(|| self.registers_error())?;
+                    self.get_global(name, dest)?;
+                    ExprDescriptor::Register {
+                        register: dest,
+                        is_temporary: true,
+                    }
+                }
+            }
+            VariableDescriptor::SessionLocal(name) => self.get_session_local(name)?,
+        })
+    }
+
+    // Compiles a new function prototype.  Methods declared with `function t:m()` have an implicit
+    // first parameter named `self`.
     fn new_prototype(
         &mut self,
         function: &'a FunctionDefinition,
-    ) -> Result<PrototypeIndex, CompilerError> {
-        let old_current = mem::replace(
-            &mut self.current_function,
-            CompilerFunction::start(&function.parameters, function.has_varargs)?,
-        );
+        has_self: bool,
+    ) -> Result<PrototypeIndex, CompilerErrorKind> {
+        let max_function_depth = self.options.max_function_depth();
+        if self.upper_functions.len() >= max_function_depth {
+            return Err(CompilerErrorKind::FunctionDepth(max_function_depth));
+        }
+
+        let new_function = if has_self {
+            let parameters: Vec<Symbol> = iter::once(Symbol::SELF)
+                .chain(function.parameters.iter().cloned())
+                .collect();
+            CompilerFunction::start(&parameters, function.has_varargs, self.options)?
+        } else {
+            CompilerFunction::start(&function.parameters, function.has_varargs, self.options)?
+        };
+        let old_current = mem::replace(&mut self.current_function, new_function);
         self.upper_functions.push(old_current);
         self.block(&function.body)?;
         let proto = mem::replace(
             &mut self.current_function,
             self.upper_functions.pop().unwrap(),
         )
-        .finish(self.mutation_context)?;
+        .finish(
+            self.mutation_context,
+            self.interner,
+            self.chunk_name,
+            self.options,
+        )?;
         self.current_function.prototypes.push(proto);
         Ok(PrototypeIndex(
-            cast(self.current_function.prototypes.len() - 1).ok_or(CompilerError::Functions)?,
+            cast(self.current_function.prototypes.len() - 1).ok_or(CompilerErrorKind::Functions)?,
         ))
     }
 
@@ -940,7 +2040,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         &mut self,
         unop: UnaryOperator,
         mut expr: ExprDescriptor<'gc, 'a>,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         if let &ExprDescriptor::Value(v) = &expr {
             if let Some(v) = unop_const_fold(unop, v) {
                 return Ok(ExprDescriptor::Value(v));
@@ -958,7 +2058,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             .current_function
             .register_allocator
             .allocate()
-            .ok_or(CompilerError::Registers)?;
+            .ok_or_else(|| self.registers_error())?;
         let unop_opcode = unop_opcode(unop, dest, source);
         self.current_function.opcodes.push(unop_opcode);
         Ok(ExprDescriptor::Register {
@@ -972,7 +2072,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         mut left: ExprDescriptor<'gc, 'a>,
         binop: BinaryOperator,
         right: &'a Expression,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         match categorize_binop(binop) {
             BinOpCategory::Simple(op) => {
                 let mut right = self.expression(right)?;
@@ -992,7 +2092,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     .current_function
                     .register_allocator
                     .allocate()
-                    .ok_or(CompilerError::Registers)?;
+                    .ok_or_else(|| self.registers_error())?;
                 let simple_binop_opcode =
                     simple_binop_opcode(op, dest, left_reg_cons, right_reg_cons);
                 self.current_function.opcodes.push(simple_binop_opcode);
@@ -1025,117 +2125,589 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 right,
             }),
 
-            BinOpCategory::Concat => unimplemented!("no support for concat operator"),
+            BinOpCategory::Concat => self.concat_operator(left, right),
+        }
+    }
+
+    // Concat is right associative, so a chain like `a .. b .. c` is parsed with each following
+    // operand nested as the trailing concat of the previous right hand expression. The whole chain
+    // is flattened here into consecutive registers and joined with a single `Concat` opcode.
+    //
+    // Adjacent string and number constants in the chain are folded together, and are only pushed
+    // when the next operand which is not a constant is reached.  Pushing a constant has no side
+    // effects, so this does not change the order of evaluation.
+    fn concat_operator(
+        &mut self,
+        left: ExprDescriptor<'gc, 'a>,
+        right: &'a Expression,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        fn push_operand<'gc, 'a>(
+            this: &mut Compiler<'gc, 'a>,
+            expr: ExprDescriptor<'gc, 'a>,
+            source: &mut Option<RegisterIndex>,
+            count: &mut u8,
+        ) -> Result<(), CompilerErrorKind> {
+            let reg = this.expr_discharge(expr, ExprDestination::PushNew)?;
+            source.get_or_insert(reg);
+            *count = count.checked_add(1).ok_or_else(|| this.registers_error())?;
+            Ok(())
+        }
+
+        let mut source = None;
+        let mut count: u8 = 0;
+        let mut constants = Vec::new();
+
+        let mut expr = left;
+        let mut right = Some(right);
+        loop {
+            match expr {
+                ExprDescriptor::Value(value @ Value::String(_))
+                | ExprDescriptor::Value(value @ Value::Integer(_))
+                | ExprDescriptor::Value(value @ Value::Number(_)) => constants.push(value),
+                expr => {
+                    if !constants.is_empty() {
+                        let folded = Value::concat(self.mutation_context, &constants).unwrap();
+                        constants.clear();
+                        push_operand(self, ExprDescriptor::Value(folded), &mut source, &mut count)?;
+                    }
+                    push_operand(self, expr, &mut source, &mut count)?;
+                }
+            }
+
+            let operand = match right {
+                Some(operand) => operand,
+                None => break,
+            };
+            let (tail, next) = match operand.tail.split_last() {
+                Some(((BinaryOperator::Concat, next), tail)) => (tail, Some(next)),
+                _ => (&operand.tail[..], None),
+            };
+
+            expr = self.head_expression(&operand.head)?;
+            for (binop, operand) in tail {
+                expr = self.binary_operator(expr, *binop, operand)?;
+            }
+            right = next;
+        }
+
+        if !constants.is_empty() {
+            let folded = Value::concat(self.mutation_context, &constants).unwrap();
+            if source.is_none() {
+                return Ok(ExprDescriptor::Value(folded));
+            }
+            push_operand(self, ExprDescriptor::Value(folded), &mut source, &mut count)?;
+        }
+        let source = source.unwrap();
+
+        self.current_function.register_allocator.pop_to(source.0);
+        let dest = self
+            .current_function
+            .register_allocator
+            .allocate()
+            .ok_or_else(|| self.registers_error())?;
+        self.current_function.opcodes.push(OpCode::Concat {
+            dest,
+            source,
+            count,
+        });
+
+        Ok(ExprDescriptor::Register {
+            register: dest,
+            is_temporary: true,
+        })
+    }
+
+    fn find_variable(&mut self, name: Symbol) -> Result<VariableDescriptor, CompilerErrorKind> {
+        // We need to be able to index functions from the top-level chunk function (index 0), up to
+        // the current function
+        let current_function = self.upper_functions.len();
+        let interner = self.interner;
+        let upvalues_error = || {
+            CompilerErrorKind::UpValues(
+                std::string::String::from_utf8_lossy(interner.name(name)).into_owned(),
+            )
+        };
+        fn get_function<'gc, 'a, 's>(
+            this: &'s mut Compiler<'gc, 'a>,
+            i: usize,
+        ) -> &'s mut CompilerFunction<'gc> {
+            if i == this.upper_functions.len() {
+                &mut this.current_function
+            } else {
+                &mut this.upper_functions[i]
+            }
+        };
+
+        for i in (0..=current_function).rev() {
+            for j in (0..get_function(self, i).locals.len()).rev() {
+                let (local_name, register) = get_function(self, i).locals[j];
+                if name == local_name {
+                    if i == current_function {
+                        return Ok(VariableDescriptor::Local(register));
+                    } else {
+                        // If we've found an upvalue in an upper function, we need to mark the
+                        // blocks in that function as owning an upvalue.  This allows us to skip
+                        // closing upvalues in jumps if we know the block does not own any upvalues.
+                        get_function(self, i).mark_read(register);
+                        for block in get_function(self, i).blocks.iter_mut().rev() {
+                            if block.stack_bottom <= register.0 {
+                                block.owns_upvalues = true;
+                                break;
+                            }
+                        }
+
+                        get_function(self, i + 1)
+                            .upvalues
+                            .push((name, UpValueDescriptor::ParentLocal(register)));
+                        let mut upvalue_index = UpValueIndex(
+                            cast(get_function(self, i + 1).upvalues.len() - 1)
+                                .ok_or_else(upvalues_error)?,
+                        );
+                        for k in i + 2..=current_function {
+                            get_function(self, k)
+                                .upvalues
+                                .push((name, UpValueDescriptor::Outer(upvalue_index)));
+                            upvalue_index = UpValueIndex(
+                                cast(get_function(self, k).upvalues.len() - 1)
+                                    .ok_or_else(upvalues_error)?,
+                            );
+                        }
+                        return Ok(VariableDescriptor::UpValue(upvalue_index));
+                    }
+                }
+            }
+
+            // The top-level function has an implicit _ENV upvalue (this is the only upvalue it can
+            // have), and we add it if it is ever referenced.
+            if i == 0 && name == Symbol::ENV && get_function(self, i).upvalues.is_empty() {
+                get_function(self, 0)
+                    .upvalues
+                    .push((Symbol::ENV, UpValueDescriptor::Environment));
+            }
+
+            for j in 0..get_function(self, i).upvalues.len() {
+                if name == get_function(self, i).upvalues[j].0 {
+                    let upvalue_index = UpValueIndex(cast(j).ok_or_else(upvalues_error)?);
+                    if i == current_function {
+                        return Ok(VariableDescriptor::UpValue(upvalue_index));
+                    } else {
+                        let mut upvalue_index = upvalue_index;
+                        for k in i + 1..=current_function {
+                            get_function(self, k)
+                                .upvalues
+                                .push((name, UpValueDescriptor::Outer(upvalue_index)));
+                            upvalue_index = UpValueIndex(
+                                cast(get_function(self, k).upvalues.len() - 1)
+                                    .ok_or_else(upvalues_error)?,
+                            );
+                        }
+                        return Ok(VariableDescriptor::UpValue(upvalue_index));
+                    }
+                }
+            }
+        }
+
+        if let Some(session_locals) = &self.session_locals {
+            let name_bytes = self.interner.name(name);
+            if session_locals.iter().any(|l| &**l == name_bytes) {
+                return Ok(VariableDescriptor::SessionLocal(name));
+            }
+        }
+
+        Ok(VariableDescriptor::Global(name))
+    }
+
+    // If the given name refers to a local of an upper function which always holds a constant, marks
+    // the local as read and returns its value.
+    fn upper_constant_local(&mut self, name: Symbol) -> Option<Value<'gc>> {
+        if self
+            .current_function
+            .locals
+            .iter()
+            .any(|&(local, _)| local == name)
+        {
+            return None;
+        }
+        for function in self.upper_functions.iter_mut().rev() {
+            if let Some(&(_, register)) = function.locals.iter().rev().find(|&&(l, _)| l == name) {
+                let value = *function.constant_locals.get(&register)?;
+                function.mark_read(register);
+                return Some(value);
+            }
+            // A matching upvalue in this function refers to a local which is not constant, or to
+            // _ENV.
+            if function
+                .upvalues
+                .iter()
+                .any(|&(upvalue, _)| upvalue == name)
+            {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Get a reference to the variable _ENV in scope, or if that is not in scope, the implicit chunk
+    // _ENV.
+    fn get_environment(&mut self) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        Ok(match self.find_variable(Symbol::ENV)? {
+            VariableDescriptor::Local(register) => {
+                self.current_function.mark_read(register);
+                ExprDescriptor::Register {
+                    register,
+                    is_temporary: false,
+                }
+            }
+            VariableDescriptor::UpValue(upvalue) => ExprDescriptor::UpValue(upvalue),
+            VariableDescriptor::Global(_) => unreachable!("there should always be an _ENV upvalue"),
+            VariableDescriptor::SessionLocal(name) => self.get_session_local(name)?,
+        })
+    }
+
+    // Get a reference to the session table parameter of a session chunk
+    fn get_session(&mut self) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        Ok(match self.find_variable(SESSION_TABLE)? {
+            VariableDescriptor::Local(register) => ExprDescriptor::Register {
+                register,
+                is_temporary: false,
+            },
+            VariableDescriptor::UpValue(upvalue) => ExprDescriptor::UpValue(upvalue),
+            VariableDescriptor::Global(_) | VariableDescriptor::SessionLocal(_) => {
+                unreachable!("session table not in scope")
+            }
+        })
+    }
+
+    fn get_session_local(
+        &mut self,
+        name: Symbol,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        let mut session = self.get_session()?;
+        let mut key = ExprDescriptor::Value(Value::String(String::new(
+            self.mutation_context,
+            self.interner.name(name),
+        )));
+        let res = self.get_table(&mut session, &mut key)?;
+        self.expr_discard(session)?;
+        self.expr_discard(key)?;
+        Ok(res)
+    }
+
+    // Declares a new local in the current function, warning if it shadows another visible local.
+    fn declare_local(&mut self, name: Symbol, register: RegisterIndex) {
+        let line = self.current_line();
+        if self.warnings.is_some() && !self.interner.name(name).starts_with(b"_") {
+            let shadows = iter::once(&self.current_function)
+                .chain(&self.upper_functions)
+                .any(|function| function.locals.iter().any(|&(n, _)| n == name));
+            if shadows {
+                self.warn(
+                    line,
+                    CompilerWarningKind::ShadowedLocal(self.name_string(name)),
+                );
+            }
+        }
+        self.current_function.declare_local(name, register);
+        let local = self.current_function.local_variables.last_mut().unwrap();
+        local.line = line;
+        local.report_unused = true;
+    }
+
+    // Warns if an assigned global's name is a single typo away from a visible local.
+    fn check_global_typo(&mut self, name: Symbol) {
+        if self.warnings.is_none() {
+            return;
+        }
+        let global = self.interner.name(name);
+        let local = iter::once(&self.current_function)
+            .chain(self.upper_functions.iter().rev())
+            .flat_map(|function| function.locals.iter().rev())
+            .map(|&(local, _)| local)
+            .find(|&local| {
+                local != SESSION_TABLE && is_single_typo(global, self.interner.name(local))
+            });
+        if let Some(local) = local {
+            let kind = CompilerWarningKind::GlobalTypo {
+                global: self.name_string(name),
+                local: self.name_string(local),
+            };
+            self.warn(self.current_line(), kind);
+        }
+    }
+
+    fn warn(&mut self, line: Option<LineNumber>, kind: CompilerWarningKind) {
+        let chunk_name =
+            std::string::String::from_utf8_lossy(self.chunk_name.as_bytes()).into_owned();
+        if let Some(warnings) = &mut self.warnings {
+            warnings.push(CompilerWarning {
+                chunk_name,
+                line,
+                kind,
+            });
+        }
+    }
+
+    fn name_string(&self, name: Symbol) -> std::string::String {
+        std::string::String::from_utf8_lossy(self.interner.name(name)).into_owned()
+    }
+
+    // Compiles an expression which is only evaluated depending on the value of another, like the
+    // right operand of `and` or `or`.
+    fn conditional_expression(
+        &mut self,
+        expression: &'a Expression,
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
+        self.current_function.conditional_depth += 1;
+        let res = self.expression(expression);
+        self.current_function.conditional_depth -= 1;
+        res
+    }
+
+    // At O2, reserves a register for every global which is read more than once by an assignment,
+    // local, function call or return statement.  The global is loaded into the register when first
+    // read outside of a short circuit operand, and later reads use the register instead of looking
+    // up the global again, until anything which may change a global is compiled.
+    //
+    // The registers are reserved before compiling the statement so that the stack top does not
+    // change across any jumps inside of the statement.
+    fn cache_statement_globals(
+        &mut self,
+        statement: &'a Statement,
+    ) -> Result<(), CompilerErrorKind> {
+        let mut reads = HashMap::new();
+        match statement {
+            Statement::LocalStatement(local_statement) => {
+                for value in &local_statement.values {
+                    self.expression_global_reads(value, &mut reads);
+                }
+            }
+            Statement::FunctionCall(function_call) => {
+                self.suffixed_global_reads(&function_call.head, &mut reads);
+                self.call_global_reads(&function_call.call, &mut reads);
+            }
+            Statement::Assignment(assignment) => {
+                for target in &assignment.targets {
+                    if let AssignmentTarget::Field(table, field) = target {
+                        self.suffixed_global_reads(table, &mut reads);
+                        if let FieldSuffix::Indexed(key) = field {
+                            self.expression_global_reads(key, &mut reads);
+                        }
+                    }
+                }
+                for value in &assignment.values {
+                    self.expression_global_reads(value, &mut reads);
+                }
+            }
+            _ => {}
+        }
+        self.cache_globals(reads)
+    }
+
+    fn cache_globals(&mut self, reads: HashMap<Symbol, usize>) -> Result<(), CompilerErrorKind> {
+        assert!(self.current_function.global_cache.is_empty());
+        if self.options.optimization_level() < OptimizationLevel::O2 {
+            return Ok(());
+        }
+        // A local `_ENV` may be reassigned without it being visible as a global store
+        if self
+            .current_function
+            .locals
+            .iter()
+            .any(|&(name, _)| name == Symbol::ENV)
+        {
+            return Ok(());
+        }
+        let mut names = reads
+            .into_iter()
+            .filter(|&(_, count)| count > 1)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        // Make the register assignment independent of the iteration order of the reads
+        names.sort_by_key(|&name| self.interner.name(name));
+        for name in names {
+            let register = self
+                .current_function
+                .register_allocator
+                .allocate()
+                .ok_or_else(|| self.registers_error())?;
+            self.current_function.global_cache.push(CachedGlobal {
+                name,
+                register,
+                state: CachedGlobalState::Empty,
+            });
+        }
+        Ok(())
+    }
+
+    fn invalidate_global_cache(&mut self) {
+        for cached in &mut self.current_function.global_cache {
+            if cached.state == CachedGlobalState::Loaded {
+                cached.state = CachedGlobalState::Stale;
+            }
         }
     }
 
-    fn find_variable(&mut self, name: &'a [u8]) -> Result<VariableDescriptor<'a>, CompilerError> {
-        // We need to be able to index functions from the top-level chunk function (index 0), up to
-        // the current function
-        let current_function = self.upper_functions.len();
-        fn get_function<'gc, 'a, 's>(
-            this: &'s mut Compiler<'gc, 'a>,
-            i: usize,
-        ) -> &'s mut CompilerFunction<'gc, 'a> {
-            if i == this.upper_functions.len() {
-                &mut this.current_function
-            } else {
-                &mut this.upper_functions[i]
+    fn clear_global_cache(&mut self) {
+        for cached in self.current_function.global_cache.drain(..) {
+            self.current_function
+                .register_allocator
+                .free(cached.register);
+        }
+    }
+
+    // Returns true if the name is not any visible local or upvalue, so that reading it reads a
+    // global.  Unlike `find_variable`, this does not add any upvalues.
+    fn is_global(&self, name: Symbol) -> bool {
+        if name == Symbol::ENV {
+            return false;
+        }
+        let visible = iter::once(&self.current_function)
+            .chain(&self.upper_functions)
+            .any(|function| {
+                function.locals.iter().any(|&(local, _)| local == name)
+                    || function
+                        .upvalues
+                        .iter()
+                        .any(|&(upvalue, _)| upvalue == name)
+            });
+        if visible {
+            return false;
+        }
+        if let Some(session_locals) = &self.session_locals {
+            let name_bytes = self.interner.name(name);
+            if session_locals.iter().any(|l| &**l == name_bytes) {
+                return false;
             }
-        };
+        }
+        true
+    }
 
-        for i in (0..=current_function).rev() {
-            for j in (0..get_function(self, i).locals.len()).rev() {
-                let (local_name, register) = get_function(self, i).locals[j];
-                if name == local_name {
-                    if i == current_function {
-                        return Ok(VariableDescriptor::Local(register));
-                    } else {
-                        // If we've found an upvalue in an upper function, we need to mark the
-                        // blocks in that function as owning an upvalue.  This allows us to skip
-                        // closing upvalues in jumps if we know the block does not own any upvalues.
-                        for block in get_function(self, i).blocks.iter_mut().rev() {
-                            if block.stack_bottom <= register.0 {
-                                block.owns_upvalues = true;
-                                break;
+    // Counts the reads of every global in an expression, not including those inside of function
+    // definitions.
+    fn expression_global_reads(&self, expression: &Expression, reads: &mut HashMap<Symbol, usize>) {
+        match &*expression.head {
+            HeadExpression::Simple(simple) => match simple {
+                SimpleExpression::TableConstructor(table) => {
+                    for field in &table.fields {
+                        match field {
+                            ConstructorField::Array(value) => {
+                                self.expression_global_reads(value, reads)
+                            }
+                            ConstructorField::Record(key, value) => {
+                                if let RecordKey::Indexed(key) = key {
+                                    self.expression_global_reads(key, reads);
+                                }
+                                self.expression_global_reads(value, reads);
                             }
                         }
-
-                        get_function(self, i + 1)
-                            .upvalues
-                            .push((name, UpValueDescriptor::ParentLocal(register)));
-                        let mut upvalue_index = UpValueIndex(
-                            cast(get_function(self, i + 1).upvalues.len() - 1)
-                                .ok_or(CompilerError::UpValues)?,
-                        );
-                        for k in i + 2..=current_function {
-                            get_function(self, k)
-                                .upvalues
-                                .push((name, UpValueDescriptor::Outer(upvalue_index)));
-                            upvalue_index = UpValueIndex(
-                                cast(get_function(self, k).upvalues.len() - 1)
-                                    .ok_or(CompilerError::UpValues)?,
-                            );
-                        }
-                        return Ok(VariableDescriptor::UpValue(upvalue_index));
                     }
                 }
+                SimpleExpression::Suffixed(suffixed) => self.suffixed_global_reads(suffixed, reads),
+                _ => {}
+            },
+            HeadExpression::UnaryOperator(_, expression) => {
+                self.expression_global_reads(expression, reads)
             }
+        }
+        for (_, right) in &expression.tail {
+            self.expression_global_reads(right, reads);
+        }
+    }
 
-            // The top-level function has an implicit _ENV upvalue (this is the only upvalue it can
-            // have), and we add it if it is ever referenced.
-            if i == 0 && name == b"_ENV" && get_function(self, i).upvalues.is_empty() {
-                get_function(self, 0)
-                    .upvalues
-                    .push((b"_ENV", UpValueDescriptor::Environment));
+    fn suffixed_global_reads(
+        &self,
+        suffixed: &SuffixedExpression,
+        reads: &mut HashMap<Symbol, usize>,
+    ) {
+        match &suffixed.primary {
+            &PrimaryExpression::Name(name) => {
+                if self.is_global(name) {
+                    *reads.entry(name).or_insert(0) += 1;
+                }
             }
-
-            for j in 0..get_function(self, i).upvalues.len() {
-                if name == get_function(self, i).upvalues[j].0 {
-                    let upvalue_index = UpValueIndex(cast(j).ok_or(CompilerError::UpValues)?);
-                    if i == current_function {
-                        return Ok(VariableDescriptor::UpValue(upvalue_index));
-                    } else {
-                        let mut upvalue_index = upvalue_index;
-                        for k in i + 1..=current_function {
-                            get_function(self, k)
-                                .upvalues
-                                .push((name, UpValueDescriptor::Outer(upvalue_index)));
-                            upvalue_index = UpValueIndex(
-                                cast(get_function(self, k).upvalues.len() - 1)
-                                    .ok_or(CompilerError::UpValues)?,
-                            );
-                        }
-                        return Ok(VariableDescriptor::UpValue(upvalue_index));
-                    }
+            PrimaryExpression::GroupedExpression(expression) => {
+                self.expression_global_reads(expression, reads)
+            }
+        }
+        for suffix in &suffixed.suffixes {
+            match suffix {
+                SuffixPart::Field(FieldSuffix::Indexed(key)) => {
+                    self.expression_global_reads(key, reads)
                 }
+                SuffixPart::Field(FieldSuffix::Named(_)) => {}
+                SuffixPart::Call(call) => self.call_global_reads(call, reads),
             }
         }
+    }
 
-        Ok(VariableDescriptor::Global(name))
+    fn call_global_reads(&self, call: &CallSuffix, reads: &mut HashMap<Symbol, usize>) {
+        let args = match call {
+            CallSuffix::Function(args) | CallSuffix::Method(_, args) => args,
+        };
+        for arg in args {
+            self.expression_global_reads(arg, reads);
+        }
     }
 
-    // Get a reference to the variable _ENV in scope, or if that is not in scope, the implicit chunk
-    // _ENV.
-    fn get_environment(&mut self) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
-        Ok(match self.find_variable(b"_ENV")? {
-            VariableDescriptor::Local(register) => ExprDescriptor::Register {
-                register,
-                is_temporary: false,
-            },
-            VariableDescriptor::UpValue(upvalue) => ExprDescriptor::UpValue(upvalue),
-            VariableDescriptor::Global(_) => unreachable!("there should always be an _ENV upvalue"),
-        })
+    // Statements and expressions are compiled recursively, so the depth of nesting is limited to
+    // avoid overflowing the stack.  Every call must be paired with decrementing `recursion_depth`
+    // once the nested statement or expression is compiled.
+    fn enter_recursion(&mut self) -> Result<(), CompilerErrorKind> {
+        if self.recursion_depth >= MAX_RECURSION {
+            Err(CompilerErrorKind::RecursionLimit(MAX_RECURSION))
+        } else {
+            self.recursion_depth += 1;
+            Ok(())
+        }
+    }
+
+    fn registers_error(&self) -> CompilerErrorKind {
+        CompilerErrorKind::Registers(self.options.max_registers())
+    }
+
+    // In strict mode, globals may not be assigned inside of a function.
+    fn check_global_assignment(&self, name: Symbol) -> Result<(), CompilerErrorKind> {
+        if self.options.strict() && !self.upper_functions.is_empty() {
+            Err(CompilerErrorKind::StrictGlobal(
+                std::string::String::from_utf8_lossy(self.interner.name(name)).into_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    // The line of the statement currently being compiled, if any
+    fn current_line(&self) -> Option<LineNumber> {
+        self.current_function
+            .opcode_lines
+            .last()
+            .map(|&(_, line)| line)
+    }
+
+    // Marks every opcode generated from now on as belonging to the given line.
+    fn set_line(&mut self, line: LineNumber) {
+        let function = &mut self.current_function;
+        let next_opcode = function.opcodes.len();
+        // A line which generated no opcodes does not need an entry.
+        if let Some(&(opcode, _)) = function.opcode_lines.last() {
+            if opcode == next_opcode {
+                function.opcode_lines.pop();
+            }
+        }
+        match function.opcode_lines.last() {
+            Some(&(_, last_line)) if last_line == line => {}
+            _ => function.opcode_lines.push((next_opcode, line)),
+        }
     }
 
-    fn unique_jump_label(&mut self) -> JumpLabel<'a> {
+    fn unique_jump_label(&mut self) -> JumpLabel {
         let jl = JumpLabel::Unique(self.current_function.unique_jump_id);
         self.current_function.unique_jump_id =
             self.current_function.unique_jump_id.checked_add(1).unwrap();
         jl
     }
 
-    fn jump(&mut self, target: JumpLabel<'a>) -> Result<(), CompilerError> {
+    fn jump(&mut self, target: JumpLabel) -> Result<(), CompilerErrorKind> {
         let jmp_inst = self.current_function.opcodes.len();
         let current_stack_top = self.current_function.register_allocator.stack_top();
         let current_block_index = self.current_function.blocks.len().checked_sub(1).unwrap();
@@ -1153,11 +2725,11 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
 
                 self.current_function.opcodes.push(OpCode::Jump {
                     offset: jump_offset(jmp_inst, jump_target.instruction)
-                        .ok_or(CompilerError::JumpOverflow)?,
+                        .ok_or(CompilerErrorKind::JumpOverflow)?,
                     close_upvalues: if needs_close_upvalues {
                         cast(jump_target.stack_top)
                             .and_then(Opt254::try_some)
-                            .ok_or(CompilerError::Registers)?
+                            .ok_or_else(|| self.registers_error())?
                     } else {
                         Opt254::none()
                     },
@@ -1172,20 +2744,111 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 offset: 0,
                 close_upvalues: Opt254::none(),
             });
+            self.push_pending_jump(target, jmp_inst);
+        }
 
-            self.current_function.pending_jumps.push(PendingJump {
-                target: target,
-                instruction: jmp_inst,
-                block_index: current_block_index,
-                stack_top: current_stack_top,
-                close_upvalues: false,
-            });
+        Ok(())
+    }
+
+    // Marks the placeholder jump at the given instruction to be resolved when the target label is
+    // reached in the current block.
+    fn push_pending_jump(&mut self, target: JumpLabel, instruction: usize) {
+        let line = self.current_line();
+        let function = &mut self.current_function;
+        function.pending_jumps.push(PendingJump {
+            target,
+            instruction,
+            block_index: function.blocks.len().checked_sub(1).unwrap(),
+            stack_top: function.register_allocator.stack_top(),
+            close_upvalues: false,
+            line,
+        });
+    }
+
+    // The offset of a backwards jump from the next instruction to a label in scope
+    fn label_offset(&self, label: JumpLabel) -> Result<i16, CompilerErrorKind> {
+        let jump_target = self
+            .current_function
+            .jump_targets
+            .iter()
+            .rev()
+            .find(|jump_target| jump_target.label == label)
+            .expect("label is not in scope");
+        jump_offset(self.current_function.opcodes.len(), jump_target.instruction)
+            .ok_or(CompilerErrorKind::JumpOverflow)
+    }
+
+    // Jump offsets are limited to the range of an i16, so jumps which span more instructions than
+    // that pass through islands of trampoline jumps.  Once any pending jump or label in scope is
+    // too far away, this emits an island, skipped over by normal execution, with a placeholder
+    // jump for each far pending jump and a jump to each far label.  The pending jumps then jump to
+    // their trampolines instead, and later jumps to the labels jump to the island.
+    fn jump_island(&mut self) -> Result<(), CompilerErrorKind> {
+        let function = &mut self.current_function;
+        let island_start = function.opcodes.len();
+        let is_far = |instruction: usize| island_start - instruction > JUMP_ISLAND_DISTANCE;
+
+        let trampoline_count = function
+            .pending_jumps
+            .iter()
+            .filter(|pending_jump| is_far(pending_jump.instruction))
+            .count()
+            + function
+                .jump_targets
+                .iter()
+                .filter(|jump_target| is_far(jump_target.instruction))
+                .count();
+        if trampoline_count == 0 {
+            return Ok(());
         }
 
+        function.opcodes.push(OpCode::Jump {
+            offset: cast(trampoline_count).ok_or(CompilerErrorKind::JumpOverflow)?,
+            close_upvalues: Opt254::none(),
+        });
+
+        for pending_jump in &mut function.pending_jumps {
+            if is_far(pending_jump.instruction) {
+                let trampoline = function.opcodes.len();
+                let offset = jump_offset(pending_jump.instruction, trampoline)
+                    .ok_or(CompilerErrorKind::JumpOverflow)?;
+                match &mut function.opcodes[pending_jump.instruction] {
+                    OpCode::Jump {
+                        offset: jump,
+                        close_upvalues,
+                    } if *jump == 0 && close_upvalues.is_none() => *jump = offset,
+                    OpCode::NumericForPrep { jump, .. } | OpCode::IntegerForPrep { jump, .. }
+                        if *jump == 0 =>
+                    {
+                        *jump = offset
+                    }
+                    _ => panic!("jump instruction is not a placeholder jump instruction"),
+                }
+                function.opcodes.push(OpCode::Jump {
+                    offset: 0,
+                    close_upvalues: Opt254::none(),
+                });
+                pending_jump.instruction = trampoline;
+            }
+        }
+
+        for jump_target in &mut function.jump_targets {
+            if is_far(jump_target.instruction) {
+                let trampoline = function.opcodes.len();
+                function.opcodes.push(OpCode::Jump {
+                    offset: jump_offset(trampoline, jump_target.instruction)
+                        .ok_or(CompilerErrorKind::JumpOverflow)?,
+                    close_upvalues: Opt254::none(),
+                });
+                jump_target.instruction = trampoline;
+            }
+        }
+
+        function.last_jump_target = Some(function.opcodes.len());
         Ok(())
     }
 
-    fn jump_target(&mut self, jump_label: JumpLabel<'a>) -> Result<(), CompilerError> {
+    fn jump_target(&mut self, jump_label: JumpLabel) -> Result<(), CompilerErrorKind> {
         let target_instruction = self.current_function.opcodes.len();
         let current_stack_top = self.current_function.register_allocator.stack_top();
         let current_block_index = self.current_function.blocks.len().checked_sub(1).unwrap();
@@ -1194,7 +2857,10 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             if jump_target.block_index < current_block_index {
                 break;
             } else if jump_target.label == jump_label {
-                return Err(CompilerError::DuplicateLabel);
+                return Err(CompilerErrorKind::DuplicateLabel(jump_label_name(
+                    self.interner,
+                    jump_label,
+                )));
             }
         }
 
@@ -1204,6 +2870,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             stack_top: current_stack_top,
             block_index: current_block_index,
         });
+        self.current_function.last_jump_target = Some(target_instruction);
 
         let mut resolving_jumps = Vec::new();
         self.current_function.pending_jumps.retain(|pending_jump| {
@@ -1219,10 +2886,22 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             }
         });
 
+        let max_registers = self.options.max_registers();
         for pending_jump in resolving_jumps {
             assert!(pending_jump.stack_top <= current_stack_top);
             if pending_jump.stack_top < current_stack_top {
-                return Err(CompilerError::JumpLocal);
+                // The first local declared between the jump and its target
+                let &(local, _) = self
+                    .current_function
+                    .locals
+                    .iter()
+                    .find(|(_, r)| r.0 >= pending_jump.stack_top)
+                    .unwrap();
+                return Err(CompilerErrorKind::JumpLocal {
+                    label: jump_label_name(self.interner, pending_jump.target),
+                    local: self.name_string(local),
+                    line: pending_jump.line,
+                });
             }
 
             match &mut self.current_function.opcodes[pending_jump.instruction] {
@@ -1231,13 +2910,20 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     close_upvalues,
                 } if *offset == 0 && close_upvalues.is_none() => {
                     *offset = jump_offset(pending_jump.instruction, target_instruction)
-                        .ok_or(CompilerError::JumpOverflow)?;
+                        .ok_or(CompilerErrorKind::JumpOverflow)?;
                     if pending_jump.close_upvalues {
                         *close_upvalues = cast(current_stack_top)
                             .and_then(Opt254::try_some)
-                            .ok_or(CompilerError::Registers)?;
+                            .ok_or(CompilerErrorKind::Registers(max_registers))?;
                     };
                 }
+                OpCode::NumericForPrep { jump, .. } | OpCode::IntegerForPrep { jump, .. }
+                    if *jump == 0 =>
+                {
+                    assert!(!pending_jump.close_upvalues);
+                    *jump = jump_offset(pending_jump.instruction, target_instruction)
+                        .ok_or(CompilerErrorKind::JumpOverflow)?;
+                }
                 _ => panic!("jump instruction is not a placeholder jump instruction"),
             }
         }
@@ -1245,22 +2931,49 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         Ok(())
     }
 
-    fn get_constant(&mut self, constant: Value<'gc>) -> Result<ConstantIndex16, CompilerError> {
+    // Instead of moving a temporary register which is about to be freed, tries to make the opcode
+    // which produced it write directly to the destination.  This is only possible when the
+    // temporary was produced by the last opcode alone, and no jump or skip could reach the position
+    // of the move without passing through that opcode.
+    fn coalesce_move(&mut self, dest: RegisterIndex, source: RegisterIndex) -> bool {
+        if self.options.optimization_level() < OptimizationLevel::O1 {
+            return false;
+        }
+        let function = &mut self.current_function;
+        let len = function.opcodes.len();
+        if len == 0 || function.last_jump_target == Some(len) {
+            return false;
+        }
+        if len >= 2 && opcode_may_skip(&function.opcodes[len - 2]) {
+            return false;
+        }
+        match opcode_dest(&mut function.opcodes[len - 1]) {
+            Some(last_dest) if *last_dest == source => {
+                *last_dest = dest;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn get_constant(&mut self, constant: Value<'gc>) -> Result<ConstantIndex16, CompilerErrorKind> {
         if let Some(constant) = self
             .current_function
             .constant_table
-            .get(&ConstantValue(constant))
+            .get(&ConstantKey::from(constant))
             .cloned()
         {
             Ok(constant)
         } else {
-            let c = ConstantIndex16(
-                cast(self.current_function.constants.len()).ok_or(CompilerError::Constants)?,
-            );
+            let max_constants = self.options.max_constants();
+            if self.current_function.constants.len() >= max_constants {
+                return Err(CompilerErrorKind::Constants(max_constants));
+            }
+            let c = ConstantIndex16(cast(self.current_function.constants.len()).unwrap());
             self.current_function.constants.push(constant);
             self.current_function
                 .constant_table
-                .insert(ConstantValue(constant), c);
+                .insert(ConstantKey::from(constant), c);
             Ok(c)
         }
     }
@@ -1269,12 +2982,37 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         &mut self,
         table: &mut ExprDescriptor<'gc, 'a>,
         key: &mut ExprDescriptor<'gc, 'a>,
-    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerError> {
+    ) -> Result<ExprDescriptor<'gc, 'a>, CompilerErrorKind> {
         let dest = self
             .current_function
             .register_allocator
             .allocate()
-            .ok_or(CompilerError::Registers)?;
+            .ok_or_else(|| self.registers_error())?;
+        self.get_table_into(table, key, dest)?;
+        Ok(ExprDescriptor::Register {
+            register: dest,
+            is_temporary: true,
+        })
+    }
+
+    // Loads the value of a global into the given register
+    fn get_global(&mut self, name: Symbol, dest: RegisterIndex) -> Result<(), CompilerErrorKind> {
+        let mut env = self.get_environment()?;
+        let mut key = ExprDescriptor::Value(Value::String(String::new(
+            self.mutation_context,
+            self.interner.name(name),
+        )));
+        self.get_table_into(&mut env, &mut key, dest)?;
+        self.expr_discard(env)?;
+        self.expr_discard(key)
+    }
+
+    fn get_table_into(
+        &mut self,
+        table: &mut ExprDescriptor<'gc, 'a>,
+        key: &mut ExprDescriptor<'gc, 'a>,
+        dest: RegisterIndex,
+    ) -> Result<(), CompilerErrorKind> {
         let op = match table {
             &mut ExprDescriptor::UpValue(table) => match self.expr_any_register_or_constant(key)? {
                 RegisterOrConstant::Constant(key) => OpCode::GetUpTableC { dest, table, key },
@@ -1290,10 +3028,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         };
 
         self.current_function.opcodes.push(op);
-        Ok(ExprDescriptor::Register {
-            register: dest,
-            is_temporary: true,
-        })
+        Ok(())
     }
 
     fn set_table(
@@ -1301,7 +3036,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         table: &mut ExprDescriptor<'gc, 'a>,
         key: &mut ExprDescriptor<'gc, 'a>,
         value: &mut ExprDescriptor<'gc, 'a>,
-    ) -> Result<(), CompilerError> {
+    ) -> Result<(), CompilerErrorKind> {
         let op = match table {
             &mut ExprDescriptor::UpValue(table) => {
                 match (
@@ -1345,6 +3080,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         };
 
         self.current_function.opcodes.push(op);
+        self.invalidate_global_cache();
         Ok(())
     }
 
@@ -1353,7 +3089,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn expr_any_register(
         &mut self,
         expr: &mut ExprDescriptor<'gc, 'a>,
-    ) -> Result<RegisterIndex, CompilerError> {
+    ) -> Result<RegisterIndex, CompilerErrorKind> {
         if let ExprDescriptor::Register { register, .. } = *expr {
             Ok(register)
         } else {
@@ -1377,7 +3113,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn expr_any_register_or_constant(
         &mut self,
         expr: &mut ExprDescriptor<'gc, 'a>,
-    ) -> Result<RegisterOrConstant, CompilerError> {
+    ) -> Result<RegisterOrConstant, CompilerErrorKind> {
         if let &mut ExprDescriptor::Value(cons) = expr {
             if let Some(c8) = cast(self.get_constant(cons)?.0) {
                 return Ok(RegisterOrConstant::Constant(ConstantIndex8(c8)));
@@ -1393,23 +3129,23 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         &mut self,
         expr: ExprDescriptor<'gc, 'a>,
         dest: ExprDestination,
-    ) -> Result<RegisterIndex, CompilerError> {
+    ) -> Result<RegisterIndex, CompilerErrorKind> {
         fn new_destination<'gc, 'a>(
             this: &mut Compiler<'gc, 'a>,
             dest: ExprDestination,
-        ) -> Result<RegisterIndex, CompilerError> {
+        ) -> Result<RegisterIndex, CompilerErrorKind> {
             Ok(match dest {
                 ExprDestination::Register(dest) => dest,
                 ExprDestination::AllocateNew => this
                     .current_function
                     .register_allocator
                     .allocate()
-                    .ok_or(CompilerError::Registers)?,
+                    .ok_or_else(|| this.registers_error())?,
                 ExprDestination::PushNew => this
                     .current_function
                     .register_allocator
                     .push(1)
-                    .ok_or(CompilerError::Registers)?,
+                    .ok_or_else(|| this.registers_error())?,
             })
         }
 
@@ -1425,7 +3161,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                         self.current_function.register_allocator.free(source);
                     }
                     let dest = new_destination(self, dest)?;
-                    if dest != source {
+                    if dest != source && !(is_temporary && self.coalesce_move(dest, source)) {
                         self.current_function
                             .opcodes
                             .push(OpCode::Move { dest, source });
@@ -1486,9 +3222,10 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 dest
             }
 
-            ExprDescriptor::FunctionCall { func, args } => match dest {
+            ExprDescriptor::FunctionCall { func, method, args } => match dest {
                 ExprDestination::Register(dest) => {
-                    let source = self.expr_function_call(*func, args, VarCount::constant(1))?;
+                    let source =
+                        self.expr_function_call(*func, method, args, VarCount::constant(1))?;
                     assert_ne!(dest, source);
                     self.current_function
                         .opcodes
@@ -1496,12 +3233,13 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     dest
                 }
                 ExprDestination::AllocateNew | ExprDestination::PushNew => {
-                    let source = self.expr_function_call(*func, args, VarCount::constant(1))?;
+                    let source =
+                        self.expr_function_call(*func, method, args, VarCount::constant(1))?;
                     assert_eq!(
                         self.current_function
                             .register_allocator
                             .push(1)
-                            .ok_or(CompilerError::Registers)?,
+                            .ok_or_else(|| self.registers_error())?,
                         source
                     );
                     source
@@ -1569,7 +3307,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 let skip = self.unique_jump_label();
                 self.jump(skip)?;
 
-                let right = self.expression(right)?;
+                let right = self.conditional_expression(right)?;
                 self.expr_discharge(right, ExprDestination::Register(dest))?;
 
                 self.jump_target(skip)?;
@@ -1595,19 +3333,20 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         &mut self,
         expr: ExprDescriptor<'gc, 'a>,
         count: u8,
-    ) -> Result<RegisterIndex, CompilerError> {
+    ) -> Result<RegisterIndex, CompilerErrorKind> {
         assert!(count != 0);
         Ok(match expr {
-            ExprDescriptor::FunctionCall { func, args } => {
+            ExprDescriptor::FunctionCall { func, method, args } => {
                 let dest = self.expr_function_call(
                     *func,
+                    method,
                     args,
-                    VarCount::try_constant(count).ok_or(CompilerError::Registers)?,
+                    VarCount::try_constant(count).ok_or_else(|| self.registers_error())?,
                 )?;
                 self.current_function
                     .register_allocator
                     .push(count)
-                    .ok_or(CompilerError::Registers)?;
+                    .ok_or_else(|| self.registers_error())?;
                 dest
             }
             ExprDescriptor::VarArgs => {
@@ -1615,10 +3354,10 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     .current_function
                     .register_allocator
                     .push(count)
-                    .ok_or(CompilerError::Registers)?;
+                    .ok_or_else(|| self.registers_error())?;
                 self.current_function.opcodes.push(OpCode::VarArgs {
                     dest,
-                    count: VarCount::try_constant(count).ok_or(CompilerError::Registers)?,
+                    count: VarCount::try_constant(count).ok_or_else(|| self.registers_error())?,
                 });
                 dest
             }
@@ -1627,7 +3366,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                     .current_function
                     .register_allocator
                     .push(count)
-                    .ok_or(CompilerError::Registers)?;
+                    .ok_or_else(|| self.registers_error())?;
                 self.current_function
                     .opcodes
                     .push(OpCode::LoadNil { dest, count });
@@ -1640,7 +3379,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                         .current_function
                         .register_allocator
                         .push(count - 1)
-                        .ok_or(CompilerError::Registers)?;
+                        .ok_or_else(|| self.registers_error())?;
                     self.current_function.opcodes.push(OpCode::LoadNil {
                         dest: nils,
                         count: count - 1,
@@ -1658,26 +3397,83 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     fn expr_function_call(
         &mut self,
         func: ExprDescriptor<'gc, 'a>,
-        mut args: Vec<ExprDescriptor<'gc, 'a>>,
+        method: Option<Symbol>,
+        args: &'a [Expression],
         returns: VarCount,
-    ) -> Result<RegisterIndex, CompilerError> {
-        let top_reg = self.expr_discharge(func, ExprDestination::PushNew)?;
+    ) -> Result<RegisterIndex, CompilerErrorKind> {
+        let (top_reg, arg_count) = self.expr_push_call(func, method, args)?;
+        self.current_function.opcodes.push(OpCode::Call {
+            func: top_reg,
+            args: arg_count,
+            returns,
+        });
+        self.invalidate_global_cache();
+
+        self.current_function.register_allocator.pop_to(top_reg.0);
+        Ok(top_reg)
+    }
+
+    // Pushes a function and its arguments to the top of the stack, returning the register of the
+    // function and the argument count to call it with.  For a method call, `func` is the table
+    // which holds the method, and it is passed as the implicit first argument.
+    fn expr_push_call(
+        &mut self,
+        mut func: ExprDescriptor<'gc, 'a>,
+        method: Option<Symbol>,
+        args: &'a [Expression],
+    ) -> Result<(RegisterIndex, VarCount), CompilerErrorKind> {
+        let (top_reg, args_len) = if let Some(method) = method {
+            let table = self.expr_any_register(&mut func)?;
+            let mut key = ExprDescriptor::Value(Value::String(String::new(
+                self.mutation_context,
+                self.interner.name(method),
+            )));
+            let key_reg_cons = self.expr_any_register_or_constant(&mut key)?;
+            // The table and key are read before the function and `self` are written, so their
+            // registers may be reused.
+            self.expr_discard(func)?;
+            self.expr_discard(key)?;
+
+            let base = self
+                .current_function
+                .register_allocator
+                .push(2)
+                .ok_or_else(|| self.registers_error())?;
+            self.current_function.opcodes.push(match key_reg_cons {
+                RegisterOrConstant::Constant(key) => OpCode::SelfC { base, table, key },
+                RegisterOrConstant::Register(key) => OpCode::SelfR { base, table, key },
+            });
+            (base, args.len() + 1)
+        } else {
+            (
+                self.expr_discharge(func, ExprDestination::PushNew)?,
+                args.len(),
+            )
+        };
 
-        let args_len = args.len();
-        let last_arg = args.pop();
+        let (last_arg, args) = match args.split_last() {
+            Some((last_arg, args)) => (Some(last_arg), args),
+            None => (None, args),
+        };
         for arg in args {
+            let arg = self.expression(arg)?;
             self.expr_discharge(arg, ExprDestination::PushNew)?;
         }
 
+        let last_arg = match last_arg {
+            Some(last_arg) => Some(self.expression(last_arg)?),
+            None => None,
+        };
         let arg_count = match last_arg {
-            Some(ExprDescriptor::FunctionCall { func, args }) => {
-                self.expr_function_call(*func, args, VarCount::variable())?;
+            Some(ExprDescriptor::FunctionCall { func, method, args }) => {
+                self.expr_function_call(*func, method, args, VarCount::variable())?;
                 VarCount::variable()
             }
             Some(ExprDescriptor::VarArgs) => {
                 self.current_function.opcodes.push(OpCode::VarArgs {
                     dest: RegisterIndex(
-                        cast(top_reg.0 as usize + args_len).ok_or(CompilerError::Registers)?,
+                        cast(top_reg.0 as usize + args_len)
+                            .ok_or_else(|| self.registers_error())?,
                     ),
                     count: VarCount::variable(),
                 });
@@ -1687,21 +3483,14 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 self.expr_discharge(last_arg, ExprDestination::PushNew)?;
                 cast(args_len)
                     .and_then(VarCount::try_constant)
-                    .ok_or(CompilerError::Registers)?
+                    .ok_or_else(|| self.registers_error())?
             }
             None => cast(args_len)
                 .and_then(VarCount::try_constant)
-                .ok_or(CompilerError::Registers)?,
+                .ok_or_else(|| self.registers_error())?,
         };
 
-        self.current_function.opcodes.push(OpCode::Call {
-            func: top_reg,
-            args: arg_count,
-            returns,
-        });
-
-        self.current_function.register_allocator.pop_to(top_reg.0);
-        Ok(top_reg)
+        Ok((top_reg, arg_count))
     }
 
     // Consumes the given expression and tests it, skipping the following instruction if the boolean
@@ -1710,14 +3499,14 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
         &mut self,
         expr: ExprDescriptor<'gc, 'a>,
         skip_if: bool,
-    ) -> Result<(), CompilerError> {
+    ) -> Result<(), CompilerErrorKind> {
         fn gen_comparison<'gc, 'a>(
             this: &mut Compiler<'gc, 'a>,
             mut left: ExprDescriptor<'gc, 'a>,
             op: ComparisonBinOp,
             mut right: ExprDescriptor<'gc, 'a>,
             skip_if: bool,
-        ) -> Result<(), CompilerError> {
+        ) -> Result<(), CompilerErrorKind> {
             let left_reg_cons = this.expr_any_register_or_constant(&mut left)?;
             let right_reg_cons = this.expr_any_register_or_constant(&mut right)?;
             this.expr_discard(left)?;
@@ -1734,7 +3523,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
             this: &mut Compiler<'gc, 'a>,
             mut expr: ExprDescriptor<'gc, 'a>,
             is_true: bool,
-        ) -> Result<(), CompilerError> {
+        ) -> Result<(), CompilerErrorKind> {
             let test_reg = this.expr_any_register(&mut expr)?;
             this.expr_discard(expr)?;
             this.current_function.opcodes.push(OpCode::Test {
@@ -1770,7 +3559,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     }
 
     // Evaluate an expression, but discard the result
-    fn expr_discard(&mut self, expr: ExprDescriptor<'gc, 'a>) -> Result<(), CompilerError> {
+    fn expr_discard(&mut self, expr: ExprDescriptor<'gc, 'a>) -> Result<(), CompilerErrorKind> {
         match expr {
             ExprDescriptor::Register {
                 register: source,
@@ -1785,8 +3574,8 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 self.expr_discard(*expr)?;
             }
 
-            ExprDescriptor::FunctionCall { func, args } => {
-                self.expr_function_call(*func, args, VarCount::constant(0))?;
+            ExprDescriptor::FunctionCall { func, method, args } => {
+                self.expr_function_call(*func, method, args, VarCount::constant(0))?;
             }
 
             ExprDescriptor::Comparison {
@@ -1826,7 +3615,7 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
                 let skip = self.unique_jump_label();
                 self.jump(skip)?;
 
-                let right = self.expression(right)?;
+                let right = self.conditional_expression(right)?;
                 self.expr_discard(right)?;
 
                 self.jump_target(skip)?;
@@ -1839,32 +3628,88 @@ impl<'gc, 'a> Compiler<'gc, 'a> {
     }
 }
 
-impl<'gc, 'a> CompilerFunction<'gc, 'a> {
+impl<'gc> CompilerFunction<'gc> {
     fn start(
-        parameters: &'a [Box<[u8]>],
+        parameters: &[Symbol],
         has_varargs: bool,
-    ) -> Result<CompilerFunction<'gc, 'a>, CompilerError> {
-        let mut function = CompilerFunction::default();
-        let fixed_params: u8 = cast(parameters.len()).ok_or(CompilerError::FixedParameters)?;
-        function.register_allocator.push(fixed_params);
+        options: &CompilerOptions,
+    ) -> Result<CompilerFunction<'gc>, CompilerErrorKind> {
+        let mut function = CompilerFunction {
+            register_allocator: RegisterAllocator::new(options.max_registers()),
+            ..CompilerFunction::default()
+        };
+        let fixed_params: u8 = cast(parameters.len()).ok_or(CompilerErrorKind::FixedParameters)?;
+        if fixed_params > 0 {
+            function
+                .register_allocator
+                .push(fixed_params)
+                .ok_or(CompilerErrorKind::Registers(options.max_registers()))?;
+        }
         function.has_varargs = has_varargs;
         function.fixed_params = fixed_params;
         for i in 0..fixed_params {
-            function
-                .locals
-                .push((&parameters[i as usize], RegisterIndex(i)));
+            function.declare_local(parameters[i as usize], RegisterIndex(i));
         }
         Ok(function)
     }
 
-    fn finish(mut self, mc: MutationContext<'gc, 'a>) -> Result<FunctionProto<'gc>, CompilerError> {
+    // Declares a new local variable, which is live starting at the next opcode.
+    fn declare_local(&mut self, name: Symbol, register: RegisterIndex) {
+        self.locals.push((name, register));
+        self.local_variables.push(LocalDescriptor {
+            name,
+            register,
+            start: self.opcodes.len(),
+            end: None,
+            line: None,
+            read: false,
+            report_unused: false,
+        });
+    }
+
+    // Removes the most recently declared local variable, which is no longer live starting at the
+    // next opcode.
+    fn pop_local(&mut self) -> Option<LocalDescriptor> {
+        self.locals.pop()?;
+        let end = self.opcodes.len();
+        // Locals go out of scope in the reverse order they were declared, so the last local still
+        // in scope is always the one being removed.
+        let variable = self
+            .local_variables
+            .iter_mut()
+            .rev()
+            .find(|v| v.end.is_none())
+            .unwrap();
+        variable.end = Some(end);
+        Some(*variable)
+    }
+
+    // Marks the local in the given register as having been read.
+    fn mark_read(&mut self, register: RegisterIndex) {
+        if let Some(variable) = self
+            .local_variables
+            .iter_mut()
+            .rev()
+            .find(|v| v.end.is_none() && v.register == register)
+        {
+            variable.read = true;
+        }
+    }
+
+    fn finish(
+        mut self,
+        mc: MutationContext<'gc, '_>,
+        interner: &Interner,
+        chunk_name: String<'gc>,
+        options: &CompilerOptions,
+    ) -> Result<FunctionProto<'gc>, CompilerErrorKind> {
         self.opcodes.push(OpCode::Return {
             start: RegisterIndex(0),
             count: VarCount::constant(0),
         });
         assert!(self.locals.len() == self.fixed_params as usize);
-        for (_, r) in self.locals.drain(..) {
-            self.register_allocator.free(r);
+        while let Some(local) = self.pop_local() {
+            self.register_allocator.free(local.register);
         }
         assert_eq!(
             self.register_allocator.stack_top(),
@@ -1872,26 +3717,210 @@ impl<'gc, 'a> CompilerFunction<'gc, 'a> {
             "register leak detected"
         );
 
-        if !self.pending_jumps.is_empty() {
-            return Err(CompilerError::GotoInvalid);
+        if let Some(pending_jump) = self.pending_jumps.first() {
+            return Err(match pending_jump.target {
+                JumpLabel::Break => CompilerErrorKind::BreakOutsideLoop {
+                    line: pending_jump.line,
+                },
+                label => CompilerErrorKind::GotoInvalid {
+                    label: jump_label_name(interner, label),
+                    line: pending_jump.line,
+                },
+            });
+        }
+
+        if options.strip_debug() {
+            self.opcode_lines.clear();
+            self.local_variables.clear();
         }
 
         Ok(FunctionProto {
+            chunk_name,
             fixed_params: self.fixed_params,
             has_varargs: self.has_varargs,
             stack_size: self.register_allocator.stack_size(),
             constants: self.constants,
             opcodes: self.opcodes,
+            opcode_lines: self.opcode_lines,
+            local_variables: self
+                .local_variables
+                .into_iter()
+                .map(|v| LocalVariable {
+                    name: String::new(mc, local_name(interner, v.name)),
+                    register: v.register,
+                    start: v.start,
+                    end: v.end.unwrap(),
+                })
+                .collect(),
             upvalues: self.upvalues.iter().map(|(_, d)| *d).collect(),
             prototypes: self
                 .prototypes
                 .into_iter()
                 .map(|f| Gc::allocate(mc, f))
                 .collect(),
+            switches: self.switches,
         })
     }
 }
 
+// Matches a condition of the form `name == constant` or `constant == name`, where the constant is
+// an integer or string literal.
+fn switch_case(condition: &Expression) -> Option<(Symbol, &SimpleExpression)> {
+    fn name(head: &HeadExpression) -> Option<Symbol> {
+        match head {
+            HeadExpression::Simple(SimpleExpression::Suffixed(SuffixedExpression {
+                primary: PrimaryExpression::Name(name),
+                suffixes,
+            })) if suffixes.is_empty() => Some(*name),
+            _ => None,
+        }
+    }
+
+    fn constant(head: &HeadExpression) -> Option<&SimpleExpression> {
+        match head {
+            HeadExpression::Simple(constant @ SimpleExpression::Integer(_))
+            | HeadExpression::Simple(constant @ SimpleExpression::String(_)) => Some(constant),
+            _ => None,
+        }
+    }
+
+    match condition.tail.as_slice() {
+        [(BinaryOperator::Equal, right)] if right.tail.is_empty() => {
+            let (left, right) = (&*condition.head, &*right.head);
+            match (name(left), constant(right), name(right), constant(left)) {
+                (Some(name), Some(constant), _, _) | (_, _, Some(name), Some(constant)) => {
+                    Some((name, constant))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Returns the name of a local for debug information, compiler generated locals are given a name
+// which cannot appear in the source.
+fn local_name(interner: &Interner, name: Symbol) -> &[u8] {
+    if name == Symbol::HIDDEN {
+        b"(hidden)"
+    } else {
+        interner.name(name)
+    }
+}
+
+// Finds every name which is the target of an assignment anywhere in the chunk, whether it resolves
+// to a local or a global.
+fn written_names(chunk: &Chunk) -> HashSet<Symbol> {
+    analyze_chunk(chunk)
+        .references
+        .iter()
+        .filter(|r| r.kind == ReferenceKind::Write)
+        .map(|r| r.name)
+        .collect()
+}
+
+// Returns the destination register of opcodes which write a single register and otherwise have no
+// effect on control flow or other registers.
+fn opcode_dest(opcode: &mut OpCode) -> Option<&mut RegisterIndex> {
+    match opcode {
+        OpCode::LoadConstant { dest, .. }
+        | OpCode::GetTableR { dest, .. }
+        | OpCode::GetTableC { dest, .. }
+        | OpCode::GetUpTableR { dest, .. }
+        | OpCode::GetUpTableC { dest, .. }
+        | OpCode::GetUpValue { dest, .. }
+        | OpCode::Not { dest, .. }
+        | OpCode::Minus { dest, .. }
+        | OpCode::BitNot { dest, .. }
+        | OpCode::Length { dest, .. }
+        | OpCode::Concat { dest, .. }
+        | OpCode::AddRR { dest, .. }
+        | OpCode::AddRC { dest, .. }
+        | OpCode::AddCR { dest, .. }
+        | OpCode::AddCC { dest, .. }
+        | OpCode::SubRR { dest, .. }
+        | OpCode::SubRC { dest, .. }
+        | OpCode::SubCR { dest, .. }
+        | OpCode::SubCC { dest, .. }
+        | OpCode::MulRR { dest, .. }
+        | OpCode::MulRC { dest, .. }
+        | OpCode::MulCR { dest, .. }
+        | OpCode::MulCC { dest, .. }
+        | OpCode::ModRR { dest, .. }
+        | OpCode::ModRC { dest, .. }
+        | OpCode::ModCR { dest, .. }
+        | OpCode::ModCC { dest, .. }
+        | OpCode::PowRR { dest, .. }
+        | OpCode::PowRC { dest, .. }
+        | OpCode::PowCR { dest, .. }
+        | OpCode::PowCC { dest, .. }
+        | OpCode::DivRR { dest, .. }
+        | OpCode::DivRC { dest, .. }
+        | OpCode::DivCR { dest, .. }
+        | OpCode::DivCC { dest, .. }
+        | OpCode::IDivRR { dest, .. }
+        | OpCode::IDivRC { dest, .. }
+        | OpCode::IDivCR { dest, .. }
+        | OpCode::IDivCC { dest, .. }
+        | OpCode::BitAndRR { dest, .. }
+        | OpCode::BitAndRC { dest, .. }
+        | OpCode::BitAndCR { dest, .. }
+        | OpCode::BitAndCC { dest, .. }
+        | OpCode::BitOrRR { dest, .. }
+        | OpCode::BitOrRC { dest, .. }
+        | OpCode::BitOrCR { dest, .. }
+        | OpCode::BitOrCC { dest, .. }
+        | OpCode::BitXorRR { dest, .. }
+        | OpCode::BitXorRC { dest, .. }
+        | OpCode::BitXorCR { dest, .. }
+        | OpCode::BitXorCC { dest, .. }
+        | OpCode::ShiftLeftRR { dest, .. }
+        | OpCode::ShiftLeftRC { dest, .. }
+        | OpCode::ShiftLeftCR { dest, .. }
+        | OpCode::ShiftLeftCC { dest, .. }
+        | OpCode::ShiftRightRR { dest, .. }
+        | OpCode::ShiftRightRC { dest, .. }
+        | OpCode::ShiftRightCR { dest, .. }
+        | OpCode::ShiftRightCC { dest, .. } => Some(dest),
+        _ => None,
+    }
+}
+
+// Returns whether the opcode may skip the opcode following it, landing on the one after that.
+fn opcode_may_skip(opcode: &OpCode) -> bool {
+    match opcode {
+        OpCode::EqRR { .. }
+        | OpCode::EqRC { .. }
+        | OpCode::EqCR { .. }
+        | OpCode::EqCC { .. }
+        | OpCode::LessRR { .. }
+        | OpCode::LessRC { .. }
+        | OpCode::LessCR { .. }
+        | OpCode::LessCC { .. }
+        | OpCode::LessEqRR { .. }
+        | OpCode::LessEqRC { .. }
+        | OpCode::LessEqCR { .. }
+        | OpCode::LessEqCC { .. }
+        | OpCode::Test { .. }
+        | OpCode::TestSet { .. }
+        | OpCode::LoadBool {
+            skip_next: true, ..
+        }
+        | OpCode::Jump { offset: 1, .. } => true,
+        _ => false,
+    }
+}
+
+fn jump_label_name(interner: &Interner, label: JumpLabel) -> std::string::String {
+    match label {
+        JumpLabel::Named(name) => {
+            std::string::String::from_utf8_lossy(interner.name(name)).into_owned()
+        }
+        JumpLabel::Break => "break".to_owned(),
+        JumpLabel::Unique(_) => unreachable!("unique labels are never invalid jump targets"),
+    }
+}
+
 fn jump_offset(source: usize, target: usize) -> Option<i16> {
     if target > source {
         cast(target - (source + 1))
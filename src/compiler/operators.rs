@@ -100,7 +100,160 @@ pub fn simple_binop_opcode(
                 OpCode::AddCC { dest, left, right }
             }
         },
-        _ => panic!("unsupported binary operator {:?}", simple_binop),
+        SimpleBinOp::Sub => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::SubRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::SubRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::SubCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::SubCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::Mul => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::MulRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::MulRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::MulCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::MulCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::Mod => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::ModRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::ModRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::ModCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::ModCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::Pow => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::PowRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::PowRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::PowCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::PowCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::Div => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::DivRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::DivRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::DivCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::DivCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::IDiv => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::IDivRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::IDivRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::IDivCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::IDivCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::BitAnd => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::BitAndRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::BitAndRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::BitAndCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::BitAndCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::BitOr => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::BitOrRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::BitOrRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::BitOrCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::BitOrCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::BitXor => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::BitXorRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::BitXorRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::BitXorCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::BitXorCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::ShiftLeft => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::ShiftLeftRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::ShiftLeftRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::ShiftLeftCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::ShiftLeftCC { dest, left, right }
+            }
+        },
+        SimpleBinOp::ShiftRight => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::ShiftRightRR { dest, left, right }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::ShiftRightRC { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::ShiftRightCR { dest, left, right }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::ShiftRightCC { dest, left, right }
+            }
+        },
     }
 }
 
@@ -111,7 +264,17 @@ pub fn simple_binop_const_fold<'gc>(
 ) -> Option<Value<'gc>> {
     match simple_binop {
         SimpleBinOp::Add => left.add(right),
-        _ => None,
+        SimpleBinOp::Sub => left.subtract(right),
+        SimpleBinOp::Mul => left.multiply(right),
+        SimpleBinOp::Mod => left.modulo(right),
+        SimpleBinOp::Pow => left.power(right),
+        SimpleBinOp::Div => left.divide(right),
+        SimpleBinOp::IDiv => left.floor_divide(right),
+        SimpleBinOp::BitAnd => left.bitwise_and(right),
+        SimpleBinOp::BitOr => left.bitwise_or(right),
+        SimpleBinOp::BitXor => left.bitwise_xor(right),
+        SimpleBinOp::ShiftLeft => left.shift_left(right),
+        SimpleBinOp::ShiftRight => left.shift_right(right),
     }
 }
 
@@ -182,7 +345,127 @@ pub fn comparison_binop_opcode(
                 }
             }
         },
-        _ => panic!("unsupported binary operator {:?}", comparison_binop),
+        ComparisonBinOp::LessThan => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessRR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessRC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessCR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessCC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+        },
+        ComparisonBinOp::LessEqual => match (left, right) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessEqRR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessEqRC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessEqCR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessEqCC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+        },
+        // `a > b` is evaluated as `b < a`, and `a >= b` as `b <= a`
+        ComparisonBinOp::GreaterThan => match (right, left) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessRR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessRC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessCR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessCC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+        },
+        ComparisonBinOp::GreaterEqual => match (right, left) {
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessEqRR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Register(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessEqRC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Register(right)) => {
+                OpCode::LessEqCR {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+            (RegisterOrConstant::Constant(left), RegisterOrConstant::Constant(right)) => {
+                OpCode::LessEqCC {
+                    skip_if,
+                    left,
+                    right,
+                }
+            }
+        },
     }
 }
 
@@ -192,21 +475,29 @@ pub fn comparison_binop_const_fold<'gc>(
     right: Value<'gc>,
 ) -> Option<Value<'gc>> {
     match comparison_binop {
+        ComparisonBinOp::NotEqual => Some(Value::Boolean(left != right)),
         ComparisonBinOp::Equal => Some(Value::Boolean(left == right)),
-        _ => None,
+        ComparisonBinOp::LessThan => left.less_than(right).map(Value::Boolean),
+        ComparisonBinOp::LessEqual => left.less_equal(right).map(Value::Boolean),
+        ComparisonBinOp::GreaterThan => right.less_than(left).map(Value::Boolean),
+        ComparisonBinOp::GreaterEqual => right.less_equal(left).map(Value::Boolean),
     }
 }
 
 pub fn unop_opcode(unop: UnaryOperator, dest: RegisterIndex, source: RegisterIndex) -> OpCode {
     match unop {
         UnaryOperator::Not => OpCode::Not { dest, source },
-        _ => panic!("unimplemented unary operator {:?}", unop),
+        UnaryOperator::Minus => OpCode::Minus { dest, source },
+        UnaryOperator::BitNot => OpCode::BitNot { dest, source },
+        UnaryOperator::Len => OpCode::Length { dest, source },
     }
 }
 
 pub fn unop_const_fold<'gc>(unop: UnaryOperator, value: Value<'gc>) -> Option<Value<'gc>> {
     match unop {
         UnaryOperator::Not => Some(Value::Boolean(!value.as_bool())),
-        _ => None,
+        UnaryOperator::Minus => value.negate(),
+        UnaryOperator::BitNot => value.bitwise_not(),
+        UnaryOperator::Len => None,
     }
 }
@@ -0,0 +1,124 @@
+/// Limits and checks applied while compiling a chunk, allowing embedders to restrict the size of
+/// the code they accept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompilerOptions {
+    max_registers: u8,
+    max_constants: usize,
+    max_function_depth: usize,
+    strict: bool,
+    strip_debug: bool,
+    optimization_level: OptimizationLevel,
+}
+
+/// How much work the compiler does to make the generated code faster.  Each level includes the
+/// optimizations of the levels below it.
+///
+/// Folding operators applied to literals and specializing numeric for loops over integer constants
+/// are done at every level, as the behavior of a for loop whose counter overflows depends on it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OptimizationLevel {
+    /// Compiles every expression and statement as written, which is the fastest to compile and
+    /// keeps every local in its own register.
+    O0,
+    /// Adds optimizations which only look at the code being generated: writing results directly to
+    /// their destination rather than through a temporary, and compiling if statements which compare
+    /// a local against constants to a `Switch`.
+    O1,
+    /// Adds optimizations which look at whole statements and functions: replacing reads of locals
+    /// which always hold a constant with the constant itself, and loading a global read more than
+    /// once in a statement only once.
+    O2,
+}
+
+/// Creates a default CompilerOptions with the largest limits that the VM supports, with strict mode
+/// disabled, with debug information included, and with every optimization enabled.
+impl Default for CompilerOptions {
+    fn default() -> CompilerOptions {
+        CompilerOptions {
+            max_registers: MAX_REGISTERS,
+            max_constants: MAX_CONSTANTS,
+            max_function_depth: MAX_FUNCTION_DEPTH,
+            strict: false,
+            strip_debug: false,
+            optimization_level: OptimizationLevel::O2,
+        }
+    }
+}
+
+impl CompilerOptions {
+    /// The most registers that any single function may use.
+    pub fn set_max_registers(mut self, max_registers: u8) -> CompilerOptions {
+        self.max_registers = max_registers;
+        self
+    }
+
+    /// The most constants that any single function may hold.  Limits larger than 65536 are treated
+    /// as 65536.
+    pub fn set_max_constants(mut self, max_constants: usize) -> CompilerOptions {
+        self.max_constants = max_constants.min(MAX_CONSTANTS);
+        self
+    }
+
+    /// How deeply function definitions may be nested inside of each other, the main function of a
+    /// chunk is not counted.
+    pub fn set_max_function_depth(mut self, max_function_depth: usize) -> CompilerOptions {
+        self.max_function_depth = max_function_depth;
+        self
+    }
+
+    /// In strict mode, globals may only be assigned at the top level of a chunk, an assignment to a
+    /// global inside of any function is an error.
+    pub fn set_strict(mut self, strict: bool) -> CompilerOptions {
+        self.strict = strict;
+        self
+    }
+
+    /// Omits the line numbers and local variable names from every compiled `FunctionProto`, making
+    /// prototypes smaller at the cost of runtime errors which are no longer located at a line.
+    pub fn set_strip_debug(mut self, strip_debug: bool) -> CompilerOptions {
+        self.strip_debug = strip_debug;
+        self
+    }
+
+    /// Selects the optimizations which change the generated code without changing its behavior,
+    /// trading compile time and how closely the code follows the source for runtime speed.
+    pub fn set_optimization_level(
+        mut self,
+        optimization_level: OptimizationLevel,
+    ) -> CompilerOptions {
+        self.optimization_level = optimization_level;
+        self
+    }
+
+    pub fn max_registers(&self) -> u8 {
+        self.max_registers
+    }
+
+    pub fn max_constants(&self) -> usize {
+        self.max_constants
+    }
+
+    pub fn max_function_depth(&self) -> usize {
+        self.max_function_depth
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn strip_debug(&self) -> bool {
+        self.strip_debug
+    }
+
+    pub fn optimization_level(&self) -> OptimizationLevel {
+        self.optimization_level
+    }
+}
+
+// Register 255 is never allocatable, see `RegisterAllocator`
+const MAX_REGISTERS: u8 = 255;
+
+// Constants are indexed by a `ConstantIndex16`
+const MAX_CONSTANTS: usize = 1 << 16;
+
+const MAX_FUNCTION_DEPTH: usize = 200;
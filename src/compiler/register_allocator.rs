@@ -1,6 +1,7 @@
 use crate::types::RegisterIndex;
 
-/// Allocates registers in the range [0-254].
+/// Allocates registers in the range [0-254], or in a smaller range [0-limit) if a lower limit is
+/// given.
 ///
 /// Register 255 is not allocatable because:
 ///   1) PUC-Rio Lua has a similar maximum
@@ -15,20 +16,28 @@ pub struct RegisterAllocator {
     stack_top: u8,
     // The index of the largest used register + 1 (e.g. the stack size required for the function)
     stack_size: u8,
+    // No register at or above this index will be allocated
+    limit: u8,
 }
 
 impl Default for RegisterAllocator {
     fn default() -> RegisterAllocator {
+        RegisterAllocator::new(255)
+    }
+}
+
+impl RegisterAllocator {
+    /// Creates an allocator which only allocates registers below the given limit.
+    pub fn new(limit: u8) -> RegisterAllocator {
         RegisterAllocator {
             registers: [false; 255],
             first_free: 0,
             stack_top: 0,
             stack_size: 0,
+            limit,
         }
     }
-}
 
-impl RegisterAllocator {
     /// Returns the free register index after the currently largest used register index
     pub fn stack_top(&self) -> u8 {
         self.stack_top
@@ -48,7 +57,7 @@ impl RegisterAllocator {
 
     /// Allocates any single available register, returns it if one is available.
     pub fn allocate(&mut self) -> Option<RegisterIndex> {
-        if self.first_free < 255 {
+        if self.first_free < self.limit {
             let register = self.first_free as u8;
             self.registers[register as usize] = true;
 
@@ -59,7 +68,7 @@ impl RegisterAllocator {
 
             let mut i = self.first_free;
             self.first_free = loop {
-                if i == 255 || !self.registers[i as usize] {
+                if i == self.limit || !self.registers[i as usize] {
                     break i;
                 }
                 i += 1;
@@ -90,7 +99,7 @@ impl RegisterAllocator {
     pub fn push(&mut self, size: u8) -> Option<RegisterIndex> {
         if size == 0 {
             None
-        } else if size <= 255 - self.stack_top {
+        } else if size <= self.limit - self.stack_top {
             let rbegin = self.stack_top as u8;
             for i in rbegin..rbegin + size {
                 self.registers[i as usize] = true;
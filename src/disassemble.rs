@@ -0,0 +1,108 @@
+use std::ascii;
+use std::fmt::{self, Write};
+
+use crate::function::FunctionProto;
+use crate::value::Value;
+
+/// Produces a human readable listing of a compiled function prototype and all of its inner
+/// prototypes, for debugging the compiler and for comparing compiler output in tests.
+///
+/// Inner prototypes are listed after their parent, and are named by their path of prototype
+/// indexes from the outermost function, so the second function defined inside of the main chunk's
+/// first function is `main.0.1`.
+pub fn disassemble(proto: &FunctionProto) -> String {
+    let mut output = String::new();
+    disassemble_proto(&mut output, "main", proto).unwrap();
+    output
+}
+
+fn disassemble_proto(output: &mut String, name: &str, proto: &FunctionProto) -> fmt::Result {
+    writeln!(
+        output,
+        "function {} (params: {}, varargs: {}, stack size: {})",
+        name, proto.fixed_params, proto.has_varargs, proto.stack_size
+    )?;
+
+    if !proto.constants.is_empty() {
+        writeln!(output, "constants:")?;
+        for (i, &constant) in proto.constants.iter().enumerate() {
+            write!(output, "  {:>4}  ", i)?;
+            write_constant(output, constant)?;
+            writeln!(output)?;
+        }
+    }
+
+    if !proto.upvalues.is_empty() {
+        writeln!(output, "upvalues:")?;
+        for (i, upvalue) in proto.upvalues.iter().enumerate() {
+            writeln!(output, "  {:>4}  {:?}", i, upvalue)?;
+        }
+    }
+
+    if !proto.local_variables.is_empty() {
+        writeln!(output, "locals:")?;
+        for (i, local) in proto.local_variables.iter().enumerate() {
+            write!(output, "  {:>4}  ", i)?;
+            write_constant(output, Value::String(local.name))?;
+            writeln!(
+                output,
+                "  register: {}, opcodes: {}..{}",
+                local.register.0, local.start, local.end
+            )?;
+        }
+    }
+
+    if !proto.switches.is_empty() {
+        writeln!(output, "switches:")?;
+        for (i, table) in proto.switches.iter().enumerate() {
+            write!(output, "  {:>4}  ", i)?;
+            let mut key = Value::Nil;
+            while let Some((case, target)) = table.next(key).unwrap() {
+                if key != Value::Nil {
+                    write!(output, ", ")?;
+                }
+                write_constant(output, case)?;
+                write!(output, " => ")?;
+                write_constant(output, target)?;
+                key = case;
+            }
+            writeln!(output)?;
+        }
+    }
+
+    writeln!(output, "opcodes:")?;
+    for (i, opcode) in proto.opcodes.iter().enumerate() {
+        if let Some(line) = proto.opcode_line(i) {
+            writeln!(output, "  {:>4}  [{:>3}]  {:?}", i, line.0, opcode)?;
+        } else {
+            writeln!(output, "  {:>4}  [  -]  {:?}", i, opcode)?;
+        }
+    }
+
+    for (i, inner) in proto.prototypes.iter().enumerate() {
+        writeln!(output)?;
+        disassemble_proto(output, &format!("{}.{}", name, i), inner)?;
+    }
+
+    Ok(())
+}
+
+fn write_constant(output: &mut String, constant: Value) -> fmt::Result {
+    match constant {
+        Value::Nil => write!(output, "nil"),
+        Value::Boolean(b) => write!(output, "{}", b),
+        Value::Integer(i) => write!(output, "{}", i),
+        Value::Number(n) => write!(output, "{:?}", n),
+        Value::String(s) => {
+            write!(output, "\"")?;
+            for &c in s.as_bytes() {
+                for e in ascii::escape_default(c) {
+                    output.push(e as char);
+                }
+            }
+            write!(output, "\"")
+        }
+        Value::Table(_) => write!(output, "<table>"),
+        Value::Closure(_) => write!(output, "<function>"),
+    }
+}
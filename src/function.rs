@@ -5,9 +5,10 @@ use failure::{bail, Error};
 use gc_arena::{Collect, Gc, GcCell, MutationContext};
 
 use crate::opcode::OpCode;
+use crate::string::String;
 use crate::table::Table;
 use crate::thread::Thread;
-use crate::types::{RegisterIndex, UpValueIndex};
+use crate::types::{LineNumber, RegisterIndex, UpValueIndex};
 use crate::value::Value;
 
 #[derive(Debug, Collect, Clone, Copy, PartialEq, Eq)]
@@ -18,16 +19,46 @@ pub enum UpValueDescriptor {
     Outer(UpValueIndex),
 }
 
+/// Debug information for a local variable, which is held in `register` by the opcodes in the range
+/// `start..end`.
+#[derive(Debug, Copy, Clone, Collect)]
+#[collect(require_copy)]
+pub struct LocalVariable<'gc> {
+    pub name: String<'gc>,
+    pub register: RegisterIndex,
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug, Collect)]
 #[collect(empty_drop)]
 pub struct FunctionProto<'gc> {
+    /// The name of the chunk the function was compiled from, used to locate runtime errors
+    pub chunk_name: String<'gc>,
     pub fixed_params: u8,
     pub has_varargs: bool,
     pub stack_size: u8,
     pub constants: Vec<Value<'gc>>,
     pub opcodes: Vec<OpCode>,
+    /// Pairs of opcode index and line number, each marking the first opcode generated for that
+    /// line, ordered by opcode index.
+    pub opcode_lines: Vec<(usize, LineNumber)>,
+    /// Every local variable declared in the function, in order of declaration
+    pub local_variables: Vec<LocalVariable<'gc>>,
     pub upvalues: Vec<UpValueDescriptor>,
     pub prototypes: Vec<Gc<'gc, FunctionProto<'gc>>>,
+    pub switches: Vec<Table<'gc>>,
+}
+
+impl<'gc> FunctionProto<'gc> {
+    /// Returns the source line which generated the opcode at the given index, if known.
+    pub fn opcode_line(&self, opcode: usize) -> Option<LineNumber> {
+        let next = match self.opcode_lines.binary_search_by_key(&opcode, |&(i, _)| i) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        next.checked_sub(1).map(|i| self.opcode_lines[i].1)
+    }
 }
 
 #[derive(Debug, Collect, Copy, Clone)]
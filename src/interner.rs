@@ -0,0 +1,93 @@
+use std::hash::Hasher;
+
+use fnv::{FnvHashMap, FnvHasher};
+
+/// A compact identifier for a name interned in an `Interner`.  Symbols from the same `Interner` are
+/// equal exactly when their names are equal, so they can be compared without looking at the names.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// The symbol for `_ENV`, which every `Interner` contains.
+    pub const ENV: Symbol = Symbol(0);
+    /// The symbol for `self`, which every `Interner` contains.
+    pub const SELF: Symbol = Symbol(1);
+
+    // A symbol which no `Interner` will ever produce, used for compiler generated names which must
+    // not conflict with any name in the source.
+    pub(crate) const HIDDEN: Symbol = Symbol(u32::MAX);
+}
+
+/// Maps names to `Symbol`s and back.  Names are interned by the lexer as they are read, so every
+/// occurrence of the same identifier in a chunk is stored only once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interner {
+    names: Vec<Box<[u8]>>,
+    // Maps the hash of a name to the last symbol interned with that hash.  Symbols whose names have
+    // the same hash are chained through `next`, so that names themselves are kept only in `names`.
+    heads: FnvHashMap<u64, Symbol>,
+    next: Vec<Option<Symbol>>,
+}
+
+impl Default for Interner {
+    fn default() -> Interner {
+        Interner::new()
+    }
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        let mut interner = Interner {
+            names: Vec::new(),
+            heads: FnvHashMap::default(),
+            next: Vec::new(),
+        };
+        assert_eq!(interner.intern(b"_ENV"), Symbol::ENV);
+        assert_eq!(interner.intern(b"self"), Symbol::SELF);
+        interner
+    }
+
+    /// Returns the symbol for the given name, adding it if it has not been interned yet.
+    pub fn intern(&mut self, name: &[u8]) -> Symbol {
+        let hash = hash_name(name);
+        if let Some(symbol) = self.find(hash, name) {
+            symbol
+        } else {
+            assert!(
+                self.names.len() < u32::MAX as usize,
+                "too many interned names"
+            );
+            let symbol = Symbol(self.names.len() as u32);
+            self.names.push(name.into());
+            self.next.push(self.heads.insert(hash, symbol));
+            symbol
+        }
+    }
+
+    /// Returns the symbol for the given name only if it has already been interned.
+    pub fn get(&self, name: &[u8]) -> Option<Symbol> {
+        self.find(hash_name(name), name)
+    }
+
+    /// Returns the name of the given symbol, which must have come from this `Interner`.
+    pub fn name(&self, symbol: Symbol) -> &[u8] {
+        &self.names[symbol.0 as usize]
+    }
+
+    fn find(&self, hash: u64, name: &[u8]) -> Option<Symbol> {
+        let mut next = self.heads.get(&hash).cloned();
+        while let Some(symbol) = next {
+            if &*self.names[symbol.0 as usize] == name {
+                return Some(symbol);
+            }
+            next = self.next[symbol.0 as usize];
+        }
+        None
+    }
+}
+
+fn hash_name(name: &[u8]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(name);
+    hasher.finish()
+}
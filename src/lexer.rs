@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::io::{self, Read};
-use std::{char, i32, i64, mem, str};
+use std::{char, fmt, i32, i64, mem, str};
 
-use failure::{err_msg, format_err, Error};
+use failure::{err_msg, format_err, Error, Fail};
 use lazy_static::lazy_static;
 
+use crate::interner::{Interner, Symbol};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Break,
@@ -66,21 +68,40 @@ pub enum Token {
     /// lexed as floats.
     Integer(i64),
     Float(f64),
-    Name(Box<[u8]>),
+    Name(Symbol),
     String(Box<[u8]>),
 }
 
+/// Error returned when the source ends inside of a long string or long comment.
+#[derive(Debug)]
+pub struct UnfinishedLongString;
+
+impl fmt::Display for UnfinishedLongString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unfinished long string")
+    }
+}
+
+impl Fail for UnfinishedLongString {}
+
 pub struct Lexer<R: Read> {
     source: Option<R>,
     peek_buffer: Vec<u8>,
     output_buffer: Vec<u8>,
     line_number: u64,
+    interner: Interner,
     char_tokens: &'static HashMap<u8, Token>,
     reserved_words: &'static HashMap<&'static [u8], Token>,
 }
 
 impl<R: Read> Lexer<R> {
     pub fn new(source: R) -> Lexer<R> {
+        Lexer::with_interner(source, Interner::new())
+    }
+
+    /// Creates a lexer which interns names into an existing `Interner`, so that symbols are shared
+    /// with previously lexed sources.
+    pub fn with_interner(source: R, interner: Interner) -> Lexer<R> {
         lazy_static! {
             static ref CHAR_TOKEN_MAP: HashMap<u8, Token> = {
                 let mut m = HashMap::new();
@@ -103,11 +124,21 @@ impl<R: Read> Lexer<R> {
             peek_buffer: Vec::new(),
             output_buffer: Vec::new(),
             line_number: 0,
+            interner,
             char_tokens: &*CHAR_TOKEN_MAP,
             reserved_words: &*RESERVED_WORD_MAP,
         }
     }
 
+    /// The interner holding every name read so far
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+
+    pub fn into_interner(self) -> Interner {
+        self.interner
+    }
+
     /// Current line number of the source file, 0-indexed
     pub fn line_number(&self) -> u64 {
         self.line_number
@@ -307,7 +338,7 @@ impl<R: Read> Lexer<R> {
                         {
                             t
                         } else {
-                            Token::Name(self.take_output())
+                            Token::Name(self.interner.intern(&self.output_buffer))
                         }
                     } else {
                         return Err(format_err!(
@@ -538,7 +569,7 @@ impl<R: Read> Lexer<R> {
             let c = if let Some(c) = self.peek(0)? {
                 c
             } else {
-                return Err(err_msg("unfinished long string"));
+                return Err(UnfinishedLongString.into());
             };
 
             match c {
@@ -708,6 +739,8 @@ pub enum Numeral {
     Float(f64),
 }
 
+/// Returns None if the decimal integer does not fit in an i64, in which case it should be read as a
+/// float instead.
 pub fn read_integer(s: &[u8]) -> Option<i64> {
     let (is_neg, s) = read_neg(s);
 
@@ -728,28 +761,26 @@ pub fn read_integer(s: &[u8]) -> Option<i64> {
     }
 }
 
+/// Hex integers wrap around modulo 2^64 rather than overflowing, so for example `0xffffffffffffffff`
+/// is -1.
 pub fn read_hex_integer(s: &[u8]) -> Option<i64> {
     let (is_neg, s) = read_neg(s);
 
-    if s[0] != b'0' || (s[1] != b'x' && s[1] != b'X') {
+    if s.len() < 3 || s[0] != b'0' || (s[1] != b'x' && s[1] != b'X') {
         return None;
     }
 
     let mut i: i64 = 0;
     for &c in &s[2..] {
         let d = from_hex_digit(c)? as i64;
-        i = i.checked_mul(16)?.checked_add(d)?;
+        i = i.wrapping_mul(16).wrapping_add(d);
     }
 
     if is_neg {
-        i = i.checked_neg()?;
+        i = i.wrapping_neg();
     }
 
-    if i == i64::MIN {
-        None
-    } else {
-        Some(i)
-    }
+    Some(i)
 }
 
 pub fn read_float(s: &[u8]) -> Option<f64> {
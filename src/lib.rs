@@ -1,11 +1,16 @@
 pub mod compiler;
+pub mod disassemble;
 pub mod function;
+pub mod interner;
 pub mod io;
 pub mod lexer;
 pub mod lua;
 pub mod opcode;
 pub mod parser;
+pub mod preprocessor;
+pub mod scope;
 pub mod sequence;
+pub mod snapshot;
 pub mod string;
 pub mod table;
 pub mod thread;
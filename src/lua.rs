@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::marker::PhantomData;
 
 use failure::Error;
 
@@ -13,24 +14,88 @@ use crate::thread::Thread;
 pub struct LuaContext<'gc> {
     pub main_thread: Thread<'gc>,
     pub globals: Table<'gc>,
+    /// A table for host code to store values that should live as long as the `Lua` instance, which
+    /// is never visible to scripts.
+    pub registry: Table<'gc>,
+    /// The number of VM instructions that should be executed during each step of a thread
+    /// `Sequence`, as configured by `VmOptions::set_granularity`.
+    pub granularity: u32,
+}
+
+/// Runtime configuration for a `Lua` instance, provided at construction time.
+#[derive(Debug, Clone)]
+pub struct VmOptions {
+    arena_parameters: ArenaParameters,
+    granularity: u32,
+}
+
+/// Creates a default VmOptions with the default `ArenaParameters` and `granularity` set to 64.
+impl Default for VmOptions {
+    fn default() -> VmOptions {
+        const GRANULARITY: u32 = 64;
+
+        VmOptions {
+            arena_parameters: ArenaParameters::default(),
+            granularity: GRANULARITY,
+        }
+    }
+}
+
+impl VmOptions {
+    /// Parameters for the garbage collector.  These cannot be changed once the `Lua` instance is
+    /// constructed.
+    pub fn set_arena_parameters(mut self, arena_parameters: ArenaParameters) -> VmOptions {
+        self.arena_parameters = arena_parameters;
+        self
+    }
+
+    /// The number of VM instructions that should be executed at a time between garbage collector
+    /// steps, available to sequences as `LuaContext::granularity`.  A granularity of zero is
+    /// treated as 1.
+    pub fn set_granularity(mut self, granularity: u32) -> VmOptions {
+        self.granularity = granularity.max(1);
+        self
+    }
+
+    pub fn arena_parameters(&self) -> &ArenaParameters {
+        &self.arena_parameters
+    }
+
+    pub fn granularity(&self) -> u32 {
+        self.granularity
+    }
 }
 
 pub struct Lua {
     arena: LuaArena,
+    options: VmOptions,
 }
 
 impl Lua {
     pub fn new() -> Lua {
-        let arena = LuaArena::new(ArenaParameters::default(), |mc| LuaRoot {
-            context: LuaContext {
-                main_thread: Thread::new(mc),
-                globals: Table::new(mc),
-            },
+        Lua::with_options(VmOptions::default())
+    }
+
+    pub fn with_options(options: VmOptions) -> Lua {
+        let arena = LuaArena::new(options.arena_parameters.clone(), |mc| LuaRoot {
+            main_thread: Thread::new(mc),
+            globals: Table::new(mc),
+            registry: Table::new(mc),
             current_sequence: GcCell::allocate(mc, None),
         });
-        Lua { arena }
+        Lua { arena, options }
+    }
+
+    pub fn options(&self) -> &VmOptions {
+        &self.options
     }
 
+    /// Changes the granularity for all sequences created after this call.
+    pub fn set_granularity(&mut self, granularity: u32) {
+        self.options = self.options.clone().set_granularity(granularity);
+    }
+
+    /// Runs a `Sequence` to completion, collecting garbage between each step.
     pub fn sequence<F, R>(&mut self, f: F) -> Result<R, Error>
     where
         F: for<'gc> FnOnce(
@@ -39,49 +104,134 @@ impl Lua {
         ) -> Result<Box<dyn Sequence<'gc, Item = R> + 'gc>, Error>,
         R: 'static,
     {
+        let granularity = self.options.granularity();
+        let mut executor = self.executor(f)?;
+        loop {
+            match executor.step(granularity) {
+                Step::Done(r) => return Ok(r),
+                Step::Error(e) => return Err(e),
+                Step::Pending => {}
+            }
+        }
+    }
+
+    /// Starts a `Sequence` without running it, so that the host can run it a bounded number of
+    /// VM instructions at a time with `Executor::step`.
+    pub fn executor<F, R>(&mut self, f: F) -> Result<Executor<'_, R>, Error>
+    where
+        F: for<'gc> FnOnce(
+            MutationContext<'gc, '_>,
+            LuaContext<'gc>,
+        ) -> Result<Box<dyn Sequence<'gc, Item = R> + 'gc>, Error>,
+        R: 'static,
+    {
+        let granularity = self.options.granularity;
         self.arena
             .mutate(move |mc, lua_root| -> Result<(), Error> {
+                let context = LuaContext {
+                    main_thread: lua_root.main_thread,
+                    globals: lua_root.globals,
+                    registry: lua_root.registry,
+                    granularity,
+                };
                 *lua_root.current_sequence.write(mc) = Some(Box::new(
-                    f(mc, lua_root.context)?
-                        .map(move |_, r| -> Result<Box<Any>, Error> { Ok(Box::new(r)) }),
+                    f(mc, context)?.map(move |_, r| -> Result<Box<Any>, Error> { Ok(Box::new(r)) }),
                 ));
                 Ok(())
             })?;
         self.arena.collect_debt();
 
-        loop {
-            let r = self.arena.mutate(move |mc, lua_root| {
+        Ok(Executor {
+            lua: self,
+            finished: false,
+            _marker: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Step<R> {
+    Done(R),
+    Pending,
+    Error(Error),
+}
+
+/// A `Sequence` started by `Lua::executor` which is run in bounded slices.  Dropping an unfinished
+/// `Executor` abandons its sequence.
+pub struct Executor<'a, R> {
+    lua: &'a mut Lua,
+    finished: bool,
+    _marker: PhantomData<R>,
+}
+
+impl<'a, R: 'static> Executor<'a, R> {
+    /// Runs the sequence for at most `instructions` VM instructions on the main thread, pumping it
+    /// at most `granularity` instructions at a time and collecting garbage between each pump.  A
+    /// pump that executes no VM instructions counts as one instruction, so sequences that never
+    /// run Lua code still finish or return `Step::Pending`.
+    ///
+    /// Panics if the sequence has already returned `Step::Done` or `Step::Error`.
+    pub fn step(&mut self, instructions: u32) -> Step<R> {
+        assert!(!self.finished, "executor stepped after it finished");
+
+        let mut remaining = instructions;
+        while remaining > 0 {
+            let (r, executed) = self.lua.arena.mutate(move |mc, lua_root| {
+                lua_root
+                    .main_thread
+                    .set_instruction_budget(mc, Some(remaining));
                 let r = lua_root
                     .current_sequence
                     .write(mc)
                     .as_mut()
                     .unwrap()
                     .pump(mc);
+                let left = lua_root
+                    .main_thread
+                    .instruction_budget()
+                    .unwrap_or(remaining);
+                lua_root.main_thread.set_instruction_budget(mc, None);
                 if r.is_some() {
                     *lua_root.current_sequence.write(mc) = None;
                 }
-                r
+                (r, remaining - left)
             });
-            self.arena.collect_debt();
+            self.lua.arena.collect_debt();
+            remaining -= executed.max(1);
 
             if let Some(r) = r {
-                match r {
-                    Ok(r) => {
-                        return Ok(*Box::<Any + 'static>::downcast(r).unwrap());
-                    }
-                    Err(e) => {
-                        return Err(e);
-                    }
-                }
+                self.finished = true;
+                return match r {
+                    Ok(r) => Step::Done(*Box::<Any + 'static>::downcast(r).unwrap()),
+                    Err(e) => Step::Error(e),
+                };
             }
         }
+
+        Step::Pending
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+impl<'a, R> Drop for Executor<'a, R> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.lua.arena.mutate(|mc, lua_root| {
+                *lua_root.current_sequence.write(mc) = None;
+            });
+        }
     }
 }
 
 #[derive(Collect)]
 #[collect(empty_drop)]
 struct LuaRoot<'gc> {
-    context: LuaContext<'gc>,
+    main_thread: Thread<'gc>,
+    globals: Table<'gc>,
+    registry: Table<'gc>,
     current_sequence:
         GcCell<'gc, Option<Box<dyn Sequence<'gc, Item = Box<dyn Any + 'static>> + 'gc>>>,
 }
@@ -1,7 +1,8 @@
 use gc_arena::Collect;
 
 use crate::types::{
-    ConstantIndex16, ConstantIndex8, Opt254, PrototypeIndex, RegisterIndex, UpValueIndex, VarCount,
+    ConstantIndex16, ConstantIndex8, Opt254, PrototypeIndex, RegisterIndex, SwitchIndex,
+    UpValueIndex, VarCount,
 };
 
 #[derive(Debug, Copy, Clone, Collect)]
@@ -59,6 +60,30 @@ pub enum OpCode {
         key: ConstantIndex8,
         value: ConstantIndex8,
     },
+    // Prepares a method call `table:key(...)`:
+    //
+    // R(base + 1) = R(table)
+    // R(base) = R(table)[key]
+    SelfR {
+        base: RegisterIndex,
+        table: RegisterIndex,
+        key: RegisterIndex,
+    },
+    SelfC {
+        base: RegisterIndex,
+        table: RegisterIndex,
+        key: ConstantIndex8,
+    },
+    // Used to set the array entries of a table constructor:
+    //
+    // R(base)[R(base + 1) + i] = R(base + 2 + i) for i in 0..count
+    //
+    // R(base + 1) must be an integer.  If `count` is variable, the values extend to the top of the
+    // stack.
+    SetList {
+        base: RegisterIndex,
+        count: VarCount,
+    },
     GetUpTableR {
         dest: RegisterIndex,
         table: UpValueIndex,
@@ -94,6 +119,12 @@ pub enum OpCode {
         args: VarCount,
         returns: VarCount,
     },
+    // Calls R(func) with the given arguments, replacing the current function's frame.  The results
+    // of the call are returned from the current function.
+    TailCall {
+        func: RegisterIndex,
+        args: VarCount,
+    },
     Return {
         start: RegisterIndex,
         count: VarCount,
@@ -107,6 +138,13 @@ pub enum OpCode {
         // If set, close upvalues >= `close_upvalues`
         close_upvalues: Opt254,
     },
+    // Look up the value in the given switch table, which maps constants to case numbers.  Skip
+    // that many instructions for a matching case, or none if there is no match.  A Switch is
+    // followed by a list of jumps, the first for no match and one for each case.
+    Switch {
+        value: RegisterIndex,
+        table: SwitchIndex,
+    },
     // Test the register as a boolean, if its boolean value matches `is_true`, skip the next
     // instruction.
     Test {
@@ -147,6 +185,30 @@ pub enum OpCode {
         base: RegisterIndex,
         jump: i16,
     },
+    // Used to set up a numeric for loop whose parameters are known to be integers, with a non-zero
+    // step:
+    //
+    // if R(base) <?= R(base + 1) then
+    //     R(base + 3) = R(base)
+    // else
+    //     pc += jump
+    // end
+    IntegerForPrep {
+        base: RegisterIndex,
+        jump: i16,
+    },
+    // Used to iterate a numeric for loop whose parameters are known to be integers.  The loop ends
+    // without overflowing if adding the step would overflow:
+    //
+    // R(base) += R(base + 2)
+    // if R(base) <?= R(base + 1) then
+    //     pc += jump
+    //     R(base + 3) = R(base)
+    // end
+    IntegerForLoop {
+        base: RegisterIndex,
+        jump: i16,
+    },
     // Used to set up for a generic for loop:
     //
     // R(base + 3), ..., R(base + 2 + var_count) = R(base)(R(base + 1), R(base + 2))
@@ -192,10 +254,68 @@ pub enum OpCode {
         left: ConstantIndex8,
         right: ConstantIndex8,
     },
+    LessRR {
+        skip_if: bool,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    LessRC {
+        skip_if: bool,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    LessCR {
+        skip_if: bool,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    LessCC {
+        skip_if: bool,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    LessEqRR {
+        skip_if: bool,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    LessEqRC {
+        skip_if: bool,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    LessEqCR {
+        skip_if: bool,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    LessEqCC {
+        skip_if: bool,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
     Not {
         dest: RegisterIndex,
         source: RegisterIndex,
     },
+    Minus {
+        dest: RegisterIndex,
+        source: RegisterIndex,
+    },
+    BitNot {
+        dest: RegisterIndex,
+        source: RegisterIndex,
+    },
+    Length {
+        dest: RegisterIndex,
+        source: RegisterIndex,
+    },
+    // R(dest) = R(source) .. R(source + 1) .. ... .. R(source + count - 1)
+    Concat {
+        dest: RegisterIndex,
+        source: RegisterIndex,
+        count: u8,
+    },
     AddRR {
         dest: RegisterIndex,
         left: RegisterIndex,
@@ -216,4 +336,224 @@ pub enum OpCode {
         left: ConstantIndex8,
         right: ConstantIndex8,
     },
+    SubRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    SubRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    SubCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    SubCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    MulRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    MulRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    MulCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    MulCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    ModRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    ModRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    ModCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    ModCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    PowRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    PowRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    PowCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    PowCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    DivRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    DivRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    DivCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    DivCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    IDivRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    IDivRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    IDivCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    IDivCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    BitAndRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    BitAndRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    BitAndCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    BitAndCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    BitOrRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    BitOrRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    BitOrCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    BitOrCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    BitXorRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    BitXorRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    BitXorCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    BitXorCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    ShiftLeftRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    ShiftLeftRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    ShiftLeftCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    ShiftLeftCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
+    ShiftRightRR {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: RegisterIndex,
+    },
+    ShiftRightRC {
+        dest: RegisterIndex,
+        left: RegisterIndex,
+        right: ConstantIndex8,
+    },
+    ShiftRightCR {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: RegisterIndex,
+    },
+    ShiftRightCC {
+        dest: RegisterIndex,
+        left: ConstantIndex8,
+        right: ConstantIndex8,
+    },
 }
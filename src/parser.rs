@@ -1,18 +1,32 @@
+use std::fmt;
 use std::io::Read;
 use std::rc::Rc;
 
-use failure::{err_msg, format_err, Error};
+use failure::{err_msg, format_err, Error, Fail};
 
-use crate::lexer::{Lexer, Token};
+use crate::interner::{Interner, Symbol};
+use crate::lexer::{Lexer, Token, UnfinishedLongString};
+use crate::types::LineNumber;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Chunk {
     pub block: Block,
+    /// Holds the names of every `Symbol` in the chunk
+    pub interner: Interner,
+}
+
+/// A single expression parsed on its own, outside of any chunk
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExpressionChunk {
+    pub expression: Expression,
+    pub interner: Interner,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Block {
     pub statements: Vec<Statement>,
+    /// The line on which each statement in `statements` begins
+    pub lines: Vec<LineNumber>,
     pub return_statement: Option<ReturnStatement>,
 }
 
@@ -36,6 +50,7 @@ pub enum Statement {
 #[derive(Debug, PartialEq, Clone)]
 pub struct ReturnStatement {
     pub returns: Vec<Expression>,
+    pub line: LineNumber,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -54,14 +69,14 @@ pub struct WhileStatement {
 #[derive(Debug, PartialEq, Clone)]
 pub enum ForStatement {
     Numeric {
-        name: Box<[u8]>,
+        name: Symbol,
         initial: Expression,
         limit: Expression,
         step: Option<Expression>,
         body: Block,
     },
     Generic {
-        names: Vec<Box<[u8]>>,
+        names: Vec<Symbol>,
         arguments: Vec<Expression>,
         body: Block,
     },
@@ -75,12 +90,12 @@ pub struct RepeatStatement {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LabelStatement {
-    pub name: Box<[u8]>,
+    pub name: Symbol,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct GotoStatement {
-    pub name: Box<[u8]>,
+    pub name: Symbol,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -91,7 +106,7 @@ pub struct FunctionStatement {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LocalStatement {
-    pub names: Vec<Box<[u8]>>,
+    pub names: Vec<Symbol>,
     pub values: Vec<Expression>,
 }
 
@@ -156,19 +171,19 @@ pub enum SimpleExpression {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum PrimaryExpression {
-    Name(Box<[u8]>),
+    Name(Symbol),
     GroupedExpression(Expression),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum FieldSuffix {
-    Named(Box<[u8]>),
+    Named(Symbol),
     Indexed(Expression),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum CallSuffix {
-    Method(Box<[u8]>, Vec<Expression>),
+    Method(Symbol, Vec<Expression>),
     Function(Vec<Expression>),
 }
 
@@ -186,7 +201,7 @@ pub struct SuffixedExpression {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionDefinition {
-    pub parameters: Vec<Box<[u8]>>,
+    pub parameters: Vec<Symbol>,
     pub has_varargs: bool,
     pub body: Block,
 }
@@ -205,15 +220,15 @@ pub struct AssignmentStatement {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AssignmentTarget {
-    Name(Box<[u8]>),
+    Name(Symbol),
     Field(SuffixedExpression, FieldSuffix),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct FunctionName {
-    pub name: Box<[u8]>,
-    pub fields: Vec<Box<[u8]>>,
-    pub method: Option<Box<[u8]>>,
+    pub name: Symbol,
+    pub fields: Vec<Symbol>,
+    pub method: Option<Symbol>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -229,10 +244,36 @@ pub enum ConstructorField {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum RecordKey {
-    Named(Box<[u8]>),
+    Named(Symbol),
     Indexed(Expression),
 }
 
+/// Error returned when the token stream ends before a complete chunk or expression has been parsed.
+#[derive(Debug)]
+pub struct UnexpectedEnd {
+    expected: Option<String>,
+}
+
+impl fmt::Display for UnexpectedEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unexpected end of token stream")?;
+        if let Some(expected) = &self.expected {
+            write!(f, ", expected {}", expected)?;
+        }
+        Ok(())
+    }
+}
+
+impl Fail for UnexpectedEnd {}
+
+/// Returns true if a parse error was caused by the source ending early, in which case the source
+/// may still parse successfully with more input appended.  Interactive environments can use this
+/// to ask for continuation lines rather than reporting an error.
+pub fn is_unexpected_end(error: &Error) -> bool {
+    error.downcast_ref::<UnexpectedEnd>().is_some()
+        || error.downcast_ref::<UnfinishedLongString>().is_some()
+}
+
 pub fn parse_chunk<R: Read>(source: R) -> Result<Chunk, Error> {
     Parser {
         lexer: Lexer::new(source),
@@ -242,24 +283,52 @@ pub fn parse_chunk<R: Read>(source: R) -> Result<Chunk, Error> {
     .parse_chunk()
 }
 
+/// Parses a single bare expression, such as a REPL input or a debugger watch expression.  The
+/// entire source must be consumed by the expression.
+pub fn parse_expression<R: Read>(source: R) -> Result<ExpressionChunk, Error> {
+    Parser {
+        lexer: Lexer::new(source),
+        read_buffer: Vec::new(),
+        recursion_guard: Rc::new(()),
+    }
+    .parse_standalone_expression()
+}
+
 struct Parser<R: Read> {
     lexer: Lexer<R>,
-    read_buffer: Vec<Token>,
+    // Tokens which have been read ahead, along with the line on which each token begins
+    read_buffer: Vec<(Token, LineNumber)>,
     recursion_guard: Rc<()>,
 }
 
 impl<R: Read> Parser<R> {
-    fn parse_chunk(&mut self) -> Result<Chunk, Error> {
+    fn parse_chunk(mut self) -> Result<Chunk, Error> {
         let block = self.parse_block()?;
         if self.look_ahead(0)? != None {
             Err(err_msg("expected end of token stream"))
         } else {
-            Ok(Chunk { block })
+            Ok(Chunk {
+                block,
+                interner: self.lexer.into_interner(),
+            })
+        }
+    }
+
+    fn parse_standalone_expression(mut self) -> Result<ExpressionChunk, Error> {
+        let expression = self.parse_expression()?;
+        if self.look_ahead(0)?.is_some() {
+            Err(err_msg("expected end of token stream"))
+        } else {
+            Ok(ExpressionChunk {
+                expression,
+                interner: self.lexer.into_interner(),
+            })
         }
     }
 
     fn parse_block(&mut self) -> Result<Block, Error> {
         let mut statements = Vec::new();
+        let mut lines = Vec::new();
         let mut return_statement = None;
 
         loop {
@@ -275,6 +344,7 @@ impl<R: Read> Parser<R> {
                 }
                 None => break,
                 _ => {
+                    lines.push(self.line()?);
                     statements.push(self.parse_statement()?);
                 }
             }
@@ -282,6 +352,7 @@ impl<R: Read> Parser<R> {
 
         Ok(Block {
             statements,
+            lines,
             return_statement,
         })
     }
@@ -304,7 +375,15 @@ impl<R: Read> Parser<R> {
             Token::Local => {
                 if self.check_ahead(1, Token::Function)? {
                     self.take_next()?;
-                    Statement::LocalFunction(self.parse_function_statement()?)
+                    let local_function = self.parse_function_statement()?;
+                    if !local_function.name.fields.is_empty()
+                        || local_function.name.method.is_some()
+                    {
+                        return Err(err_msg(
+                            "local function name cannot have fields or a method",
+                        ));
+                    }
+                    Statement::LocalFunction(local_function)
                 } else {
                     Statement::LocalStatement(self.parse_local_statement()?)
                 }
@@ -320,6 +399,7 @@ impl<R: Read> Parser<R> {
     }
 
     fn parse_return_statement(&mut self) -> Result<ReturnStatement, Error> {
+        let line = self.line()?;
         self.expect_next(Token::Return)?;
         let returns = match self.look_ahead(0)? {
             None
@@ -333,7 +413,7 @@ impl<R: Read> Parser<R> {
         if self.check_ahead(0, Token::SemiColon)? {
             self.take_next()?;
         }
-        Ok(ReturnStatement { returns })
+        Ok(ReturnStatement { returns, line })
     }
 
     fn parse_if_statement(&mut self) -> Result<IfStatement, Error> {
@@ -850,10 +930,10 @@ impl<R: Read> Parser<R> {
     // Return a reference to the next token in the stream, erroring if we are at the end.
     fn get_next(&mut self) -> Result<&Token, Error> {
         self.read_ahead(1)?;
-        if let Some(token) = self.read_buffer.get(0) {
+        if let Some((token, _)) = self.read_buffer.get(0) {
             Ok(token)
         } else {
-            Err(format_err!("unexpected end of token stream"))
+            Err(UnexpectedEnd { expected: None }.into())
         }
     }
 
@@ -861,12 +941,12 @@ impl<R: Read> Parser<R> {
     fn expect_next(&mut self, token: Token) -> Result<(), Error> {
         self.read_ahead(1)?;
         if self.read_buffer.is_empty() {
-            Err(format_err!(
-                "unexpected end of token stream, expected {:?}",
-                token
-            ))
+            Err(UnexpectedEnd {
+                expected: Some(format!("{:?}", token)),
+            }
+            .into())
         } else {
-            let next_token = self.read_buffer.remove(0);
+            let (next_token, _) = self.read_buffer.remove(0);
             if next_token == token {
                 Ok(())
             } else {
@@ -880,12 +960,15 @@ impl<R: Read> Parser<R> {
     }
 
     // Consume the next token which should be a name, and return it, otherwise error.
-    fn expect_name(&mut self) -> Result<Box<[u8]>, Error> {
+    fn expect_name(&mut self) -> Result<Symbol, Error> {
         self.read_ahead(1)?;
         if self.read_buffer.is_empty() {
-            Err(err_msg("unexpected end of token stream, expected name"))
+            Err(UnexpectedEnd {
+                expected: Some("name".to_owned()),
+            }
+            .into())
         } else {
-            match self.read_buffer.remove(0) {
+            match self.read_buffer.remove(0).0 {
                 Token::Name(name) => Ok(name),
                 token => Err(format_err!("expected name found {:?}", token)),
             }
@@ -896,9 +979,12 @@ impl<R: Read> Parser<R> {
     fn expect_string(&mut self) -> Result<Box<[u8]>, Error> {
         self.read_ahead(1)?;
         if self.read_buffer.is_empty() {
-            Err(err_msg("unexpected end of token stream, expected string"))
+            Err(UnexpectedEnd {
+                expected: Some("string".to_owned()),
+            }
+            .into())
         } else {
-            match self.read_buffer.remove(0) {
+            match self.read_buffer.remove(0).0 {
                 Token::String(string) => Ok(string),
                 token => Err(format_err!("expected string found {:?}", token)),
             }
@@ -909,23 +995,34 @@ impl<R: Read> Parser<R> {
     fn take_next(&mut self) -> Result<Token, Error> {
         self.read_ahead(1)?;
         if self.read_buffer.is_empty() {
-            Err(err_msg("unexpected end of token stream"))
+            Err(UnexpectedEnd { expected: None }.into())
         } else {
-            Ok(self.read_buffer.remove(0))
+            Ok(self.read_buffer.remove(0).0)
         }
     }
 
+    // Return the line on which the next token in the stream begins, or the last line of the source
+    // if we are at the end.
+    fn line(&mut self) -> Result<LineNumber, Error> {
+        self.read_ahead(0)?;
+        Ok(if let Some((_, line)) = self.read_buffer.first() {
+            *line
+        } else {
+            LineNumber(self.lexer.line_number() + 1)
+        })
+    }
+
     // Return the nth token ahead in the stream, if it is not past the end.
     fn look_ahead(&mut self, n: usize) -> Result<Option<&Token>, Error> {
         self.read_ahead(n + 1)?;
-        Ok(self.read_buffer.get(n))
+        Ok(self.read_buffer.get(n).map(|(t, _)| t))
     }
 
     // Return true if the nth token ahead in the stream matches the given token.  If this would read
     // past the end of the stream, this will simply return false.
     fn check_ahead(&mut self, n: usize, token: Token) -> Result<bool, Error> {
         self.read_ahead(n)?;
-        Ok(if let Some(t) = self.read_buffer.get(n) {
+        Ok(if let Some((t, _)) = self.read_buffer.get(n) {
             *t == token
         } else {
             false
@@ -936,8 +1033,10 @@ impl<R: Read> Parser<R> {
     // possible).
     fn read_ahead(&mut self, n: usize) -> Result<(), Error> {
         while self.read_buffer.len() <= n {
+            self.lexer.skip_whitespace()?;
+            let line = LineNumber(self.lexer.line_number() + 1);
             if let Some(token) = self.lexer.read_token()? {
-                self.read_buffer.push(token);
+                self.read_buffer.push((token, line));
             } else {
                 break;
             }
@@ -970,7 +1069,7 @@ fn binary_priority(operator: BinaryOperator) -> (u8, u8) {
         BinaryOperator::ShiftLeft => (7, 7),
         BinaryOperator::ShiftRight => (7, 7),
         BinaryOperator::Concat => (9, 8),
-        BinaryOperator::NotEqual => (10, 10),
+        BinaryOperator::NotEqual => (3, 3),
         BinaryOperator::Equal => (3, 3),
         BinaryOperator::LessThan => (3, 3),
         BinaryOperator::LessEqual => (3, 3),
@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, Read};
+
+/// An optional source stage which strips conditional sections out of a Lua script before it
+/// reaches the lexer, driven by a set of host-supplied flags.
+///
+/// Directives are Lua comments which must be the first thing on their line:
+///
+/// ```lua
+/// --@if DEBUG
+/// print("only compiled when DEBUG is set")
+/// --@else
+/// print("only compiled when DEBUG is not set")
+/// --@end
+/// ```
+///
+/// `--@if not FLAG` inverts the condition, and sections may be nested.  Only `if`, `else` and `end`
+/// are directives, any other comment beginning with `--@` (such as `--@param x`) is left alone.
+/// Every directive line and every stripped line is replaced by an empty line, so line numbers in
+/// the output match the original source.  Since directives are plain comments, a script containing
+/// them is still valid Lua when it is not preprocessed, with every section included.
+///
+/// Directives are recognized line by line without lexing, so a line inside of a long string or
+/// long comment that happens to begin with `--@` will also be treated as a directive.
+pub struct Preprocessor<R> {
+    source: R,
+    flags: HashSet<Box<[u8]>>,
+    conditions: Vec<Condition>,
+    line: Vec<u8>,
+    line_pos: usize,
+    line_number: u64,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Condition {
+    // Whether lines in the current section are included in the output
+    active: bool,
+    // Whether the section enclosing this one is included in the output
+    parent_active: bool,
+    in_else: bool,
+}
+
+impl<R: BufRead> Preprocessor<R> {
+    pub fn new<I>(source: R, flags: I) -> Preprocessor<R>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        Preprocessor {
+            source,
+            flags: flags
+                .into_iter()
+                .map(|f| f.as_ref().to_vec().into_boxed_slice())
+                .collect(),
+            conditions: Vec::new(),
+            line: Vec::new(),
+            line_pos: 0,
+            line_number: 0,
+        }
+    }
+
+    // Returns true if lines at the current nesting level should be included in the output
+    fn is_active(&self) -> bool {
+        self.conditions.last().map(|c| c.active).unwrap_or(true)
+    }
+
+    // Handles a directive or strips an inactive line, leaving only the line ending in the line
+    // buffer.
+    fn process_line(&mut self) -> Result<(), io::Error> {
+        let line_end = self
+            .line
+            .iter()
+            .rposition(|&c| c != b'\n' && c != b'\r')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let start = self.line[..line_end]
+            .iter()
+            .position(|&c| c != b' ' && c != b'\t')
+            .unwrap_or(line_end);
+
+        let content = &self.line[start..line_end];
+        let directive_text: &[u8] = if content.starts_with(b"--@") {
+            &content[3..]
+        } else {
+            b""
+        };
+        let mut words = directive_text
+            .split(|&c| c == b' ' || c == b'\t')
+            .filter(|w| !w.is_empty());
+        let directive = words.next().unwrap_or(b"");
+
+        if directive == b"if" || directive == b"else" || directive == b"end" {
+            let mut argument = words.next();
+            let negate = argument == Some(b"not");
+            if negate {
+                argument = words.next();
+            }
+
+            match (directive, argument, words.next()) {
+                (b"if", Some(flag), None) => {
+                    let parent_active = self.is_active();
+                    let is_set = self.flags.contains(flag);
+                    self.conditions.push(Condition {
+                        active: parent_active && is_set != negate,
+                        parent_active,
+                        in_else: false,
+                    });
+                }
+                (b"else", None, None) if !negate => match self.conditions.last_mut() {
+                    Some(condition) if !condition.in_else => {
+                        condition.active = condition.parent_active && !condition.active;
+                        condition.in_else = true;
+                    }
+                    _ => return Err(self.error("unexpected '--@else' directive")),
+                },
+                (b"end", None, None) if !negate => {
+                    if self.conditions.pop().is_none() {
+                        return Err(self.error("unexpected '--@end' directive"));
+                    }
+                }
+                _ => return Err(self.error("malformed preprocessor directive")),
+            }
+
+            self.line.drain(..line_end);
+        } else if !self.is_active() {
+            self.line.drain(..line_end);
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, msg: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} on line {}", msg, self.line_number),
+        )
+    }
+}
+
+impl<R: BufRead> Read for Preprocessor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        // A processed line may be left empty if it was the last line and had no line ending, so
+        // keep reading until there is output or the source is exhausted.
+        while self.line_pos == self.line.len() {
+            self.line.clear();
+            self.line_pos = 0;
+            if self.source.read_until(b'\n', &mut self.line)? == 0 {
+                if !self.conditions.is_empty() {
+                    return Err(self.error("unterminated '--@if' directive at end of source"));
+                }
+                return Ok(0);
+            }
+            self.line_number += 1;
+            self.process_line()?;
+        }
+
+        let len = buf.len().min(self.line.len() - self.line_pos);
+        buf[..len].copy_from_slice(&self.line[self.line_pos..self.line_pos + len]);
+        self.line_pos += len;
+        Ok(len)
+    }
+}
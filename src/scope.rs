@@ -0,0 +1,448 @@
+use std::iter;
+
+use crate::interner::{Interner, Symbol};
+use crate::parser::{
+    AssignmentTarget, Block, CallSuffix, Chunk, ConstructorField, Expression, FieldSuffix,
+    ForStatement, FunctionDefinition, FunctionStatement, HeadExpression, PrimaryExpression,
+    RecordKey, SimpleExpression, Statement, SuffixPart, SuffixedExpression, TableConstructor,
+};
+use crate::types::LineNumber;
+
+/// An index into `SymbolTable::scopes`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ScopeId(pub usize);
+
+/// An index into `SymbolTable::locals`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct LocalId(pub usize);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScopeKind {
+    /// The outermost scope of a function body (or of the main chunk), which holds the function
+    /// parameters.
+    Function,
+    Block,
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<ScopeId>,
+    /// The `ScopeKind::Function` scope that this scope is contained in (for function scopes, this
+    /// is the scope itself).
+    pub function: ScopeId,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LocalKind {
+    Parameter,
+    Local,
+    LocalFunction,
+    ForVariable,
+}
+
+#[derive(Debug)]
+pub struct LocalVariable {
+    pub name: Symbol,
+    pub kind: LocalKind,
+    pub scope: ScopeId,
+    /// The line of the statement which declares the variable, if known.  Parameters are declared by
+    /// the statement containing their function.
+    pub line: Option<LineNumber>,
+    /// True if any inner function references this variable as an upvalue
+    pub captured: bool,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Resolution {
+    /// A local variable declared in the same function as the reference
+    Local(LocalId),
+    /// A local variable of an enclosing function, accessed as an upvalue
+    UpValue(LocalId),
+    /// A global variable, accessed as a field of `_ENV`.  Holds the in-scope local `_ENV` variable
+    /// if there is one, otherwise the implicit chunk `_ENV` is used.
+    Global(Option<LocalId>),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReferenceKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug)]
+pub struct Reference {
+    pub name: Symbol,
+    pub kind: ReferenceKind,
+    pub scope: ScopeId,
+    pub resolution: Resolution,
+    /// The line of the statement containing the reference, if known
+    pub line: Option<LineNumber>,
+}
+
+/// The result of resolving every variable name in a chunk, using the same scoping rules as the
+/// compiler.  Scopes, locals, and references are all recorded in source order.
+#[derive(Debug)]
+pub struct SymbolTable<'a> {
+    pub interner: &'a Interner,
+    pub scopes: Vec<Scope>,
+    pub locals: Vec<LocalVariable>,
+    pub references: Vec<Reference>,
+}
+
+impl<'a> SymbolTable<'a> {
+    pub fn scope(&self, scope: ScopeId) -> &Scope {
+        &self.scopes[scope.0]
+    }
+
+    pub fn local(&self, local: LocalId) -> &LocalVariable {
+        &self.locals[local.0]
+    }
+
+    /// The name of a symbol from the analyzed chunk
+    pub fn name(&self, symbol: Symbol) -> &'a [u8] {
+        self.interner.name(symbol)
+    }
+
+    /// All locals declared with the given name, in declaration order.
+    pub fn locals_named<'s>(&'s self, name: &[u8]) -> impl Iterator<Item = LocalId> + 's {
+        let symbol = self.interner.get(name);
+        self.locals
+            .iter()
+            .enumerate()
+            .filter(move |(_, l)| Some(l.name) == symbol)
+            .map(|(i, _)| LocalId(i))
+    }
+
+    /// All references which resolve to the given local variable, either directly or as an upvalue.
+    pub fn references_to(&self, local: LocalId) -> impl Iterator<Item = &Reference> {
+        self.references.iter().filter(move |r| match r.resolution {
+            Resolution::Local(l) | Resolution::UpValue(l) => l == local,
+            Resolution::Global(_) => false,
+        })
+    }
+
+    /// All references to global variables.
+    pub fn global_references(&self) -> impl Iterator<Item = &Reference> {
+        self.references
+            .iter()
+            .filter(|r| matches!(r.resolution, Resolution::Global(_)))
+    }
+}
+
+/// Resolves all variable names in the given chunk into a `SymbolTable`.
+pub fn analyze_chunk<'a>(chunk: &'a Chunk) -> SymbolTable<'a> {
+    let mut resolver = Resolver {
+        table: SymbolTable {
+            interner: &chunk.interner,
+            scopes: Vec::new(),
+            locals: Vec::new(),
+            references: Vec::new(),
+        },
+        active_locals: Vec::new(),
+        current_scope: None,
+        current_line: None,
+    };
+    resolver.enter_scope(ScopeKind::Function);
+    resolver.block(&chunk.block);
+    resolver.exit_scope();
+    resolver.table
+}
+
+struct Resolver<'a> {
+    table: SymbolTable<'a>,
+    // All locals currently in scope, with the innermost declarations last
+    active_locals: Vec<LocalId>,
+    current_scope: Option<ScopeId>,
+    // The line of the statement being resolved
+    current_line: Option<LineNumber>,
+}
+
+impl<'a> Resolver<'a> {
+    fn block(&mut self, block: &'a Block) {
+        self.enter_scope(ScopeKind::Block);
+        self.block_statements(block);
+        self.exit_scope();
+    }
+
+    // Resolves the statements of a block, with the line of each statement as the current line.
+    // The current line is restored afterwards, so the rest of an enclosing statement is still
+    // located at that statement.
+    fn block_statements(&mut self, block: &'a Block) {
+        let outer_line = self.current_line;
+        for (i, statement) in block.statements.iter().enumerate() {
+            self.current_line = block.lines.get(i).copied();
+            self.statement(statement);
+        }
+        if let Some(return_statement) = &block.return_statement {
+            self.current_line = Some(return_statement.line);
+            for expr in &return_statement.returns {
+                self.expression(expr);
+            }
+        }
+        self.current_line = outer_line;
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::If(if_statement) => {
+                for (cond, block) in
+                    iter::once(&if_statement.if_part).chain(&if_statement.else_if_parts)
+                {
+                    self.expression(cond);
+                    self.block(block);
+                }
+                if let Some(else_part) = &if_statement.else_part {
+                    self.block(else_part);
+                }
+            }
+            Statement::While(while_statement) => {
+                self.expression(&while_statement.condition);
+                self.block(&while_statement.block);
+            }
+            Statement::Do(block) => self.block(block),
+            Statement::For(ForStatement::Numeric {
+                name,
+                initial,
+                limit,
+                step,
+                body,
+            }) => {
+                self.expression(initial);
+                self.expression(limit);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+                self.enter_scope(ScopeKind::Block);
+                self.declare_local(*name, LocalKind::ForVariable);
+                self.block_statements(body);
+                self.exit_scope();
+            }
+            Statement::For(ForStatement::Generic {
+                names,
+                arguments,
+                body,
+            }) => {
+                for argument in arguments {
+                    self.expression(argument);
+                }
+                self.enter_scope(ScopeKind::Block);
+                for name in names {
+                    self.declare_local(*name, LocalKind::ForVariable);
+                }
+                self.block_statements(body);
+                self.exit_scope();
+            }
+            Statement::Repeat(repeat_statement) => {
+                // The locals inside a `repeat` block are in scope for the `until` condition.
+                self.enter_scope(ScopeKind::Block);
+                self.block_statements(&repeat_statement.body);
+                self.expression(&repeat_statement.until);
+                self.exit_scope();
+            }
+            Statement::Function(function_statement) => self.function_statement(function_statement),
+            Statement::LocalFunction(local_function) => {
+                self.declare_local(local_function.name.name, LocalKind::LocalFunction);
+                self.function_definition(&local_function.definition, false);
+            }
+            Statement::LocalStatement(local_statement) => {
+                for value in &local_statement.values {
+                    self.expression(value);
+                }
+                for name in &local_statement.names {
+                    self.declare_local(*name, LocalKind::Local);
+                }
+            }
+            Statement::Label(_) | Statement::Break | Statement::Goto(_) => {}
+            Statement::FunctionCall(function_call) => {
+                self.suffixed_expression(&function_call.head);
+                self.call_suffix(&function_call.call);
+            }
+            Statement::Assignment(assignment) => {
+                for target in &assignment.targets {
+                    match target {
+                        AssignmentTarget::Name(name) => {
+                            self.reference(*name, ReferenceKind::Write);
+                        }
+                        AssignmentTarget::Field(table, field) => {
+                            self.suffixed_expression(table);
+                            self.field_suffix(field);
+                        }
+                    }
+                }
+                for value in &assignment.values {
+                    self.expression(value);
+                }
+            }
+        }
+    }
+
+    fn function_statement(&mut self, function_statement: &'a FunctionStatement) {
+        let name = &function_statement.name;
+        if name.fields.is_empty() && name.method.is_none() {
+            self.reference(name.name, ReferenceKind::Write);
+        } else {
+            self.reference(name.name, ReferenceKind::Read);
+        }
+        self.function_definition(&function_statement.definition, name.method.is_some());
+    }
+
+    fn function_definition(&mut self, function: &'a FunctionDefinition, has_self: bool) {
+        self.enter_scope(ScopeKind::Function);
+        if has_self {
+            self.declare_local(Symbol::SELF, LocalKind::Parameter);
+        }
+        for parameter in &function.parameters {
+            self.declare_local(*parameter, LocalKind::Parameter);
+        }
+        self.block(&function.body);
+        self.exit_scope();
+    }
+
+    fn expression(&mut self, expression: &'a Expression) {
+        match &*expression.head {
+            HeadExpression::Simple(simple) => self.simple_expression(simple),
+            HeadExpression::UnaryOperator(_, expr) => self.expression(expr),
+        }
+        for (_, right) in &expression.tail {
+            self.expression(right);
+        }
+    }
+
+    fn simple_expression(&mut self, simple_expression: &'a SimpleExpression) {
+        match simple_expression {
+            SimpleExpression::TableConstructor(table_constructor) => {
+                self.table_constructor(table_constructor)
+            }
+            SimpleExpression::Function(function) => self.function_definition(function, false),
+            SimpleExpression::Suffixed(suffixed) => self.suffixed_expression(suffixed),
+            SimpleExpression::Float(_)
+            | SimpleExpression::Integer(_)
+            | SimpleExpression::String(_)
+            | SimpleExpression::Nil
+            | SimpleExpression::True
+            | SimpleExpression::False
+            | SimpleExpression::VarArgs => {}
+        }
+    }
+
+    fn table_constructor(&mut self, table_constructor: &'a TableConstructor) {
+        for field in &table_constructor.fields {
+            match field {
+                ConstructorField::Array(value) => self.expression(value),
+                ConstructorField::Record(key, value) => {
+                    if let RecordKey::Indexed(key) = key {
+                        self.expression(key);
+                    }
+                    self.expression(value);
+                }
+            }
+        }
+    }
+
+    fn suffixed_expression(&mut self, suffixed_expression: &'a SuffixedExpression) {
+        match &suffixed_expression.primary {
+            PrimaryExpression::Name(name) => self.reference(*name, ReferenceKind::Read),
+            PrimaryExpression::GroupedExpression(expr) => self.expression(expr),
+        }
+        for suffix in &suffixed_expression.suffixes {
+            match suffix {
+                SuffixPart::Field(field) => self.field_suffix(field),
+                SuffixPart::Call(call) => self.call_suffix(call),
+            }
+        }
+    }
+
+    fn field_suffix(&mut self, field_suffix: &'a FieldSuffix) {
+        if let FieldSuffix::Indexed(key) = field_suffix {
+            self.expression(key);
+        }
+    }
+
+    fn call_suffix(&mut self, call_suffix: &'a CallSuffix) {
+        match call_suffix {
+            CallSuffix::Function(args) | CallSuffix::Method(_, args) => {
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+        }
+    }
+
+    fn enter_scope(&mut self, kind: ScopeKind) {
+        let id = ScopeId(self.table.scopes.len());
+        let function = match kind {
+            ScopeKind::Function => id,
+            ScopeKind::Block => self.table.scope(self.current_scope.unwrap()).function,
+        };
+        self.table.scopes.push(Scope {
+            kind,
+            parent: self.current_scope,
+            function,
+        });
+        self.current_scope = Some(id);
+    }
+
+    fn exit_scope(&mut self) {
+        let current_scope = self.current_scope.unwrap();
+        while let Some(&last) = self.active_locals.last() {
+            if self.table.local(last).scope == current_scope {
+                self.active_locals.pop();
+            } else {
+                break;
+            }
+        }
+        self.current_scope = self.table.scope(current_scope).parent;
+    }
+
+    fn declare_local(&mut self, name: Symbol, kind: LocalKind) {
+        let id = LocalId(self.table.locals.len());
+        self.table.locals.push(LocalVariable {
+            name,
+            kind,
+            scope: self.current_scope.unwrap(),
+            line: self.current_line,
+            captured: false,
+        });
+        self.active_locals.push(id);
+    }
+
+    // Find the innermost local with the given name, marking it as captured if it is found in an
+    // enclosing function.
+    fn find_local(&mut self, name: Symbol) -> Option<Resolution> {
+        let current_function = self.table.scope(self.current_scope.unwrap()).function;
+        for &local in self.active_locals.iter().rev() {
+            let variable = &mut self.table.locals[local.0];
+            if variable.name == name {
+                return Some(
+                    if self.table.scopes[variable.scope.0].function == current_function {
+                        Resolution::Local(local)
+                    } else {
+                        variable.captured = true;
+                        Resolution::UpValue(local)
+                    },
+                );
+            }
+        }
+        None
+    }
+
+    fn reference(&mut self, name: Symbol, kind: ReferenceKind) {
+        let resolution = if let Some(resolution) = self.find_local(name) {
+            resolution
+        } else {
+            Resolution::Global(match self.find_local(Symbol::ENV) {
+                Some(Resolution::Local(env)) | Some(Resolution::UpValue(env)) => Some(env),
+                _ => None,
+            })
+        };
+
+        self.table.references.push(Reference {
+            name,
+            kind,
+            scope: self.current_scope.unwrap(),
+            resolution,
+            line: self.current_line,
+        });
+    }
+}
@@ -215,16 +215,19 @@ impl<'gc, S, C, F, R> Sequence<'gc> for MapWith<S, C, F>
 where
     S: Sequence<'gc>,
     C: Collect,
-    F: 'static + FnOnce(MutationContext<'gc, '_>, C, S::Item) -> R,
+    F: 'static + FnOnce(MutationContext<'gc, '_>, C, S::Item) -> Result<R, Error>,
 {
     type Item = R;
 
     fn pump(&mut self, mc: MutationContext<'gc, '_>) -> Option<Result<R, Error>> {
         match self.0.take() {
             Some((mut a, c, StaticCollect(f))) => match a.pump(mc) {
-                Some(Ok(r)) => Some(Ok(f(mc, c, r))),
+                Some(Ok(r)) => Some(f(mc, c, r)),
                 Some(Err(e)) => Some(Err(e)),
-                None => None,
+                None => {
+                    self.0 = Some((a, c, StaticCollect(f)));
+                    None
+                }
             },
             None => panic!("cannot pump a finished sequence"),
         }
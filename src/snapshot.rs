@@ -0,0 +1,171 @@
+use gc_arena::{Collect, MutationContext};
+
+use crate::table::Table;
+use crate::value::Value;
+
+/// A copy of the contents of a table, which can later be compared with the table's contents at
+/// that time.  This is mostly useful for auditing what a script does to its globals:
+///
+/// ```ignore
+/// let snapshot = TableSnapshot::new(mc, lc.globals);
+/// Ok(Box::new(
+///     lc.main_thread
+///         .call_function(mc, closure, &[], lc.granularity)
+///         .map_with(snapshot, |_, snapshot, _| Ok(snapshot.diff())),
+/// ))
+/// ```
+///
+/// Only the table itself is copied, so changes inside of nested tables are not detected.
+#[derive(Debug, Copy, Clone, Collect)]
+#[collect(require_copy)]
+pub struct TableSnapshot<'gc> {
+    table: Table<'gc>,
+    copy: Table<'gc>,
+}
+
+impl<'gc> TableSnapshot<'gc> {
+    pub fn new(mc: MutationContext<'gc, '_>, table: Table<'gc>) -> TableSnapshot<'gc> {
+        let copy = Table::new(mc);
+        let mut key = Value::Nil;
+        while let Some((k, v)) = table.next(key).unwrap() {
+            copy.set(mc, k, v).unwrap();
+            key = k;
+        }
+        TableSnapshot { table, copy }
+    }
+
+    /// Compares the snapshot with the current contents of the table.  Fields which were present in
+    /// the snapshot are listed first in snapshot traversal order, followed by any new fields in the
+    /// traversal order of the table.
+    pub fn diff(&self) -> TableDiff {
+        let mut changes = Vec::new();
+
+        let mut key = Value::Nil;
+        while let Some((k, old)) = self.copy.next(key).unwrap() {
+            let new = self.table.get(k);
+            let kind = if new == Value::Nil {
+                Some(ChangeKind::Deleted)
+            } else if !raw_equal(old, new) {
+                Some(ChangeKind::Modified)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                changes.push(FieldChange {
+                    kind,
+                    key: ValueSummary::new(k),
+                    old: ValueSummary::new(old),
+                    new: ValueSummary::new(new),
+                });
+            }
+            key = k;
+        }
+
+        let mut key = Value::Nil;
+        while let Some((k, new)) = self.table.next(key).unwrap() {
+            if self.copy.get(k) == Value::Nil {
+                changes.push(FieldChange {
+                    kind: ChangeKind::Created,
+                    key: ValueSummary::new(k),
+                    old: ValueSummary::Nil,
+                    new: ValueSummary::new(new),
+                });
+            }
+            key = k;
+        }
+
+        TableDiff { changes }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TableDiff {
+    pub changes: Vec<FieldChange>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn created(&self) -> impl Iterator<Item = &FieldChange> {
+        self.changes_of_kind(ChangeKind::Created)
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &FieldChange> {
+        self.changes_of_kind(ChangeKind::Modified)
+    }
+
+    pub fn deleted(&self) -> impl Iterator<Item = &FieldChange> {
+        self.changes_of_kind(ChangeKind::Deleted)
+    }
+
+    fn changes_of_kind(&self, kind: ChangeKind) -> impl Iterator<Item = &FieldChange> {
+        self.changes.iter().filter(move |c| c.kind == kind)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// A single changed field.  `old` is nil for created fields, and `new` is nil for deleted fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub kind: ChangeKind,
+    pub key: ValueSummary,
+    pub old: ValueSummary,
+    pub new: ValueSummary,
+}
+
+/// A description of a `Value` which does not depend on the GC arena, so it can outlive it.  Simple
+/// values are copied, but tables and functions are described only by their type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSummary {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(Box<[u8]>),
+    Table,
+    Function,
+}
+
+impl ValueSummary {
+    pub fn new(value: Value) -> ValueSummary {
+        match value {
+            Value::Nil => ValueSummary::Nil,
+            Value::Boolean(b) => ValueSummary::Boolean(b),
+            Value::Integer(i) => ValueSummary::Integer(i),
+            Value::Number(n) => ValueSummary::Number(n),
+            Value::String(s) => ValueSummary::String(s.as_bytes().into()),
+            Value::Table(_) => ValueSummary::Table,
+            Value::Closure(_) => ValueSummary::Function,
+        }
+    }
+
+    /// The name of the Lua type of the summarized value, as returned by `type`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ValueSummary::Nil => "nil",
+            ValueSummary::Boolean(_) => "boolean",
+            ValueSummary::Integer(_) | ValueSummary::Number(_) => "number",
+            ValueSummary::String(_) => "string",
+            ValueSummary::Table => "table",
+            ValueSummary::Function => "function",
+        }
+    }
+}
+
+// Like `Value`'s `PartialEq`, but treats a NaN as unchanged when compared with itself, and an
+// integer as changed when replaced by an equal float.
+fn raw_equal<'gc>(a: Value<'gc>, b: Value<'gc>) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (Value::Integer(_), Value::Number(_)) | (Value::Number(_), Value::Integer(_)) => false,
+        (a, b) => a == b,
+    }
+}
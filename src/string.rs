@@ -1,7 +1,9 @@
 use std::hash::{Hash, Hasher};
+use std::io;
 use std::ops::Deref;
+use std::sync::Arc;
 
-use gc_arena::{Collect, Gc, MutationContext};
+use gc_arena::{static_collect, Collect, Gc, MutationContext};
 
 #[derive(Debug, Copy, Clone, Collect)]
 #[collect(require_copy)]
@@ -10,6 +12,20 @@ pub enum String<'gc> {
     Short32(u8, Gc<'gc, [u8; 32]>),
     Long(Gc<'gc, Box<[u8]>>),
     Static(&'static [u8]),
+    Shared(Gc<'gc, SharedBuffer>),
+}
+
+/// A host-owned buffer held by a `String::Shared`, which keeps the buffer alive for as long as the
+/// string is reachable.
+#[derive(Debug)]
+pub struct SharedBuffer(Arc<[u8]>);
+
+static_collect!(SharedBuffer);
+
+impl SharedBuffer {
+    pub fn buffer(&self) -> &Arc<[u8]> {
+        &self.0
+    }
 }
 
 impl<'gc> String<'gc> {
@@ -28,6 +44,26 @@ impl<'gc> String<'gc> {
         }
     }
 
+    /// Creates a string from an owned buffer, which is used directly without copying if the string
+    /// is too long to be stored inline.
+    pub fn from_buffer(mc: MutationContext<'gc, '_>, s: Box<[u8]>) -> String<'gc> {
+        if s.len() <= 32 {
+            String::new(mc, &s)
+        } else {
+            String::Long(Gc::allocate(mc, s))
+        }
+    }
+
+    /// Creates a string which refers to static bytes, without copying or allocating.
+    pub fn from_static(s: &'static [u8]) -> String<'gc> {
+        String::Static(s)
+    }
+
+    /// Creates a string which shares a buffer with the host, without copying it.
+    pub fn from_shared(mc: MutationContext<'gc, '_>, s: Arc<[u8]>) -> String<'gc> {
+        String::Shared(Gc::allocate(mc, SharedBuffer(s)))
+    }
+
     pub fn new_static(&self, s: &'static [u8]) -> String<'gc> {
         String::Static(s)
     }
@@ -38,6 +74,7 @@ impl<'gc> String<'gc> {
             String::Short32(l, b) => &b[0..*l as usize],
             String::Long(b) => b,
             String::Static(b) => b,
+            String::Shared(b) => &b.0,
         }
     }
 }
@@ -63,3 +100,62 @@ impl<'gc> Hash for String<'gc> {
         self.as_bytes().hash(state);
     }
 }
+
+/// Accumulates bytes for a string that is built in pieces, so that only the finished string is
+/// allocated in the arena.  Implements `io::Write`, so `write!` can be used to format into it.
+#[derive(Debug, Default, Clone)]
+pub struct StringBuilder {
+    buffer: Vec<u8>,
+}
+
+impl StringBuilder {
+    pub fn new() -> StringBuilder {
+        StringBuilder::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> StringBuilder {
+        StringBuilder {
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, byte: u8) {
+        self.buffer.push(byte);
+    }
+
+    pub fn append(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Finishes the string, moving the accumulated buffer into the arena.
+    pub fn finish<'gc>(self, mc: MutationContext<'gc, '_>) -> String<'gc> {
+        String::from_buffer(mc, self.buffer.into_boxed_slice())
+    }
+}
+
+impl io::Write for StringBuilder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.append(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -21,6 +21,10 @@ pub enum InvalidTableKey {
     IsNil,
 }
 
+#[derive(Fail, Debug)]
+#[fail(display = "invalid key to 'next'")]
+pub struct InvalidNextKey;
+
 impl<'gc> PartialEq for Table<'gc> {
     fn eq(&self, other: &Table<'gc>) -> bool {
         self.0.as_ptr() == other.0.as_ptr()
@@ -52,13 +56,41 @@ impl<'gc> Table<'gc> {
     ) -> Result<Value<'gc>, InvalidTableKey> {
         self.0.write(mc).set(key, value)
     }
+
+    /// Returns a "border" of the table, an integer index where the value at that index is non-nil
+    /// and the value at the next index is nil, or zero if the value at index 1 is nil.  When the
+    /// table is a sequence this is its length, otherwise any border may be returned.
+    pub fn length(&self) -> i64 {
+        self.0.read().length()
+    }
+
+    /// Returns the key and value following `key` in the traversal order of the table, or the
+    /// first key and value if `key` is nil.  Returns `None` when there are no more entries.
+    ///
+    /// As in PUC-Rio Lua, fields may be cleared (assigned nil) during a traversal, and the traversal
+    /// will still visit every other field exactly once, including continuing from the cleared key.
+    /// The behavior is unspecified if a value is assigned to a field which did not exist when the
+    /// traversal started, fields may be skipped or visited more than once.
+    ///
+    /// Errors if `key` is not nil and not present in the table.
+    pub fn next(
+        &self,
+        key: Value<'gc>,
+    ) -> Result<Option<(Value<'gc>, Value<'gc>)>, InvalidNextKey> {
+        self.0.read().next(key)
+    }
 }
 
 #[derive(Debug, Collect, Default)]
 #[collect(empty_drop)]
 struct TableState<'gc> {
     array: Vec<Value<'gc>>,
-    map: FnvHashMap<TableKey<'gc>, Value<'gc>>,
+    // The map part of the table, in insertion order.  A key which is assigned nil keeps its entry
+    // with a nil value until the next time the map part runs out of capacity, so that `next` can
+    // always find the position of a key cleared during a traversal.
+    entries: Vec<(TableKey<'gc>, Value<'gc>)>,
+    // The index in `entries` of every key in the map part
+    indices: FnvHashMap<TableKey<'gc>, usize>,
 }
 
 impl<'gc> TableState<'gc> {
@@ -70,10 +102,88 @@ impl<'gc> TableState<'gc> {
         }
 
         if let Ok(key) = TableKey::new(key) {
-            self.map.get(&key).cloned().unwrap_or(Value::Nil)
+            if let Some(&i) = self.indices.get(&key) {
+                return self.entries[i].1;
+            }
+        }
+        Value::Nil
+    }
+
+    fn length(&self) -> i64 {
+        // If the last element of the array part is nil, there must be a border inside of the array
+        // part, which we can binary search for.
+        let array_len = self.array.len();
+        if array_len > 0 && self.array[array_len - 1] == Value::Nil {
+            let mut i = 0;
+            let mut j = array_len;
+            while j - i > 1 {
+                let m = (i + j) / 2;
+                if self.array[m - 1] == Value::Nil {
+                    j = m;
+                } else {
+                    i = m;
+                }
+            }
+            return i as i64;
+        }
+
+        if self.entries.is_empty() {
+            return array_len as i64;
+        }
+
+        // Otherwise, find an index past the end of the array part which is nil by doubling, then
+        // binary search between the last known non-nil index and that index.
+        let mut i = array_len as i64;
+        let mut j = i + 1;
+        while self.get(Value::Integer(j)) != Value::Nil {
+            i = j;
+            if j > i64::MAX / 2 {
+                // Pathological case, fall back to a linear search
+                let mut i = 1;
+                while self.get(Value::Integer(i)) != Value::Nil {
+                    i += 1;
+                }
+                return i - 1;
+            }
+            j *= 2;
+        }
+        while j - i > 1 {
+            let m = (i + j) / 2;
+            if self.get(Value::Integer(m)) == Value::Nil {
+                j = m;
+            } else {
+                i = m;
+            }
+        }
+        i
+    }
+
+    fn next(&self, key: Value<'gc>) -> Result<Option<(Value<'gc>, Value<'gc>)>, InvalidNextKey> {
+        // Traversal visits the array part in order followed by the map part in order, and `start`
+        // is the position to continue from in that combined sequence.
+        let start = if key == Value::Nil {
+            0
+        } else if let Some(index) = to_array_index(key).filter(|&i| i < self.array.len()) {
+            index + 1
         } else {
-            Value::Nil
+            let key = TableKey::new(key).map_err(|_| InvalidNextKey)?;
+            self.array.len() + self.indices.get(&key).ok_or(InvalidNextKey)? + 1
+        };
+
+        for i in start..self.array.len() {
+            if self.array[i] != Value::Nil {
+                return Ok(Some((Value::Integer(i as i64 + 1), self.array[i])));
+            }
+        }
+
+        let start = start.saturating_sub(self.array.len());
+        for &(key, value) in &self.entries[start..] {
+            if value != Value::Nil {
+                return Ok(Some((key.0, value)));
+            }
         }
+
+        Ok(None)
     }
 
     fn set(&mut self, key: Value<'gc>, value: Value<'gc>) -> Result<Value<'gc>, InvalidTableKey> {
@@ -85,11 +195,17 @@ impl<'gc> TableState<'gc> {
         }
 
         let hash_key = TableKey::new(key)?;
-        if value == Value::Nil {
-            Ok(self.map.remove(&hash_key).unwrap_or(Value::Nil))
-        } else if self.map.len() < self.map.capacity() {
-            Ok(self.map.insert(hash_key, value).unwrap_or(Value::Nil))
+        if let Some(&i) = self.indices.get(&hash_key) {
+            Ok(mem::replace(&mut self.entries[i].1, value))
+        } else if value == Value::Nil {
+            Ok(Value::Nil)
+        } else if self.entries.len() < self.entries.capacity() {
+            self.insert_entry(hash_key, value);
+            Ok(Value::Nil)
         } else {
+            // The map part is full, so this is the time to drop any entries for cleared keys.
+            self.entries.retain(|(_, v)| *v != Value::Nil);
+
             // If a new element does not fit in either the array or map part of the table, we need
             // to grow.  First, we find the total count of array candidate elements across the array
             // part, the map part, and the newly inserted key.
@@ -108,7 +224,7 @@ impl<'gc> TableState<'gc> {
                 }
             }
 
-            for (k, _) in &self.map {
+            for (k, _) in &self.entries {
                 if let Some(i) = to_array_index(k.0) {
                     array_counts[highest_bit(i)] += 1;
                     array_total += 1;
@@ -139,7 +255,6 @@ impl<'gc> TableState<'gc> {
             }
 
             let old_array_size = self.array.len();
-            let old_map_size = self.map.len();
             if optimal_size > old_array_size {
                 // If we're growing the array part, we need to grow the array and take any newly valid
                 // array keys from the map part.
@@ -149,7 +264,7 @@ impl<'gc> TableState<'gc> {
                 self.array.resize(capacity, Value::Nil);
 
                 let array = &mut self.array;
-                self.map.retain(|k, v| {
+                self.entries.retain(|(k, v)| {
                     if let Some(i) = to_array_index(k.0) {
                         if i < array.len() {
                             array[i] = *v;
@@ -158,30 +273,35 @@ impl<'gc> TableState<'gc> {
                     }
                     true
                 });
-            } else {
-                // If we aren't growing the array, we're adding a new element to the map that won't
-                // fit in the advertised capacity.  The capacity of std::collections::HashMap is
-                // just a lower-bound, so we may actually be able to insert past the capacity
-                // without the advertised capacity growing, so to make sure that we don't try to
-                // grow repeatedly, we need to make sure the capacity actually increases.  We simply
-                // double the capacity here.
-                self.map.reserve(old_map_size);
             }
 
-            // Now we can insert the new key value pair
+            // Entries may have moved, so the indices must be rebuilt.
+            self.indices.clear();
+            for (i, (k, _)) in self.entries.iter().enumerate() {
+                self.indices.insert(*k, i);
+            }
+
+            // Now we can insert the new key value pair.  If the map part is still full, pushing the
+            // new entry will grow it.
             if let Some(index) = index_key {
                 if index < self.array.len() {
                     return Ok(mem::replace(&mut self.array[index], value));
                 }
             }
-            Ok(self.map.insert(hash_key, value).unwrap_or(Value::Nil))
+            self.insert_entry(hash_key, value);
+            Ok(Value::Nil)
         }
     }
+
+    fn insert_entry(&mut self, key: TableKey<'gc>, value: Value<'gc>) {
+        self.indices.insert(key, self.entries.len());
+        self.entries.push((key, value));
+    }
 }
 
 // Value which implements Hash and Eq, and cannot contain Nil or NaN values.
-#[derive(Debug, Collect, PartialEq)]
-#[collect(empty_drop)]
+#[derive(Debug, Copy, Clone, Collect, PartialEq)]
+#[collect(require_copy)]
 struct TableKey<'gc>(Value<'gc>);
 
 impl<'gc> Eq for TableKey<'gc> {}
@@ -1,7 +1,7 @@
 use std::collections::btree_map::Entry as BTreeEntry;
 use std::collections::BTreeMap;
 
-use failure::Error;
+use failure::{err_msg, format_err, Error};
 
 use gc_arena::{Collect, Gc, GcCell, MutationContext};
 
@@ -49,19 +49,36 @@ impl<'gc> Thread<'gc> {
         state.stack.push(Value::Closure(closure));
         state.stack.extend(args);
         let res_pc = state.pc;
-        state.call_function(
-            closure_index,
-            VarCount::variable(),
-            VarCount::variable(),
-            res_pc,
-            true,
-        );
+        state
+            .call_function(
+                closure_index,
+                VarCount::variable(),
+                VarCount::variable(),
+                res_pc,
+                true,
+            )
+            .expect("closure is always callable");
 
         ThreadSequence {
             thread: Some(*self),
             granularity,
         }
     }
+
+    // Limits the VM instructions which `ThreadSequence` steps on this thread may execute in total,
+    // so that the host can bound a slice of execution by instruction count.  While a budget is set,
+    // each step executes no more than the remaining budget, and subtracts what it executed.
+    pub(crate) fn set_instruction_budget(
+        &self,
+        mc: MutationContext<'gc, '_>,
+        instruction_budget: Option<u32>,
+    ) {
+        self.0.write(mc).instruction_budget = instruction_budget;
+    }
+
+    pub(crate) fn instruction_budget(&self) -> Option<u32> {
+        self.0.read().instruction_budget
+    }
 }
 
 #[derive(Debug, Copy, Clone, Collect)]
@@ -77,11 +94,25 @@ impl<'gc> Sequence<'gc> for ThreadSequence<'gc> {
     fn pump(&mut self, mc: MutationContext<'gc, '_>) -> Option<Result<Vec<Value<'gc>>, Error>> {
         let thread = self.thread.expect("cannot pump a finished ThreadSequence");
         let mut state = thread.0.write(mc);
-        if let Some(res) = state.run(mc, thread, self.granularity) {
-            self.thread = None;
-            Some(Ok(res))
-        } else {
-            None
+        let limit = match state.instruction_budget {
+            Some(budget) => self.granularity.min(budget),
+            None => self.granularity,
+        };
+        let mut remaining = limit;
+        let res = state.run(mc, thread, &mut remaining);
+        if let Some(budget) = &mut state.instruction_budget {
+            *budget -= limit - remaining;
+        }
+        match res {
+            Ok(Some(res)) => {
+                self.thread = None;
+                Some(Ok(res))
+            }
+            Ok(None) => None,
+            Err(err) => {
+                self.thread = None;
+                Some(Err(state.runtime_error(mc, thread, err)))
+            }
         }
     }
 }
@@ -104,6 +135,7 @@ struct ThreadState<'gc> {
     frames: Vec<Frame>,
     pc: usize,
     open_upvalues: BTreeMap<usize, UpValue<'gc>>,
+    instruction_budget: Option<u32>,
 }
 
 impl<'gc> ThreadState<'gc> {
@@ -113,15 +145,18 @@ impl<'gc> ThreadState<'gc> {
             frames: Vec::new(),
             pc: 0,
             open_upvalues: BTreeMap::new(),
+            instruction_budget: None,
         }
     }
 
+    // Executes at most `instructions` VM instructions, and leaves the number not executed in
+    // `instructions`.
     fn run(
         &mut self,
         mc: MutationContext<'gc, '_>,
         self_thread: Thread<'gc>,
-        mut instructions: u32,
-    ) -> Option<Vec<Value<'gc>>> {
+        instructions: &mut u32,
+    ) -> Result<Option<Vec<Value<'gc>>>, Error> {
         'function_start: loop {
             let current_frame = self
                 .frames
@@ -132,6 +167,11 @@ impl<'gc> ThreadState<'gc> {
             let current_function = get_closure(self.stack[current_frame.bottom]);
 
             loop {
+                if *instructions == 0 {
+                    return Ok(None);
+                }
+                *instructions -= 1;
+
                 let op = current_function.0.proto.opcodes[self.pc];
                 self.pc += 1;
 
@@ -170,54 +210,62 @@ impl<'gc> ThreadState<'gc> {
 
                     OpCode::GetTableR { dest, table, key } => {
                         self.stack[current_frame.base + dest.0 as usize] =
-                            get_table(self.stack[current_frame.base + table.0 as usize])
+                            get_table(self.stack[current_frame.base + table.0 as usize])?
                                 .get(self.stack[current_frame.base + key.0 as usize]);
                     }
 
                     OpCode::GetTableC { dest, table, key } => {
                         self.stack[current_frame.base + dest.0 as usize] =
-                            get_table(self.stack[current_frame.base + table.0 as usize])
+                            get_table(self.stack[current_frame.base + table.0 as usize])?
                                 .get(current_function.0.proto.constants[key.0 as usize]);
                     }
 
+                    OpCode::SelfR { base, table, key } => {
+                        let table = self.stack[current_frame.base + table.0 as usize];
+                        let key = self.stack[current_frame.base + key.0 as usize];
+                        self.stack[current_frame.base + base.0 as usize + 1] = table;
+                        self.stack[current_frame.base + base.0 as usize] =
+                            get_table(table)?.get(key);
+                    }
+
+                    OpCode::SelfC { base, table, key } => {
+                        let table = self.stack[current_frame.base + table.0 as usize];
+                        let key = current_function.0.proto.constants[key.0 as usize];
+                        self.stack[current_frame.base + base.0 as usize + 1] = table;
+                        self.stack[current_frame.base + base.0 as usize] =
+                            get_table(table)?.get(key);
+                    }
+
                     OpCode::SetTableRR { table, key, value } => {
-                        get_table(self.stack[current_frame.base + table.0 as usize])
-                            .set(
-                                mc,
-                                self.stack[current_frame.base + key.0 as usize],
-                                self.stack[current_frame.base + value.0 as usize],
-                            )
-                            .expect("could not set table value");
+                        get_table(self.stack[current_frame.base + table.0 as usize])?.set(
+                            mc,
+                            self.stack[current_frame.base + key.0 as usize],
+                            self.stack[current_frame.base + value.0 as usize],
+                        )?;
                     }
 
                     OpCode::SetTableRC { table, key, value } => {
-                        get_table(self.stack[current_frame.base + table.0 as usize])
-                            .set(
-                                mc,
-                                self.stack[current_frame.base + key.0 as usize],
-                                current_function.0.proto.constants[value.0 as usize],
-                            )
-                            .expect("could not set table value");
+                        get_table(self.stack[current_frame.base + table.0 as usize])?.set(
+                            mc,
+                            self.stack[current_frame.base + key.0 as usize],
+                            current_function.0.proto.constants[value.0 as usize],
+                        )?;
                     }
 
                     OpCode::SetTableCR { table, key, value } => {
-                        get_table(self.stack[current_frame.base + table.0 as usize])
-                            .set(
-                                mc,
-                                current_function.0.proto.constants[key.0 as usize],
-                                self.stack[current_frame.base + value.0 as usize],
-                            )
-                            .expect("could not set table value");
+                        get_table(self.stack[current_frame.base + table.0 as usize])?.set(
+                            mc,
+                            current_function.0.proto.constants[key.0 as usize],
+                            self.stack[current_frame.base + value.0 as usize],
+                        )?;
                     }
 
                     OpCode::SetTableCC { table, key, value } => {
-                        get_table(self.stack[current_frame.base + table.0 as usize])
-                            .set(
-                                mc,
-                                current_function.0.proto.constants[key.0 as usize],
-                                current_function.0.proto.constants[value.0 as usize],
-                            )
-                            .expect("could not set table value");
+                        get_table(self.stack[current_frame.base + table.0 as usize])?.set(
+                            mc,
+                            current_function.0.proto.constants[key.0 as usize],
+                            current_function.0.proto.constants[value.0 as usize],
+                        )?;
                     }
 
                     OpCode::GetUpTableR { dest, table, key } => {
@@ -225,7 +273,7 @@ impl<'gc> ThreadState<'gc> {
                             get_table(self.get_upvalue(
                                 self_thread,
                                 current_function.0.upvalues[table.0 as usize],
-                            ))
+                            ))?
                             .get(self.stack[current_frame.base + key.0 as usize]);
                     }
 
@@ -234,7 +282,7 @@ impl<'gc> ThreadState<'gc> {
                             get_table(self.get_upvalue(
                                 self_thread,
                                 current_function.0.upvalues[table.0 as usize],
-                            ))
+                            ))?
                             .get(current_function.0.proto.constants[key.0 as usize]);
                     }
 
@@ -242,52 +290,48 @@ impl<'gc> ThreadState<'gc> {
                         get_table(self.get_upvalue(
                             self_thread,
                             current_function.0.upvalues[table.0 as usize],
-                        ))
+                        ))?
                         .set(
                             mc,
                             self.stack[current_frame.base + key.0 as usize],
                             self.stack[current_frame.base + value.0 as usize],
-                        )
-                        .expect("could not set table value");
+                        )?;
                     }
 
                     OpCode::SetUpTableRC { table, key, value } => {
                         get_table(self.get_upvalue(
                             self_thread,
                             current_function.0.upvalues[table.0 as usize],
-                        ))
+                        ))?
                         .set(
                             mc,
                             self.stack[current_frame.base + key.0 as usize],
                             current_function.0.proto.constants[value.0 as usize],
-                        )
-                        .expect("could not set table value");
+                        )?;
                     }
 
                     OpCode::SetUpTableCR { table, key, value } => {
                         get_table(self.get_upvalue(
                             self_thread,
                             current_function.0.upvalues[table.0 as usize],
-                        ))
+                        ))?
                         .set(
                             mc,
                             current_function.0.proto.constants[key.0 as usize],
                             self.stack[current_frame.base + value.0 as usize],
-                        )
-                        .expect("could not set table value");
+                        )?;
                     }
 
                     OpCode::SetUpTableCC { table, key, value } => {
                         get_table(self.get_upvalue(
                             self_thread,
                             current_function.0.upvalues[table.0 as usize],
-                        ))
+                        ))?
                         .set(
                             mc,
                             current_function.0.proto.constants[key.0 as usize],
                             current_function.0.proto.constants[value.0 as usize],
-                        )
-                        .expect("could not set table value");
+                        )?;
                     }
 
                     OpCode::Call {
@@ -301,7 +345,40 @@ impl<'gc> ThreadState<'gc> {
                             returns,
                             self.pc,
                             false,
-                        );
+                        )?;
+                        continue 'function_start;
+                    }
+
+                    OpCode::TailCall { func, args } => {
+                        self.close_upvalues(mc, self_thread, current_frame.bottom);
+
+                        // Move the function and its arguments down to the bottom of the current
+                        // frame and call it in place of the current function, with the current
+                        // function's expected returns.
+                        let func = current_frame.base + func.0 as usize;
+                        // Check the function before the current frame is removed, so that an error
+                        // is located in the calling function.
+                        match self.stack[func] {
+                            Value::Closure(_) => {}
+                            _ => return Err(err_msg("attempt to call a non-function value")),
+                        }
+                        let arg_count = args
+                            .get_constant()
+                            .map(|c| c as usize)
+                            .unwrap_or(self.stack.len() - func - 1);
+                        for i in 0..=arg_count {
+                            self.stack[current_frame.bottom + i] = self.stack[func + i];
+                        }
+                        self.stack.truncate(current_frame.bottom + arg_count + 1);
+
+                        self.frames.pop();
+                        self.call_function(
+                            current_frame.bottom,
+                            VarCount::variable(),
+                            current_frame.returns,
+                            current_frame.restore_pc,
+                            current_frame.call_boundary,
+                        )?;
                         continue 'function_start;
                     }
 
@@ -331,7 +408,7 @@ impl<'gc> ThreadState<'gc> {
                                 self.stack.clear();
                             }
 
-                            return Some(ret_vals);
+                            return Ok(Some(ret_vals));
                         } else {
                             for i in 0..returning.min(count) {
                                 self.stack[current_frame.bottom + i] = self.stack[start + i]
@@ -399,6 +476,23 @@ impl<'gc> ThreadState<'gc> {
                         }
                     }
 
+                    OpCode::Switch { value, table } => {
+                        // Integral floats are equal to the corresponding integer, so look them up
+                        // as integers.
+                        let value = match self.stack[current_frame.base + value.0 as usize] {
+                            Value::Number(n) => Value::Number(n)
+                                .to_integer()
+                                .map(Value::Integer)
+                                .unwrap_or(Value::Number(n)),
+                            value => value,
+                        };
+                        if let Value::Integer(case) =
+                            current_function.0.proto.switches[table.0 as usize].get(value)
+                        {
+                            self.pc += case as usize;
+                        }
+                    }
+
                     OpCode::Test { value, is_true } => {
                         let value = self.stack[current_frame.base + value.0 as usize];
                         if value.as_bool() == is_true {
@@ -419,6 +513,37 @@ impl<'gc> ThreadState<'gc> {
                         }
                     }
 
+                    OpCode::SetList { base, count } => {
+                        let base = current_frame.base + base.0 as usize;
+                        let table = match self.stack[base] {
+                            Value::Table(t) => t,
+                            _ => panic!("SetList on a value that is not a table"),
+                        };
+                        let start = match self.stack[base + 1] {
+                            Value::Integer(i) => i,
+                            _ => panic!("SetList start index is not an integer"),
+                        };
+                        let values_start = base + 2;
+                        let value_count = count
+                            .get_constant()
+                            .map(|c| c as usize)
+                            .unwrap_or(self.stack.len() - values_start);
+
+                        for i in 0..value_count {
+                            table.set(
+                                mc,
+                                Value::Integer(start + i as i64),
+                                self.stack[values_start + i],
+                            )?;
+                        }
+
+                        if count.is_variable() {
+                            // Variable values have set the top of the stack, which we must reset now
+                            // that they are consumed.
+                            self.stack.resize(current_frame.top, Value::Nil);
+                        }
+                    }
+
                     OpCode::Closure { proto, dest } => {
                         let proto = current_function.0.proto.prototypes[proto.0 as usize];
                         let mut upvalues = Vec::new();
@@ -457,7 +582,7 @@ impl<'gc> ThreadState<'gc> {
                         let base = current_frame.base + base.0 as usize;
                         self.stack[base] = self.stack[base]
                             .subtract(self.stack[base + 2])
-                            .expect("non numeric for loop parameters");
+                            .ok_or_else(|| err_msg("non numeric for loop parameters"))?;
                         self.pc = add_offset(self.pc, jump);
                     }
 
@@ -465,19 +590,20 @@ impl<'gc> ThreadState<'gc> {
                         const ERR_MSG: &str = "non numeric for loop parameter";
 
                         let base = current_frame.base + base.0 as usize;
-                        self.stack[base] =
-                            self.stack[base].add(self.stack[base + 2]).expect(ERR_MSG);
+                        self.stack[base] = self.stack[base]
+                            .add(self.stack[base + 2])
+                            .ok_or_else(|| err_msg(ERR_MSG))?;
                         let past_end = if self.stack[base + 2]
                             .less_than(Value::Integer(0))
-                            .expect(ERR_MSG)
+                            .ok_or_else(|| err_msg(ERR_MSG))?
                         {
                             self.stack[base]
                                 .less_than(self.stack[base + 1])
-                                .expect(ERR_MSG)
+                                .ok_or_else(|| err_msg(ERR_MSG))?
                         } else {
                             self.stack[base + 1]
                                 .less_than(self.stack[base])
-                                .expect(ERR_MSG)
+                                .ok_or_else(|| err_msg(ERR_MSG))?
                         };
                         if !past_end {
                             self.pc = add_offset(self.pc, jump);
@@ -485,6 +611,38 @@ impl<'gc> ThreadState<'gc> {
                         }
                     }
 
+                    OpCode::IntegerForPrep { base, jump } => {
+                        let base = current_frame.base + base.0 as usize;
+                        let (counter, limit, step) = integer_for_parameters(&self.stack[base..])?;
+                        if integer_for_continues(counter, limit, step) {
+                            self.stack[base + 3] = Value::Integer(counter);
+                        } else {
+                            self.pc = add_offset(self.pc, jump);
+                        }
+                    }
+
+                    OpCode::IntegerForLoop { base, jump } => {
+                        let base = current_frame.base + base.0 as usize;
+                        // `IntegerForPrep` has already checked that the hidden loop registers hold
+                        // integers, and nothing else writes to them.
+                        let (counter, limit, step) =
+                            match (self.stack[base], self.stack[base + 1], self.stack[base + 2]) {
+                                (
+                                    Value::Integer(counter),
+                                    Value::Integer(limit),
+                                    Value::Integer(step),
+                                ) => (counter, limit, step),
+                                _ => unreachable!("non integer for loop parameter"),
+                            };
+                        if let Some(counter) = counter.checked_add(step) {
+                            if integer_for_continues(counter, limit, step) {
+                                self.stack[base] = Value::Integer(counter);
+                                self.stack[base + 3] = Value::Integer(counter);
+                                self.pc = add_offset(self.pc, jump);
+                            }
+                        }
+                    }
+
                     OpCode::GenericForCall { base, var_count } => {
                         let base = current_frame.base + base.0 as usize;
                         self.stack.resize(base + 6, Value::Nil);
@@ -497,7 +655,7 @@ impl<'gc> ThreadState<'gc> {
                             VarCount::constant(var_count),
                             self.pc,
                             false,
-                        );
+                        )?;
                         continue 'function_start;
                     }
 
@@ -579,44 +737,556 @@ impl<'gc> ThreadState<'gc> {
                         }
                     }
 
+                    OpCode::LessRR {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        if left
+                            .less_than(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessRC {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        if left
+                            .less_than(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessCR {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        if left
+                            .less_than(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessCC {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        if left
+                            .less_than(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessEqRR {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        if left
+                            .less_equal(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessEqRC {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        if left
+                            .less_equal(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessEqCR {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        if left
+                            .less_equal(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
+                    OpCode::LessEqCC {
+                        skip_if,
+                        left,
+                        right,
+                    } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        if left
+                            .less_equal(right)
+                            .ok_or_else(|| err_msg("could not compare values"))?
+                            == skip_if
+                        {
+                            self.pc += 1;
+                        }
+                    }
+
                     OpCode::Not { dest, source } => {
                         let source = self.stack[current_frame.base + source.0 as usize];
                         self.stack[current_frame.base + dest.0 as usize] = source.not();
                     }
 
+                    OpCode::Minus { dest, source } => {
+                        let source = self.stack[current_frame.base + source.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = source
+                            .negate()
+                            .ok_or_else(|| err_msg("could not apply unary operator"))?;
+                    }
+
+                    OpCode::BitNot { dest, source } => {
+                        let source = self.stack[current_frame.base + source.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = source
+                            .bitwise_not()
+                            .ok_or_else(|| err_msg("could not apply unary operator"))?;
+                    }
+
+                    OpCode::Length { dest, source } => {
+                        let source = self.stack[current_frame.base + source.0 as usize];
+                        // When metatables are supported, this is where the `__len` metamethod of a
+                        // table should be called.
+                        self.stack[current_frame.base + dest.0 as usize] = source
+                            .length()
+                            .ok_or_else(|| err_msg("could not get the length of a value"))?;
+                    }
+
+                    OpCode::Concat {
+                        dest,
+                        source,
+                        count,
+                    } => {
+                        let source = current_frame.base + source.0 as usize;
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            Value::concat(mc, &self.stack[source..source + count as usize])
+                                .ok_or_else(|| err_msg("could not concatenate values"))?;
+                    }
+
                     OpCode::AddRR { dest, left, right } => {
                         let left = self.stack[current_frame.base + left.0 as usize];
                         let right = self.stack[current_frame.base + right.0 as usize];
-                        self.stack[current_frame.base + dest.0 as usize] =
-                            left.add(right).expect("could not apply binary operator");
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .add(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
                     }
 
                     OpCode::AddRC { dest, left, right } => {
                         let left = self.stack[current_frame.base + left.0 as usize];
                         let right = current_function.0.proto.constants[right.0 as usize];
-                        self.stack[current_frame.base + dest.0 as usize] =
-                            left.add(right).expect("could not apply binary operator");
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .add(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
                     }
 
                     OpCode::AddCR { dest, left, right } => {
                         let left = current_function.0.proto.constants[left.0 as usize];
                         let right = self.stack[current_frame.base + right.0 as usize];
-                        self.stack[current_frame.base + dest.0 as usize] =
-                            left.add(right).expect("could not apply binary operator");
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .add(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
                     }
 
                     OpCode::AddCC { dest, left, right } => {
                         let left = current_function.0.proto.constants[left.0 as usize];
                         let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .add(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::SubRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .subtract(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::SubRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .subtract(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::SubCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .subtract(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::SubCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .subtract(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::MulRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .multiply(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::MulRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .multiply(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::MulCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .multiply(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::MulCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .multiply(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ModRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .modulo(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ModRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .modulo(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ModCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .modulo(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ModCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .modulo(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::PowRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .power(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::PowRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .power(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::PowCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .power(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::PowCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .power(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::DivRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::DivRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::DivCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::DivCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::IDivRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .floor_divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::IDivRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .floor_divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::IDivCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .floor_divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::IDivCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .floor_divide(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitAndRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_and(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitAndRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_and(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitAndCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_and(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitAndCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_and(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitOrRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
                         self.stack[current_frame.base + dest.0 as usize] =
-                            left.add(right).expect("could not apply binary operator");
+                            left.bitwise_or(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
                     }
-                }
 
-                if instructions == 0 {
-                    return None;
-                } else {
-                    instructions -= 1
+                    OpCode::BitOrRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.bitwise_or(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitOrCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.bitwise_or(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitOrCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.bitwise_or(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitXorRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_xor(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitXorRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_xor(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitXorCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_xor(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::BitXorCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .bitwise_xor(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftLeftRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.shift_left(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftLeftRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.shift_left(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftLeftCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.shift_left(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftLeftCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] =
+                            left.shift_left(right)
+                                .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftRightRR { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .shift_right(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftRightRC { dest, left, right } => {
+                        let left = self.stack[current_frame.base + left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .shift_right(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftRightCR { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = self.stack[current_frame.base + right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .shift_right(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
+
+                    OpCode::ShiftRightCC { dest, left, right } => {
+                        let left = current_function.0.proto.constants[left.0 as usize];
+                        let right = current_function.0.proto.constants[right.0 as usize];
+                        self.stack[current_frame.base + dest.0 as usize] = left
+                            .shift_right(right)
+                            .ok_or_else(|| err_msg("could not apply binary operator"))?;
+                    }
                 }
             }
         }
@@ -629,10 +1299,10 @@ impl<'gc> ThreadState<'gc> {
         returns: VarCount,
         restore_pc: usize,
         call_boundary: bool,
-    ) {
+    ) -> Result<(), Error> {
         let closure = match self.stack[closure_index] {
             Value::Closure(c) => c,
-            _ => panic!("not a closure"),
+            _ => return Err(err_msg("attempt to call a non-function value")),
         };
 
         let arg_count = if let Some(constant) = args.get_constant() {
@@ -671,6 +1341,39 @@ impl<'gc> ThreadState<'gc> {
         });
 
         self.pc = 0;
+        Ok(())
+    }
+
+    // Locates an error raised while running at the chunk and line of the current opcode, then
+    // unwinds every frame up to and including the most recent call boundary so that the thread may
+    // be used again.
+    fn runtime_error(
+        &mut self,
+        mc: MutationContext<'gc, '_>,
+        self_thread: Thread<'gc>,
+        error: Error,
+    ) -> Error {
+        let current_frame = *self.frames.last().expect("no current ThreadState frame");
+        let proto = get_closure(self.stack[current_frame.bottom]).0.proto;
+        let chunk_name = String::from_utf8_lossy(proto.chunk_name.as_bytes()).into_owned();
+        let error = match proto.opcode_line(self.pc - 1) {
+            Some(line) => format_err!("{}:{}: {}", chunk_name, line.0, error),
+            None => format_err!("{}:?: {}", chunk_name, error),
+        };
+
+        while let Some(frame) = self.frames.pop() {
+            if frame.call_boundary {
+                self.close_upvalues(mc, self_thread, frame.bottom);
+                self.pc = frame.restore_pc;
+                self.stack.truncate(frame.bottom);
+                if let Some(frame) = self.frames.last() {
+                    self.stack.resize(frame.top, Value::Nil);
+                }
+                break;
+            }
+        }
+
+        error
     }
 
     fn get_upvalue(&self, self_thread: Thread<'gc>, upvalue: UpValue<'gc>) -> Value<'gc> {
@@ -712,10 +1415,10 @@ fn get_closure<'gc>(value: Value<'gc>) -> Closure<'gc> {
     }
 }
 
-fn get_table<'gc>(value: Value<'gc>) -> Table<'gc> {
+fn get_table<'gc>(value: Value<'gc>) -> Result<Table<'gc>, Error> {
     match value {
-        Value::Table(t) => t,
-        _ => panic!("value is not a table"),
+        Value::Table(t) => Ok(t),
+        _ => Err(err_msg("attempt to index a non-table value")),
     }
 }
 
@@ -728,3 +1431,22 @@ fn add_offset(pc: usize, offset: i16) -> usize {
         pc
     }
 }
+
+// The counter, limit and step of a numeric for loop compiled with `IntegerForPrep`, checked once
+// when the loop is entered.
+fn integer_for_parameters(stack: &[Value]) -> Result<(i64, i64, i64), Error> {
+    match (stack[0], stack[1], stack[2]) {
+        (Value::Integer(counter), Value::Integer(limit), Value::Integer(step)) => {
+            Ok((counter, limit, step))
+        }
+        _ => Err(err_msg("non integer for loop parameter")),
+    }
+}
+
+fn integer_for_continues(counter: i64, limit: i64, step: i64) -> bool {
+    if step < 0 {
+        counter >= limit
+    } else {
+        counter <= limit
+    }
+}
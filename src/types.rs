@@ -3,7 +3,7 @@ use std::fmt::{self, Debug};
 use gc_arena::Collect;
 
 /// An index that points to a register in the stack relative to the current frame.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Collect)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Collect)]
 #[collect(require_static)]
 pub struct RegisterIndex(pub u8);
 
@@ -27,6 +27,16 @@ pub struct UpValueIndex(pub u8);
 #[collect(require_static)]
 pub struct PrototypeIndex(pub u8);
 
+/// An index into the switch table list
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Collect)]
+#[collect(require_static)]
+pub struct SwitchIndex(pub u8);
+
+/// A 1-indexed line number in the source of a chunk
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Collect)]
+#[collect(require_static)]
+pub struct LineNumber(pub u64);
+
 /// A one byte Option value that can either be Some(0-254) or None
 #[derive(Copy, Clone, Eq, PartialEq, Collect)]
 #[collect(require_static)]
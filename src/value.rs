@@ -1,9 +1,10 @@
 use std::i64;
+use std::io::Write;
 
-use gc_arena::Collect;
+use gc_arena::{Collect, MutationContext};
 
 use crate::function::Closure;
-use crate::string::String;
+use crate::string::{String, StringBuilder};
 use crate::table::Table;
 
 #[derive(Debug, Copy, Clone, Collect)]
@@ -61,9 +62,26 @@ impl<'gc> Value<'gc> {
         Value::Boolean(!self.as_bool())
     }
 
+    pub fn negate(self) -> Option<Value<'gc>> {
+        match self {
+            Value::Integer(a) => Some(Value::Integer(a.wrapping_neg())),
+            Value::Number(a) => Some(Value::Number(-a)),
+            _ => None,
+        }
+    }
+
+    /// The raw length of a string or table, without considering any metamethods.
+    pub fn length(self) -> Option<Value<'gc>> {
+        match self {
+            Value::String(s) => Some(Value::Integer(s.len() as i64)),
+            Value::Table(t) => Some(Value::Integer(t.length())),
+            _ => None,
+        }
+    }
+
     pub fn add(self, other: Value<'gc>) -> Option<Value<'gc>> {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a + b)),
+            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a.wrapping_add(b))),
             (Value::Number(a), Value::Number(b)) => Some(Value::Number(a + b)),
             (Value::Integer(a), Value::Number(b)) => Some(Value::Number(a as f64 + b)),
             (Value::Number(a), Value::Integer(b)) => Some(Value::Number(a + b as f64)),
@@ -73,7 +91,7 @@ impl<'gc> Value<'gc> {
 
     pub fn subtract(self, other: Value<'gc>) -> Option<Value<'gc>> {
         match (self, other) {
-            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a - b)),
+            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a.wrapping_sub(b))),
             (Value::Number(a), Value::Number(b)) => Some(Value::Number(a - b)),
             (Value::Integer(a), Value::Number(b)) => Some(Value::Number(a as f64 - b)),
             (Value::Number(a), Value::Integer(b)) => Some(Value::Number(a - b as f64)),
@@ -81,13 +99,223 @@ impl<'gc> Value<'gc> {
         }
     }
 
+    pub fn multiply(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(Value::Integer(a.wrapping_mul(b))),
+            (Value::Number(a), Value::Number(b)) => Some(Value::Number(a * b)),
+            (Value::Integer(a), Value::Number(b)) => Some(Value::Number(a as f64 * b)),
+            (Value::Number(a), Value::Integer(b)) => Some(Value::Number(a * b as f64)),
+            _ => None,
+        }
+    }
+
+    /// Float division, the result is always a float even for integer operands.
+    pub fn divide(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.float_operands(other)?;
+        Some(Value::Number(a / b))
+    }
+
+    /// Floor division, which results in an integer for integer operands.  Integer division by zero
+    /// is an error.
+    pub fn floor_divide(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        match (self, other) {
+            (Value::Integer(_), Value::Integer(0)) => None,
+            (Value::Integer(a), Value::Integer(b)) => {
+                let q = a.wrapping_div(b);
+                // Round towards negative infinity rather than towards zero
+                if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+                    Some(Value::Integer(q - 1))
+                } else {
+                    Some(Value::Integer(q))
+                }
+            }
+            _ => {
+                let (a, b) = self.float_operands(other)?;
+                Some(Value::Number((a / b).floor()))
+            }
+        }
+    }
+
+    /// Lua modulo, where the result has the same sign as the divisor.  Integer modulo by zero is an
+    /// error.
+    pub fn modulo(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        match (self, other) {
+            (Value::Integer(_), Value::Integer(0)) => None,
+            (Value::Integer(a), Value::Integer(b)) => {
+                let r = a.wrapping_rem(b);
+                if r != 0 && (r < 0) != (b < 0) {
+                    Some(Value::Integer(r + b))
+                } else {
+                    Some(Value::Integer(r))
+                }
+            }
+            _ => {
+                let (a, b) = self.float_operands(other)?;
+                let r = a % b;
+                if r != 0.0 && (r < 0.0) != (b < 0.0) {
+                    Some(Value::Number(r + b))
+                } else {
+                    Some(Value::Number(r))
+                }
+            }
+        }
+    }
+
+    /// Exponentiation, the result is always a float even for integer operands.
+    pub fn power(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.float_operands(other)?;
+        Some(Value::Number(a.powf(b)))
+    }
+
+    pub fn bitwise_not(self) -> Option<Value<'gc>> {
+        Some(Value::Integer(!self.to_integer()?))
+    }
+
+    pub fn bitwise_and(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.integer_operands(other)?;
+        Some(Value::Integer(a & b))
+    }
+
+    pub fn bitwise_or(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.integer_operands(other)?;
+        Some(Value::Integer(a | b))
+    }
+
+    pub fn bitwise_xor(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.integer_operands(other)?;
+        Some(Value::Integer(a ^ b))
+    }
+
+    /// Logical left shift, shifting right for negative shift amounts.  Shifting by 64 or more bits
+    /// in either direction results in zero.
+    pub fn shift_left(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.integer_operands(other)?;
+        Some(Value::Integer(shift_left(a, b)))
+    }
+
+    /// Logical right shift, shifting left for negative shift amounts.
+    pub fn shift_right(self, other: Value<'gc>) -> Option<Value<'gc>> {
+        let (a, b) = self.integer_operands(other)?;
+        Some(Value::Integer(shift_left(a, b.wrapping_neg())))
+    }
+
     pub fn less_than(self, other: Value<'gc>) -> Option<bool> {
         match (self, other) {
             (Value::Integer(a), Value::Integer(b)) => Some(a < b),
             (Value::Number(a), Value::Number(b)) => Some(a < b),
             (Value::Integer(a), Value::Number(b)) => Some((a as f64) < b),
             (Value::Number(a), Value::Integer(b)) => Some(a < (b as f64)),
+            (Value::String(a), Value::String(b)) => Some(a.as_bytes() < b.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn less_equal(self, other: Value<'gc>) -> Option<bool> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a <= b),
+            (Value::Number(a), Value::Number(b)) => Some(a <= b),
+            (Value::Integer(a), Value::Number(b)) => Some((a as f64) <= b),
+            (Value::Number(a), Value::Integer(b)) => Some(a <= (b as f64)),
+            (Value::String(a), Value::String(b)) => Some(a.as_bytes() <= b.as_bytes()),
             _ => None,
         }
     }
+
+    // Converts both operands of an arithmetic operation to floats, if they are both numbers
+    fn float_operands(self, other: Value<'gc>) -> Option<(f64, f64)> {
+        let to_float = |v| match v {
+            Value::Integer(i) => Some(i as f64),
+            Value::Number(n) => Some(n),
+            _ => None,
+        };
+        Some((to_float(self)?, to_float(other)?))
+    }
+
+    /// Converts a number to an integer if it has an exact integer representation, which is the
+    /// conversion Lua applies to the operands of bitwise operators.
+    pub fn to_integer(self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(i),
+            // 2^63 is exactly representable as a float, but is outside of the range of i64
+            Value::Number(n)
+                if n.floor() == n
+                    && (-9223372036854775808.0..9223372036854775808.0).contains(&n) =>
+            {
+                Some(n as i64)
+            }
+            _ => None,
+        }
+    }
+
+    fn integer_operands(self, other: Value<'gc>) -> Option<(i64, i64)> {
+        Some((self.to_integer()?, other.to_integer()?))
+    }
+
+    /// Concatenates a sequence of strings and numbers as the Lua `..` operator does, converting
+    /// numbers to strings in the same format as `tostring`. Returns None if any value is not a
+    /// string or a number.
+    pub fn concat(mc: MutationContext<'gc, '_>, values: &[Value<'gc>]) -> Option<Value<'gc>> {
+        let mut builder = StringBuilder::new();
+        for &value in values {
+            match value {
+                Value::String(s) => builder.append(s.as_bytes()),
+                Value::Integer(i) => write!(builder, "{}", i).unwrap(),
+                Value::Number(n) => builder.append(format_number(n).as_bytes()),
+                _ => return None,
+            }
+        }
+        Some(Value::String(builder.finish(mc)))
+    }
+}
+
+fn shift_left(a: i64, b: i64) -> i64 {
+    if b <= -64 || b >= 64 {
+        0
+    } else if b >= 0 {
+        ((a as u64) << b) as i64
+    } else {
+        ((a as u64) >> -b) as i64
+    }
+}
+
+// Formats a float the way PUC-Rio Lua does with its default "%.14g" format, adding a trailing ".0"
+// when the result would otherwise look like an integer.
+fn format_number(n: f64) -> std::string::String {
+    if n.is_nan() {
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.to_owned();
+    } else if n.is_infinite() {
+        return if n < 0.0 { "-inf" } else { "inf" }.to_owned();
+    }
+
+    // Formatting in exponential notation first gives the decimal exponent after rounding to 14
+    // significant digits, which decides between the fixed and exponential forms.
+    let exponential = format!("{:.13e}", n);
+    let e_pos = exponential.find('e').unwrap();
+    let exponent: i32 = exponential[e_pos + 1..].parse().unwrap();
+
+    if !(-4..14).contains(&exponent) {
+        let mantissa = trim_fraction(&exponential[..e_pos]);
+        format!(
+            "{}e{}{:02}",
+            mantissa,
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    } else {
+        let fixed = format!("{:.*}", (13 - exponent) as usize, n);
+        let fixed = trim_fraction(&fixed);
+        if fixed.contains('.') {
+            fixed.to_owned()
+        } else {
+            format!("{}.0", fixed)
+        }
+    }
+}
+
+fn trim_fraction(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
 }
@@ -0,0 +1,65 @@
+use failure::Error;
+
+use luster::compiler::{CompilationCache, CompileError, CompilerOptions, OptimizationLevel};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::sequence::SequenceExt;
+use luster::value::Value;
+
+const SOURCE: &str = r#"
+    local s = "x" .. 1.5
+    local function f(n)
+        if n == 1 then return "one" elseif n == 2 then return "two" elseif n == 3 then return s end
+    end
+    return f(1) == "one" and f(3) == "x1.5" and f(4) == nil
+"#;
+
+// Compiles the source through the cache in a new arena and runs it, returning whether it returned
+// true along with the opcodes of the compiled function.
+fn run_cached(
+    cache: &mut CompilationCache,
+    source: &str,
+    options: CompilerOptions,
+) -> Result<(bool, String), Error> {
+    Lua::new().sequence(move |mc, lc| {
+        let proto = cache.compile(mc, b"test", source.as_bytes(), &options)?;
+        let opcodes = format!("{:?}", proto.opcodes);
+        Ok(Box::new(
+            lc.main_thread
+                .call_function(mc, Closure::new(mc, proto, Some(lc.globals))?, &[], 64)
+                .map(move |_, r| Ok((r == [Value::Boolean(true)], opcodes))),
+        ))
+    })
+}
+
+#[test]
+fn test_compilation_cache() {
+    let mut cache = CompilationCache::new();
+    assert!(cache.is_empty());
+
+    let compiled = run_cached(&mut cache, SOURCE, CompilerOptions::default()).unwrap();
+    assert!(compiled.0);
+    assert_eq!(cache.len(), 1);
+
+    // Rehydrated in a different arena, and behaves the same as the compiled chunk
+    let cached = run_cached(&mut cache, SOURCE, CompilerOptions::default()).unwrap();
+    assert_eq!(cached, compiled);
+    assert_eq!(cache.len(), 1);
+
+    // Different options are a different chunk
+    let options = CompilerOptions::default().set_optimization_level(OptimizationLevel::O0);
+    let unoptimized = run_cached(&mut cache, SOURCE, options).unwrap();
+    assert!(unoptimized.0);
+    assert_eq!(cache.len(), 2);
+
+    // Chunks which fail to compile are not cached
+    let err = run_cached(&mut cache, "local = 1", CompilerOptions::default()).unwrap_err();
+    match err.downcast::<CompileError>() {
+        Ok(CompileError::Parse { .. }) => {}
+        err => panic!("unexpected error {:?}", err),
+    }
+    assert_eq!(cache.len(), 2);
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
@@ -0,0 +1,581 @@
+use luster::compiler::{
+    compile, compile_chunk, compile_chunk_with_warnings, compile_function, CompileError,
+    CompilerError, CompilerErrorKind, CompilerOptions, OptimizationLevel,
+};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::opcode::OpCode;
+use luster::parser::{parse_chunk, Chunk, Expression, HeadExpression, Statement, UnaryOperator};
+use luster::sequence::{sequence_fn, SequenceExt};
+use luster::types::LineNumber;
+use luster::value::Value;
+
+fn compile_error(chunk: Chunk, options: CompilerOptions) -> CompilerError {
+    let err = Lua::new()
+        .sequence(move |mc, _| {
+            compile_chunk(mc, b"test", &chunk, &options)?;
+            Ok(Box::new(sequence_fn(|_| Ok(()))))
+        })
+        .unwrap_err();
+    match err.downcast::<CompilerError>() {
+        Ok(err) => err,
+        Err(err) => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_local_function_name() {
+    // The parser does not produce a local function with a field or method name, so build one from
+    // a global function statement.
+    let mut chunk = parse_chunk(&b"function t.f() end"[..]).unwrap();
+    let statement = match chunk.block.statements.pop() {
+        Some(Statement::Function(statement)) => statement,
+        _ => panic!("expected a function statement"),
+    };
+    chunk
+        .block
+        .statements
+        .push(Statement::LocalFunction(statement));
+
+    match compile_error(chunk, CompilerOptions::default()).kind {
+        CompilerErrorKind::LocalFunctionName(ref name) if name == "t" => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+}
+
+#[test]
+fn test_opcode_lines() {
+    let chunk =
+        parse_chunk(&b"local a, b = ...\n\nlocal c = a + b\n-- comment\nreturn c"[..]).unwrap();
+    let lines = Lua::new()
+        .sequence(move |mc, _| {
+            let proto = compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?;
+            let lines = (0..proto.opcodes.len())
+                .map(|i| proto.opcode_line(i))
+                .collect::<Vec<_>>();
+            Ok(Box::new(sequence_fn(move |_| Ok(lines))))
+        })
+        .unwrap();
+
+    assert_eq!(
+        lines,
+        vec![
+            Some(LineNumber(1)),
+            Some(LineNumber(3)),
+            Some(LineNumber(5)),
+            Some(LineNumber(5)),
+            Some(LineNumber(5)),
+        ]
+    );
+}
+
+#[test]
+fn test_local_variables() {
+    let chunk = parse_chunk(&b"local a = ...\ndo local b = a + 1 end\nlocal c = a"[..]).unwrap();
+    let locals = Lua::new()
+        .sequence(move |mc, _| {
+            let proto = compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?;
+            let locals = proto
+                .local_variables
+                .iter()
+                .map(|l| (l.name.as_bytes().to_vec(), l.register.0, l.start..l.end))
+                .collect::<Vec<_>>();
+            Ok(Box::new(sequence_fn(move |_| Ok(locals))))
+        })
+        .unwrap();
+
+    assert_eq!(
+        locals,
+        vec![
+            (b"a".to_vec(), 0, 1..3),
+            (b"b".to_vec(), 1, 2..2),
+            (b"c".to_vec(), 1, 3..3),
+        ]
+    );
+}
+
+#[test]
+fn test_compiler_limits() {
+    fn error_kind(source: &str, options: CompilerOptions) -> CompilerErrorKind {
+        compile_error(parse_chunk(source.as_bytes()).unwrap(), options).kind
+    }
+
+    match error_kind(
+        "local a, b, c = ...",
+        CompilerOptions::default().set_max_registers(2),
+    ) {
+        CompilerErrorKind::Registers(2) => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+
+    match error_kind(
+        "return 'a', 'b', 'c'",
+        CompilerOptions::default().set_max_constants(2),
+    ) {
+        CompilerErrorKind::Constants(2) => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+
+    match error_kind(
+        "return function() return function() end end",
+        CompilerOptions::default().set_max_function_depth(1),
+    ) {
+        CompilerErrorKind::FunctionDepth(1) => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+
+    // Two functions with 200 locals each, captured by an inner function as 400 upvalues
+    let locals = |prefix: &str| {
+        (0..200)
+            .map(|i| format!("local {}{} ", prefix, i))
+            .collect::<String>()
+    };
+    let captures = (0..200)
+        .map(|i| format!("a{} = b{} ", i, i))
+        .collect::<String>();
+    let source = format!(
+        "{} return function() {} return function() {} end end",
+        locals("a"),
+        locals("b"),
+        captures
+    );
+    match error_kind(&source, CompilerOptions::default()) {
+        CompilerErrorKind::UpValues(ref name) if name == "b128" => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+
+    assert_eq!(
+        CompilerOptions::default()
+            .set_max_constants(1 << 20)
+            .max_constants(),
+        1 << 16
+    );
+}
+
+#[test]
+fn test_strict_mode() {
+    fn compiles(source: &str, options: CompilerOptions) -> bool {
+        let chunk = parse_chunk(source.as_bytes()).unwrap();
+        Lua::new()
+            .sequence(move |mc, _| {
+                let ok = compile_chunk(mc, b"test", &chunk, &options).is_ok();
+                Ok(Box::new(sequence_fn(move |_| Ok(ok))))
+            })
+            .unwrap()
+    }
+
+    let strict = CompilerOptions::default().set_strict(true);
+    assert!(compiles("x = 1 function f() end", strict.clone()));
+    assert!(compiles("local x function f() x = 1 end", strict.clone()));
+    assert!(compiles(
+        "local t = {} function f() t.x = 1 end",
+        strict.clone()
+    ));
+    assert!(!compiles("function f() x = 1 end", strict.clone()));
+    assert!(!compiles(
+        "function f() function g() end end",
+        strict.clone()
+    ));
+    assert!(compiles(
+        "function f() x = 1 end",
+        CompilerOptions::default()
+    ));
+
+    match compile_error(
+        parse_chunk(&b"function f()\n  y = 1\nend"[..]).unwrap(),
+        strict,
+    )
+    .kind
+    {
+        CompilerErrorKind::StrictGlobal(name) => assert_eq!(name, "y"),
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+}
+
+#[test]
+fn test_warnings() {
+    fn warnings(source: &str) -> Vec<String> {
+        let chunk = parse_chunk(source.as_bytes()).unwrap();
+        Lua::new()
+            .sequence(move |mc, _| {
+                let mut warnings = Vec::new();
+                compile_chunk_with_warnings(
+                    mc,
+                    b"test",
+                    &chunk,
+                    &CompilerOptions::default(),
+                    &mut warnings,
+                )?;
+                let warnings = warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+                Ok(Box::new(sequence_fn(move |_| Ok(warnings))))
+            })
+            .unwrap()
+    }
+
+    assert_eq!(
+        warnings("local a = 1\nlocal b = 2\nreturn b"),
+        vec!["test:1: unused local 'a'"]
+    );
+    assert!(warnings("local _a = 1 for _, v in pairs({}) do return v end").is_empty());
+    assert!(warnings("local a = 1 return function() return a end").is_empty());
+    assert!(warnings("local function f() return f end return f").is_empty());
+
+    assert_eq!(
+        warnings("local x = 1\ndo\n  local x = 2\n  return x\nend\nreturn x"),
+        vec!["test:3: local 'x' shadows an earlier local"]
+    );
+    assert_eq!(
+        warnings("local x = 1\nfunction f(y)\n  local x = y\n  return x\nend\nreturn x"),
+        vec!["test:3: local 'x' shadows an earlier local"]
+    );
+
+    assert_eq!(
+        warnings("local count = 0\ncuont = count + 1"),
+        vec!["test:2: assignment to global 'cuont', did you mean local 'count'?"]
+    );
+    assert_eq!(
+        warnings("local total = 0\nfunction totals() return total end"),
+        vec!["test:2: assignment to global 'totals', did you mean local 'total'?"]
+    );
+    assert!(warnings("local ab = 0 ac = ab").is_empty());
+    assert!(warnings("local count = 0 other = count").is_empty());
+}
+
+#[test]
+fn test_strip_debug() {
+    let chunk = parse_chunk(&b"local a = ...\nreturn function(b) return a + b end"[..]).unwrap();
+    let debug_sizes = Lua::new()
+        .sequence(move |mc, _| {
+            let mut sizes = Vec::new();
+            for &strip_debug in &[false, true] {
+                let options = CompilerOptions::default().set_strip_debug(strip_debug);
+                let proto = compile_chunk(mc, b"test", &chunk, &options)?;
+                let inner = &proto.prototypes[0];
+                sizes.push((
+                    proto.opcode_lines.len() + inner.opcode_lines.len(),
+                    proto.local_variables.len() + inner.local_variables.len(),
+                ));
+            }
+            Ok(Box::new(sequence_fn(move |_| Ok(sizes))))
+        })
+        .unwrap();
+
+    assert_eq!(debug_sizes, vec![(3, 2), (0, 0)]);
+}
+
+#[test]
+fn test_constant_types() {
+    let chunk = parse_chunk(&b"x = 1\ny = 1.0\nz = 0.0\nw = -0.0\nv = 1"[..]).unwrap();
+    let constants = Lua::new()
+        .sequence(move |mc, _| {
+            let proto = compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?;
+            let constants = proto
+                .constants
+                .iter()
+                .filter_map(|c| match *c {
+                    Value::Integer(i) => Some(format!("integer {}", i)),
+                    Value::Number(n) => Some(format!("float {:?}", n)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            Ok(Box::new(sequence_fn(move |_| Ok(constants))))
+        })
+        .unwrap();
+
+    assert_eq!(
+        constants,
+        vec!["integer 1", "float 1.0", "float 0.0", "float -0.0"]
+    );
+}
+
+#[test]
+fn test_constant_propagation() {
+    let chunk =
+        parse_chunk(&b"local n = 100\nreturn function() return function() return n end end"[..])
+            .unwrap();
+    let upvalues = Lua::new()
+        .sequence(move |mc, _| {
+            let mut upvalues = Vec::new();
+            for &level in &[OptimizationLevel::O2, OptimizationLevel::O1] {
+                let options = CompilerOptions::default().set_optimization_level(level);
+                let proto = compile_chunk(mc, b"test", &chunk, &options)?;
+                let inner = &proto.prototypes[0];
+                upvalues.push((inner.upvalues.len(), inner.prototypes[0].upvalues.len()));
+            }
+            Ok(Box::new(sequence_fn(move |_| Ok(upvalues))))
+        })
+        .unwrap();
+
+    assert_eq!(upvalues, vec![(0, 0), (1, 1)]);
+}
+
+#[test]
+fn test_move_coalescing() {
+    let chunk = parse_chunk(&b"local a, b = ...\na = a + b\nb = -a\nreturn a, b"[..]).unwrap();
+    let moves = Lua::new()
+        .sequence(move |mc, _| {
+            let mut moves = Vec::new();
+            for &level in &[OptimizationLevel::O1, OptimizationLevel::O0] {
+                let options = CompilerOptions::default().set_optimization_level(level);
+                let proto = compile_chunk(mc, b"test", &chunk, &options)?;
+                moves.push(
+                    proto
+                        .opcodes
+                        .iter()
+                        .filter(|op| match op {
+                            OpCode::Move { .. } => true,
+                            _ => false,
+                        })
+                        .count(),
+                );
+            }
+            Ok(Box::new(sequence_fn(move |_| Ok(moves))))
+        })
+        .unwrap();
+
+    // Only the moves of the returned values remain
+    assert_eq!(moves, vec![2, 4]);
+}
+
+#[test]
+fn test_optimization_levels() {
+    let chunk = parse_chunk(
+        &br#"
+            local a = ...
+            if a == 1 then a = 2 elseif a == 2 then a = 3 elseif a == 3 then a = 1 end
+        "#[..],
+    )
+    .unwrap();
+    let switches = Lua::new()
+        .sequence(move |mc, _| {
+            let mut switches = Vec::new();
+            for &level in &[OptimizationLevel::O1, OptimizationLevel::O0] {
+                let options = CompilerOptions::default().set_optimization_level(level);
+                let proto = compile_chunk(mc, b"test", &chunk, &options)?;
+                switches.push(
+                    proto
+                        .opcodes
+                        .iter()
+                        .filter(|op| match op {
+                            OpCode::Switch { .. } => true,
+                            _ => false,
+                        })
+                        .count(),
+                );
+            }
+            Ok(Box::new(sequence_fn(move |_| Ok(switches))))
+        })
+        .unwrap();
+
+    assert_eq!(switches, vec![1, 0]);
+    assert!(OptimizationLevel::O0 < OptimizationLevel::O1);
+    assert_eq!(
+        CompilerOptions::default().optimization_level(),
+        OptimizationLevel::O2
+    );
+}
+
+#[test]
+fn test_recursion_limit() {
+    // The parser limits nesting itself, so build an expression nested more deeply than it allows
+    let mut chunk = parse_chunk(&b"return 1"[..]).unwrap();
+    let returns = &mut chunk.block.return_statement.as_mut().unwrap().returns;
+    for _ in 0..1000 {
+        let expression = returns.pop().unwrap();
+        returns.push(Expression {
+            head: Box::new(HeadExpression::UnaryOperator(
+                UnaryOperator::Minus,
+                expression,
+            )),
+            tail: Vec::new(),
+        });
+    }
+
+    match compile_error(chunk, CompilerOptions::default()).kind {
+        CompilerErrorKind::RecursionLimit(_) => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+}
+
+#[test]
+fn test_global_cache() {
+    fn global_reads(source: &str, level: OptimizationLevel) -> usize {
+        let chunk = parse_chunk(source.as_bytes()).unwrap();
+        Lua::new()
+            .sequence(move |mc, _| {
+                let options = CompilerOptions::default().set_optimization_level(level);
+                let proto = compile_chunk(mc, b"test", &chunk, &options)?;
+                let reads = proto
+                    .opcodes
+                    .iter()
+                    .filter(|op| match op {
+                        OpCode::GetUpTableC { .. } => true,
+                        _ => false,
+                    })
+                    .count();
+                Ok(Box::new(sequence_fn(move |_| Ok(reads))))
+            })
+            .unwrap()
+    }
+
+    assert_eq!(
+        global_reads("x = t.a + t.b + t.a", OptimizationLevel::O2),
+        1
+    );
+    assert_eq!(
+        global_reads("x = t.a + t.b + t.a", OptimizationLevel::O1),
+        3
+    );
+    // Calls and stores may change any global
+    assert_eq!(
+        global_reads("x = t.a + f() + t.a", OptimizationLevel::O2),
+        3
+    );
+    assert_eq!(
+        global_reads("t.a = t.b t.c = t.d", OptimizationLevel::O2),
+        2
+    );
+    // A read inside a short circuit operand may not happen, so it does not load the cache
+    assert_eq!(
+        global_reads("local a, b = y and t.a, t.b", OptimizationLevel::O2),
+        3
+    );
+    assert_eq!(
+        global_reads("local t x = t.a + t.b", OptimizationLevel::O2),
+        0
+    );
+}
+
+#[test]
+fn test_long_jumps() {
+    // Every jump over or back across this body is too long for a single jump offset
+    let body = "x = x + 1\n".repeat(40000);
+    let source = format!(
+        r#"
+            local x = 0
+            if x == 0 then {body} else x = nil end
+            local n = 0
+            while n < 2 do n = n + 1 {body} end
+            for i = 1, 3 do if i == 3 then break end {body} end
+            for i = 1.0, 2 do {body} end
+            for i = 2, 1 do {body} end
+            local function iter(_, i) if i < 2 then return i + 1, function() return i end end end
+            for i, f in iter, nil, 0 do {body} end
+            n = 0
+            ::top::
+            n = n + 1
+            if n < 3 then {body} goto top end
+            return x
+        "#,
+        body = body
+    );
+    let chunk = parse_chunk(source.as_bytes()).unwrap();
+
+    let result = Lua::new()
+        .sequence(move |mc, lc| {
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(
+                            mc,
+                            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+                            Some(lc.globals),
+                        )?,
+                        &[],
+                        64,
+                    )
+                    .map(|_, r| Ok(r == [Value::Integer(40000 * 11)])),
+            ))
+        })
+        .unwrap();
+    assert!(result);
+}
+
+#[test]
+fn test_compile() {
+    let (same_opcodes, parse_error, compile_error) = Lua::new()
+        .sequence(|mc, _| {
+            let source = "local a = ...\nreturn a";
+            let proto = compile(mc, b"test", source.as_bytes())?;
+            let chunk = parse_chunk(source.as_bytes())?;
+            let expected = compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?;
+            let same_opcodes = format!("{:?}", proto.opcodes) == format!("{:?}", expected.opcodes);
+
+            let parse_error = match compile(mc, b"test", &b"local = 1"[..]) {
+                Err(err @ CompileError::Parse { .. }) => err.to_string(),
+                res => panic!("unexpected result {:?}", res.map(|_| ())),
+            };
+            let compile_error = match compile(mc, b"test", &b"\ngoto done"[..]) {
+                Err(err @ CompileError::Compiler(_)) => err.to_string(),
+                res => panic!("unexpected result {:?}", res.map(|_| ())),
+            };
+            Ok(Box::new(sequence_fn(move |_| {
+                Ok((same_opcodes, parse_error, compile_error))
+            })))
+        })
+        .unwrap();
+
+    assert!(same_opcodes);
+    assert_eq!(parse_error, "test: expected name found Assign");
+    assert_eq!(compile_error, "test:2: no visible label 'done' for goto");
+}
+
+#[test]
+fn test_compile_function() {
+    let chunk = parse_chunk(&b"return request .. response, ..."[..]).unwrap();
+    let returned = Lua::new()
+        .sequence(move |mc, lc| {
+            let proto = compile_function(
+                mc,
+                b"test",
+                &[b"request", b"response", b"unused"],
+                true,
+                &chunk,
+                &CompilerOptions::default(),
+            )?;
+            assert_eq!(proto.fixed_params, 3);
+            assert!(proto.has_varargs);
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(mc, proto, Some(lc.globals))?,
+                        &[
+                            Value::String(luster::string::String::new(mc, b"a")),
+                            Value::String(luster::string::String::new(mc, b"b")),
+                            Value::Nil,
+                            Value::Integer(7),
+                        ],
+                        64,
+                    )
+                    .map(|_, r| match &r[..] {
+                        [Value::String(s), Value::Integer(7)] => Ok(s.as_bytes() == b"ab"),
+                        _ => Ok(false),
+                    }),
+            ))
+        })
+        .unwrap();
+    assert!(returned);
+
+    let chunk = parse_chunk(&b"return ..."[..]).unwrap();
+    let err = Lua::new()
+        .sequence(move |mc, _| {
+            compile_function(mc, b"test", &[], false, &chunk, &CompilerOptions::default())?;
+            Ok(Box::new(sequence_fn(|_| Ok(()))))
+        })
+        .unwrap_err();
+    match err.downcast::<CompilerError>().unwrap().kind {
+        CompilerErrorKind::VarArgs => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+    match compile_error(
+        parse_chunk(&b"local function f() return ... end"[..]).unwrap(),
+        CompilerOptions::default(),
+    )
+    .kind
+    {
+        CompilerErrorKind::VarArgs => {}
+        err => panic!("unexpected compiler error {:?}", err),
+    }
+}
@@ -0,0 +1,105 @@
+use luster::compiler::{compile_chunk, CompilerError, CompilerOptions};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::parser::parse_chunk;
+use luster::sequence::SequenceExt;
+use luster::value::Value;
+
+fn run(lua: &mut Lua, source: &[u8]) -> Result<Vec<i64>, String> {
+    let chunk = parse_chunk(source).unwrap();
+    lua.sequence(move |mc, lc| {
+        Ok(Box::new(
+            lc.main_thread
+                .call_function(
+                    mc,
+                    Closure::new(
+                        mc,
+                        compile_chunk(mc, b"=stdin", &chunk, &CompilerOptions::default())?,
+                        Some(lc.globals),
+                    )?,
+                    &[],
+                    lc.granularity,
+                )
+                .map(|_, r| {
+                    Ok(r.iter()
+                        .map(|v| match v {
+                            Value::Integer(i) => *i,
+                            v => panic!("unexpected return value {:?}", v),
+                        })
+                        .collect())
+                }),
+        ))
+    })
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn test_compiler_error_location() {
+    let err = run(&mut Lua::new(), b"local x = 1\n::top::\n::top::").unwrap_err();
+    assert_eq!(err, "=stdin:3: label 'top' defined multiple times");
+
+    let chunk = parse_chunk(&b"\n\n::top::\n::top::"[..]).unwrap();
+    let err = Lua::new()
+        .sequence(move |mc, _| {
+            compile_chunk(mc, b"labels", &chunk, &CompilerOptions::default())?;
+            Ok(Box::new(luster::sequence::sequence_fn(|_| Ok(()))))
+        })
+        .unwrap_err()
+        .downcast::<CompilerError>()
+        .unwrap();
+    assert_eq!(err.chunk_name, "labels");
+    assert_eq!(err.line.map(|l| l.0), Some(4));
+}
+
+#[test]
+fn test_runtime_error_location() {
+    let mut lua = Lua::new();
+    assert_eq!(
+        run(&mut lua, b"local t = {}\n\nreturn t + 1").unwrap_err(),
+        "=stdin:3: could not apply binary operator"
+    );
+    assert_eq!(
+        run(
+            &mut lua,
+            b"local g\nlocal function f()\n  return g()\nend\nreturn f()"
+        )
+        .unwrap_err(),
+        "=stdin:3: attempt to call a non-function value"
+    );
+    assert_eq!(
+        run(&mut lua, b"local t\nt.x = 1").unwrap_err(),
+        "=stdin:2: attempt to index a non-table value"
+    );
+
+    // The main thread is usable again after an error
+    assert_eq!(run(&mut lua, b"return 1 + 2"), Ok(vec![3]));
+}
+
+#[test]
+fn test_goto_errors() {
+    let mut lua = Lua::new();
+    assert_eq!(
+        run(
+            &mut lua,
+            b"local a = 1\ngoto x\nlocal y = 1\n::x::\nreturn y"
+        )
+        .unwrap_err(),
+        "=stdin:2: goto 'x' jumps into the scope of local 'y'"
+    );
+    assert_eq!(
+        run(
+            &mut lua,
+            b"local function f()\n  goto nowhere\nend\nreturn f"
+        )
+        .unwrap_err(),
+        "=stdin:2: no visible label 'nowhere' for goto"
+    );
+    assert_eq!(
+        run(&mut lua, b"do goto inner end\ndo ::inner:: end").unwrap_err(),
+        "=stdin:1: no visible label 'inner' for goto"
+    );
+    assert_eq!(
+        run(&mut lua, b"local a\n\nbreak").unwrap_err(),
+        "=stdin:3: break outside of a loop"
+    );
+}
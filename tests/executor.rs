@@ -0,0 +1,154 @@
+use luster::compiler::{compile_chunk, CompilerOptions};
+use luster::function::Closure;
+use luster::lua::{Lua, Step, VmOptions};
+use luster::parser::parse_chunk;
+use luster::sequence::SequenceExt;
+use luster::value::Value;
+
+#[test]
+fn test_stepped_execution() {
+    let mut lua = Lua::with_options(VmOptions::default().set_granularity(1));
+    let chunk = parse_chunk(
+        &br#"
+            local s = 0
+            for i = 1, 1000 do
+                s = s + i
+            end
+            return s
+        "#[..],
+    )
+    .unwrap();
+
+    let mut executor = lua
+        .executor(|mc, lc| {
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(
+                            mc,
+                            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+                            Some(lc.globals),
+                        )?,
+                        &[],
+                        lc.granularity,
+                    )
+                    .map(|_, r| Ok(r == [Value::Integer(500500)])),
+            ))
+        })
+        .unwrap();
+
+    let mut slices = 0;
+    loop {
+        match executor.step(10) {
+            Step::Done(r) => {
+                assert!(r);
+                break;
+            }
+            Step::Pending => slices += 1,
+            Step::Error(e) => panic!("error stepping executor: {}", e),
+        }
+    }
+    assert!(executor.is_finished());
+    assert!(slices > 10);
+}
+
+#[test]
+fn test_instruction_budget() {
+    let mut lua = Lua::new();
+    let chunk = parse_chunk(
+        &br#"
+            local s = 0
+            for i = 1, 1000 do
+                s = s + i
+            end
+            return s
+        "#[..],
+    )
+    .unwrap();
+
+    let mut executor = lua
+        .executor(|mc, lc| {
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(
+                            mc,
+                            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+                            Some(lc.globals),
+                        )?,
+                        &[],
+                        lc.granularity,
+                    )
+                    .map(|_, r| Ok(r == [Value::Integer(500500)])),
+            ))
+        })
+        .unwrap();
+
+    // Each loop iteration executes at least two instructions, so ten instructions at a time
+    // cannot finish in fewer than 200 slices, even though the granularity is larger than ten.
+    let mut slices = 0;
+    loop {
+        match executor.step(10) {
+            Step::Done(r) => {
+                assert!(r);
+                break;
+            }
+            Step::Pending => slices += 1,
+            Step::Error(e) => panic!("error stepping executor: {}", e),
+        }
+    }
+    assert!(slices >= 200);
+}
+
+#[test]
+fn test_abandoned_executor() {
+    let mut lua = Lua::with_options(VmOptions::default().set_granularity(1));
+    let chunk = parse_chunk(&b"while true do end"[..]).unwrap();
+
+    let mut executor = lua
+        .executor(|mc, lc| {
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(
+                            mc,
+                            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+                            Some(lc.globals),
+                        )?,
+                        &[],
+                        lc.granularity,
+                    )
+                    .map(|_, _| Ok(())),
+            ))
+        })
+        .unwrap();
+    match executor.step(100) {
+        Step::Pending => {}
+        _ => panic!("infinite loop finished"),
+    }
+    drop(executor);
+
+    let chunk = parse_chunk(&b"return 1 + 1"[..]).unwrap();
+    let results = lua
+        .sequence(|mc, lc| {
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(
+                            mc,
+                            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+                            Some(lc.globals),
+                        )?,
+                        &[],
+                        lc.granularity,
+                    )
+                    .map(|_, r| Ok(r == [Value::Integer(2)])),
+            ))
+        })
+        .unwrap();
+    assert!(results);
+}
@@ -0,0 +1,119 @@
+use luster::compiler::{compile_expression, evaluate_constant, CompilerOptions, ConstantError};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::parser::parse_expression;
+use luster::sequence::{sequence_fn, SequenceExt};
+use luster::string::String;
+use luster::value::Value;
+
+#[test]
+fn test_compile_expression() {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, lc| {
+        lc.globals
+            .set(mc, Value::String(String::Static(b"x")), Value::Integer(4))?;
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+
+    let expression = parse_expression("x + 3 == 7".as_bytes()).unwrap();
+    let results = lua
+        .sequence(move |mc, lc| {
+            let proto = compile_expression(mc, b"test", &expression, &CompilerOptions::default())?;
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(mc, Closure::new(mc, proto, Some(lc.globals))?, &[], 64)
+                    .map(|_, r| Ok(r == [Value::Boolean(true)])),
+            ))
+        })
+        .unwrap();
+    assert!(results);
+}
+
+#[test]
+fn test_compile_multiple_results() {
+    let mut lua = Lua::new();
+    let expression = parse_expression("(function() return 1, 2, 3 end)()".as_bytes()).unwrap();
+    let results = lua
+        .sequence(move |mc, lc| {
+            let proto = compile_expression(mc, b"test", &expression, &CompilerOptions::default())?;
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(mc, Closure::new(mc, proto, Some(lc.globals))?, &[], 64)
+                    .map(|_, r| Ok(r == [Value::Integer(1), Value::Integer(2), Value::Integer(3)])),
+            ))
+        })
+        .unwrap();
+    assert!(results);
+}
+
+#[test]
+fn test_evaluate_constant() {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, _| {
+        let evaluate =
+            |source: &str| evaluate_constant(mc, &parse_expression(source.as_bytes()).unwrap());
+
+        assert_eq!(evaluate("1 + 2 + 0.5").unwrap(), Value::Number(3.5));
+        assert_eq!(evaluate("(1 + 2) == 3").unwrap(), Value::Boolean(true));
+        assert_eq!(
+            evaluate("1 < 2 and 2 <= 2.0").unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(evaluate("\"b\" > \"a\"").unwrap(), Value::Boolean(true));
+        assert_eq!(evaluate("1 ~= 1.0").unwrap(), Value::Boolean(false));
+        assert_eq!(evaluate("not nil").unwrap(), Value::Boolean(true));
+        assert_eq!(
+            evaluate("nil or \"default\"").unwrap(),
+            Value::String(String::new(mc, b"default"))
+        );
+        assert_eq!(evaluate("false and 1").unwrap(), Value::Boolean(false));
+        assert_eq!(
+            evaluate("\"a\" .. 1 .. 2.5").unwrap(),
+            Value::String(String::new(mc, b"a12.5"))
+        );
+        assert_eq!(evaluate("12 & 10 | 1 << 4").unwrap(), Value::Integer(24));
+        assert_eq!(evaluate("3.0 ~ 1").unwrap(), Value::Integer(2));
+        assert_eq!(evaluate("~5").unwrap(), Value::Integer(-6));
+        assert_eq!(evaluate("7 // 2 * 3 - 1").unwrap(), Value::Integer(8));
+        assert_eq!(evaluate("-7 % 3").unwrap(), Value::Integer(2));
+        assert_eq!(evaluate("1 / 2 + 2 ^ 2").unwrap(), Value::Number(4.5));
+        assert_eq!(
+            evaluate("9223372036854775807 + 1").unwrap(),
+            Value::Integer(-9223372036854775807 - 1)
+        );
+        assert_eq!(evaluate("-~2.0").unwrap(), Value::Integer(3));
+
+        match evaluate("x + 1") {
+            Err(ConstantError::NotConstant) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match evaluate("true or f()") {
+            Err(ConstantError::NotConstant) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match evaluate("1 + true") {
+            Err(ConstantError::Operator) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match evaluate("1 < \"2\"") {
+            Err(ConstantError::Operator) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match evaluate("1 // 0") {
+            Err(ConstantError::Operator) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match evaluate("~1.5") {
+            Err(ConstantError::Operator) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match evaluate("1.5 & 1") {
+            Err(ConstantError::Operator) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+}
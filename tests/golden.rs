@@ -0,0 +1,80 @@
+use std::env;
+use std::fs::{read_dir, File};
+use std::io::{stdout, Read, Write};
+use std::path::Path;
+
+use luster::compiler::{compile_chunk, CompilerOptions};
+use luster::disassemble::disassemble;
+use luster::io::buffered_read;
+use luster::lua::Lua;
+use luster::parser::parse_chunk;
+use luster::sequence::sequence_fn;
+
+// Set this environment variable to rewrite the golden files from the current compiler output,
+// rather than checking against them.
+const REGENERATE_VAR: &str = "LUSTER_REGENERATE_GOLDEN";
+
+fn disassemble_file(path: &Path) -> String {
+    let chunk = parse_chunk(buffered_read(File::open(path).unwrap()).unwrap()).unwrap();
+    let name = path.to_string_lossy().into_owned();
+    Lua::new()
+        .sequence(move |mc, _| {
+            let listing = disassemble(&compile_chunk(
+                mc,
+                name.as_bytes(),
+                &chunk,
+                &CompilerOptions::default(),
+            )?);
+            Ok(Box::new(sequence_fn(move |_| Ok(listing))))
+        })
+        .unwrap()
+}
+
+#[test]
+fn test_golden_bytecode() {
+    let regenerate = env::var_os(REGENERATE_VAR).is_some();
+    let mut file_failed = false;
+
+    for entry in read_dir("./tests/golden").expect("could not list dir contents") {
+        let path = entry.expect("could not read dir entry").path();
+        if path.extension().map(|e| e != "lua").unwrap_or(true) {
+            continue;
+        }
+
+        let listing = disassemble_file(&path);
+        let golden_path = path.with_extension("bytecode");
+
+        if regenerate {
+            let _ = writeln!(stdout(), "regenerating {:?}", golden_path);
+            File::create(&golden_path)
+                .and_then(|mut f| f.write_all(listing.as_bytes()))
+                .expect("could not write golden file");
+        } else {
+            let mut golden = String::new();
+            match File::open(&golden_path).and_then(|mut f| f.read_to_string(&mut golden)) {
+                Ok(_) if golden == listing => {}
+                Ok(_) => {
+                    let _ = writeln!(
+                        stdout(),
+                        "bytecode for {:?} does not match {:?}, compiler output was:\n{}",
+                        path,
+                        golden_path,
+                        listing
+                    );
+                    file_failed = true;
+                }
+                Err(err) => {
+                    let _ = writeln!(stdout(), "could not read {:?}: {}", golden_path, err);
+                    file_failed = true;
+                }
+            }
+        }
+    }
+
+    if file_failed {
+        panic!(
+            "one or more golden files did not match, set {} to regenerate them",
+            REGENERATE_VAR
+        );
+    }
+}
@@ -0,0 +1,9 @@
+local count = 0
+local function inc(step)
+    count = count + step
+    return count
+end
+function global_inc()
+    return inc(1)
+end
+return inc
@@ -0,0 +1,4 @@
+local a, b = ...
+if a < b then
+    return a > 1, a >= b, 2 <= b, 1 < 2
+end
@@ -0,0 +1,6 @@
+local a, b = "a", "b"
+local s = a .. b .. "c"
+local function f()
+    return "f"
+end
+return a .. f() .. 1 + 2 .. b, (a .. b) .. (s .. a)
@@ -0,0 +1,10 @@
+local a = ...
+if 1 < 2 then
+    a = 1
+else
+    a = 2
+end
+if 2 ~= 2 then
+    a = 3
+end
+return a
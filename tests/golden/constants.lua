@@ -0,0 +1,6 @@
+local debug = false
+local name = "luster\n"
+x = name
+if not debug then
+    y = 1.5 + 1
+end
@@ -0,0 +1,5 @@
+local function f(...)
+    return {...}
+end
+local t = {1, 2, x = 3, ["y"] = 4; f(5, 6)}
+return {}, {t, f()}, {f(), 7}
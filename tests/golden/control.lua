@@ -0,0 +1,11 @@
+local n = 0
+for i = 1, 10 do
+    if i == 5 then
+        break
+    end
+    n = n + i
+end
+while n == 10 do
+    n = n + 1
+end
+return n
@@ -0,0 +1,12 @@
+local t = ...
+while t do
+    break
+    t = t + 1
+end
+goto skip
+t = 2
+::skip::
+do
+    return t
+end
+t = 3
@@ -0,0 +1,2 @@
+local a = ...
+return 1 + 2 * 3, 7 // 2, 1 / 2, 2 ^ 10, 5 % -3, 1 << 4 | 1, a * (2 - 1), 1 // 0, 1 % 0, 1.5 | 0
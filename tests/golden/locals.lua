@@ -0,0 +1,5 @@
+local a, b = 1, 2
+local c = a + b
+local d
+d = c + 1
+return d
@@ -0,0 +1,4 @@
+local t = ...
+t:m(1, ...)
+local a = t.inner:get()
+return t:m()
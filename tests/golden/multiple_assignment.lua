@@ -0,0 +1,3 @@
+local a, b, t
+a, b = b, a
+a, t[a], b = f()
@@ -0,0 +1,29 @@
+local x, r = ...
+if x == 1 then
+    r = "one"
+elseif x == "two" then
+    r = "two"
+elseif 3 == x then
+    r = "three"
+elseif x == 1 then
+    r = "unreachable"
+else
+    r = "other"
+end
+
+-- Too few cases
+if x == 1 then
+    r = 1
+elseif x == 2 then
+    r = 2
+end
+
+-- Not the same local in every condition
+if x == 1 then
+    r = 1
+elseif r == 2 then
+    r = 2
+elseif x == 3 then
+    r = 3
+end
+return r
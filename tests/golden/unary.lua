@@ -0,0 +1,2 @@
+local a = ...
+return -a, -5, -(1 + 2), not a, -2 ^ 2
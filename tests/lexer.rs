@@ -1,5 +1,6 @@
 use std::f64;
 
+use luster::interner::{Interner, Symbol};
 use luster::lexer::{Lexer, Token};
 
 fn test_tokens(source: &str, tokens: &[Token]) {
@@ -35,10 +36,6 @@ fn str_token(s: &str) -> Token {
     Token::String(s.as_bytes().to_vec().into_boxed_slice())
 }
 
-fn name_token(s: &str) -> Token {
-    Token::Name(s.as_bytes().to_vec().into_boxed_slice())
-}
-
 #[test]
 fn comments() {
     test_tokens_lines(
@@ -115,6 +112,9 @@ fn numerals() {
             0x99999999999999999999999999999999p999999999999999999999999999999
             9223372036854775807
             9223372036854775808
+            0x7fffffffffffffff
+            0xffffffffffffffff
+            0x10000000000000001
         "#,
         &[
             Token::Integer(0xdeadbeef),
@@ -128,12 +128,18 @@ fn numerals() {
             Token::Float(f64::INFINITY),
             Token::Integer(9223372036854775807),
             Token::Float(9223372036854775808.0),
+            Token::Integer(9223372036854775807),
+            Token::Integer(-1),
+            Token::Integer(1),
         ],
     );
 }
 
 #[test]
 fn words() {
+    // The lexer interns names in the order they are read, so a fresh interner given the same names
+    // produces the same symbols.
+    let mut interner = Interner::new();
     test_tokens(
         r#"
             break do else elseif end function goto if in local nil for while repeat until return
@@ -163,12 +169,32 @@ fn words() {
             Token::Not,
             Token::And,
             Token::Or,
-            name_token("custom"),
-            name_token("names"),
+            Token::Name(interner.intern(b"custom")),
+            Token::Name(interner.intern(b"names")),
         ],
     );
 }
 
+#[test]
+fn interned_names() {
+    let mut lexer = Lexer::new(&b"foo bar foo self _ENV"[..]);
+    let mut names = Vec::new();
+    while let Some(token) = lexer.read_token().unwrap() {
+        match token {
+            Token::Name(name) => names.push(name),
+            token => panic!("unexpected token {:?}", token),
+        }
+    }
+
+    assert_eq!(names[0], names[2]);
+    assert_ne!(names[0], names[1]);
+    assert_eq!(names[3], Symbol::SELF);
+    assert_eq!(names[4], Symbol::ENV);
+    assert_eq!(lexer.interner().name(names[1]), b"bar");
+    assert_eq!(lexer.interner().get(b"foo"), Some(names[0]));
+    assert_eq!(lexer.interner().get(b"baz"), None);
+}
+
 #[test]
 fn ops() {
     test_tokens(
@@ -0,0 +1,9 @@
+use std::mem;
+
+use luster::opcode::OpCode;
+
+#[test]
+fn test_opcode_size() {
+    // Every opcode packs into a single 32-bit word, keep it that way.
+    assert_eq!(mem::size_of::<OpCode>(), 4);
+}
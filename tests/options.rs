@@ -0,0 +1,71 @@
+use gc_arena::ArenaParameters;
+
+use luster::compiler::{compile_chunk, CompilerOptions};
+use luster::function::Closure;
+use luster::lua::{Lua, VmOptions};
+use luster::parser::parse_chunk;
+use luster::sequence::{sequence_fn, SequenceExt};
+use luster::value::Value;
+
+#[test]
+fn test_vm_options() {
+    let mut lua = Lua::with_options(
+        VmOptions::default()
+            .set_arena_parameters(ArenaParameters::default().set_min_sleep(0))
+            .set_granularity(1),
+    );
+    assert_eq!(lua.options().granularity(), 1);
+    assert_eq!(
+        lua.sequence(|_, lc| {
+            let granularity = lc.granularity;
+            Ok(Box::new(sequence_fn(move |_| Ok(granularity))))
+        })
+        .unwrap(),
+        1
+    );
+
+    let chunk = parse_chunk(
+        &br#"
+            local t = {}
+            local s = 0
+            for i = 1, 100 do
+                t[i] = {}
+                s = s + i
+            end
+            return s
+        "#[..],
+    )
+    .unwrap();
+    let results = lua
+        .sequence(|mc, lc| {
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(
+                            mc,
+                            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+                            Some(lc.globals),
+                        )?,
+                        &[],
+                        lc.granularity,
+                    )
+                    .map(|_, r| Ok(r == [Value::Integer(5050)])),
+            ))
+        })
+        .unwrap();
+    assert!(results);
+
+    lua.set_granularity(256);
+    assert_eq!(
+        lua.sequence(|_, lc| {
+            let granularity = lc.granularity;
+            Ok(Box::new(sequence_fn(move |_| Ok(granularity))))
+        })
+        .unwrap(),
+        256
+    );
+
+    lua.set_granularity(0);
+    assert_eq!(lua.options().granularity(), 1);
+}
@@ -1,11 +1,15 @@
+use luster::interner::Interner;
 use luster::parser::{
-    parse_chunk, Block, CallSuffix, Chunk, ConstructorField, Expression, FunctionCallStatement,
-    HeadExpression, PrimaryExpression, SimpleExpression, Statement, SuffixedExpression,
-    TableConstructor,
+    is_unexpected_end, parse_chunk, parse_expression, BinaryOperator, Block, CallSuffix, Chunk,
+    ConstructorField, Expression, FunctionCallStatement, HeadExpression, PrimaryExpression,
+    SimpleExpression, Statement, SuffixedExpression, TableConstructor,
 };
+use luster::types::LineNumber;
 
 #[test]
 fn test_function_call() {
+    let mut interner = Interner::new();
+    let print = interner.intern(b"print");
     assert_eq!(
         parse_chunk("print(10, 20);print'foo';print{30.0}".as_bytes()).unwrap(),
         Chunk {
@@ -13,9 +17,7 @@ fn test_function_call() {
                 statements: vec![
                     Statement::FunctionCall(FunctionCallStatement {
                         head: SuffixedExpression {
-                            primary: PrimaryExpression::Name(
-                                "print".as_bytes().to_vec().into_boxed_slice(),
-                            ),
+                            primary: PrimaryExpression::Name(print),
                             suffixes: vec![],
                         },
                         call: CallSuffix::Function(vec![
@@ -35,9 +37,7 @@ fn test_function_call() {
                     }),
                     Statement::FunctionCall(FunctionCallStatement {
                         head: SuffixedExpression {
-                            primary: PrimaryExpression::Name(
-                                "print".as_bytes().to_vec().into_boxed_slice(),
-                            ),
+                            primary: PrimaryExpression::Name(print),
                             suffixes: vec![],
                         },
                         call: CallSuffix::Function(vec![Expression {
@@ -49,9 +49,7 @@ fn test_function_call() {
                     }),
                     Statement::FunctionCall(FunctionCallStatement {
                         head: SuffixedExpression {
-                            primary: PrimaryExpression::Name(
-                                "print".as_bytes().to_vec().into_boxed_slice(),
-                            ),
+                            primary: PrimaryExpression::Name(print),
                             suffixes: vec![],
                         },
                         call: CallSuffix::Function(vec![Expression {
@@ -69,8 +67,51 @@ fn test_function_call() {
                         },]),
                     }),
                 ],
+                lines: vec![LineNumber(1), LineNumber(1), LineNumber(1)],
                 return_statement: None,
             },
+            interner,
         }
     );
 }
+
+#[test]
+fn test_standalone_expression() {
+    assert_eq!(
+        parse_expression("1 + 2".as_bytes()).unwrap().expression,
+        Expression {
+            head: Box::new(HeadExpression::Simple(SimpleExpression::Integer(1))),
+            tail: vec![(
+                BinaryOperator::Add,
+                Expression {
+                    head: Box::new(HeadExpression::Simple(SimpleExpression::Integer(2))),
+                    tail: vec![],
+                }
+            )],
+        }
+    );
+
+    assert!(parse_expression("1 + 2 x = 3".as_bytes()).is_err());
+    assert!(parse_expression("local x = 3".as_bytes()).is_err());
+}
+
+#[test]
+fn test_unexpected_end() {
+    for source in &["if x then", "f(1,", "local", "x = [[long", "--[==[ comment"] {
+        assert!(is_unexpected_end(
+            &parse_chunk(source.as_bytes()).unwrap_err()
+        ));
+    }
+    for source in &["if x end", "f(1,)", "x = 'short"] {
+        assert!(!is_unexpected_end(
+            &parse_chunk(source.as_bytes()).unwrap_err()
+        ));
+    }
+}
+
+#[test]
+fn test_local_function_name() {
+    assert!(parse_chunk("local function f() end".as_bytes()).is_ok());
+    assert!(parse_chunk("local function a.b() end".as_bytes()).is_err());
+    assert!(parse_chunk("local function a:b() end".as_bytes()).is_err());
+}
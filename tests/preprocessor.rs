@@ -0,0 +1,67 @@
+use std::io::Read;
+
+use luster::parser::parse_chunk;
+use luster::preprocessor::Preprocessor;
+
+fn preprocess(source: &str, flags: &[&str]) -> Result<String, std::io::Error> {
+    let mut output = String::new();
+    Preprocessor::new(source.as_bytes(), flags).read_to_string(&mut output)?;
+    Ok(output)
+}
+
+#[test]
+fn test_conditional_sections() {
+    let source = r#"a = 1
+--@if DEBUG
+b = 2
+  --@if not VERBOSE
+c = 3
+  --@else
+d = 4
+  --@end
+--@else
+e = 5
+--@end
+f = 6"#;
+
+    assert_eq!(
+        preprocess(source, &[]).unwrap(),
+        "a = 1\n\n\n\n\n\n\n\n\ne = 5\n\nf = 6"
+    );
+    assert_eq!(
+        preprocess(source, &["DEBUG"]).unwrap(),
+        "a = 1\n\nb = 2\n\nc = 3\n\n\n\n\n\n\nf = 6"
+    );
+    assert_eq!(
+        preprocess(source, &["DEBUG", "VERBOSE"]).unwrap(),
+        "a = 1\n\nb = 2\n\n\n\nd = 4\n\n\n\n\nf = 6"
+    );
+}
+
+#[test]
+fn test_invalid_directives() {
+    assert!(preprocess("--@if DEBUG\nprint(1)", &[]).is_err());
+    assert!(preprocess("--@end\n", &[]).is_err());
+    assert!(preprocess("--@if A\n--@else\n--@else\n--@end\n", &[]).is_err());
+    assert!(preprocess("--@if\n--@end\n", &[]).is_err());
+    assert!(preprocess("--@if A B\n--@end\n", &[]).is_err());
+}
+
+#[test]
+fn test_ordinary_comments() {
+    assert_eq!(
+        preprocess("--@param x\n--@todo\nreturn x\n", &[]).unwrap(),
+        "--@param x\n--@todo\nreturn x\n"
+    );
+    assert_eq!(
+        preprocess("--@if A\n--@ifdef B\n--@end\n", &[]).unwrap(),
+        "\n\n\n"
+    );
+}
+
+#[test]
+fn test_parse_preprocessed() {
+    let source = "--@if DEBUG\nprint(\n--@end\n";
+    assert!(parse_chunk(source.as_bytes()).is_err());
+    assert!(parse_chunk(Preprocessor::new(source.as_bytes(), &[] as &[&str])).is_ok());
+}
@@ -0,0 +1,40 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Runs the REPL binary with the given input, returning everything it printed to stdout
+fn run_repl(input: &str) -> String {
+    let mut repl = Command::new(env!("CARGO_BIN_EXE_repl"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    repl.stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = repl.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_continued_expression() {
+    let output = run_repl("1 +\n2\n");
+    assert!(
+        output.contains("[Integer(3)]"),
+        "unexpected output {:?}",
+        output
+    );
+}
+
+#[test]
+fn test_continued_statement() {
+    let output = run_repl("local function f()\nreturn 4 end\nf()\n");
+    assert!(
+        output.contains("[Integer(4)]"),
+        "unexpected output {:?}",
+        output
+    );
+}
@@ -0,0 +1,18 @@
+local a = 7
+local b = 2
+local n = 0 - 7
+local f = 1.5
+
+local function is_int(x)
+    return x .. "" == (x // 1) .. ""
+end
+
+return
+    a - b == 5 and a * b == 14 and a / b == 3.5 and a // b == 3 and a % b == 1 and
+    n // b == 0 - 4 and n % b == 1 and a % (0 - 2) == 0 - 1 and n % (0 - 2) == 0 - 1 and
+    a ^ b == 49 and 4 ^ 0.5 == 2 and 2 ^ 0 - 1 == 0 and
+    f * 2 == 3 and f // 1 == 1 and f % 1 == 0.5 and (0 - f) // 1 == 0 - 2 and (0 - f) % 1 == 0.5 and
+    a * f == 10.5 and a - f == 5.5 and
+    a / 7 .. "" == "1.0" and a ^ 1 .. "" == "7.0" and a // 1 .. "" == "7" and f // 1 .. "" == "1.0" and
+    is_int(a * b) and 9223372036854775807 + 1 == 0 - 9223372036854775807 - 1 and
+    (1 + 2) * 3 - 4 / 2 == 7
@@ -0,0 +1,11 @@
+local a = 12
+local b = 10
+local f = 3.0
+
+return
+    a & b == 8 and a | b == 14 and a ~ b == 6 and
+    1 << 4 == 16 and 256 >> 4 == 16 and 1 << 63 == 0 - 9223372036854775807 - 1 and
+    1 << 64 == 0 and 1 >> 64 == 0 and 16 << (0 - 2) == 4 and 1 >> (0 - 2) == 4 and
+    (0 - 1) >> 63 == 1 and (0 - 1) >> 1 == 9223372036854775807 and
+    f | 0 == 3 and (f | 0) .. "" == "3" and 2 ^ 2 & 7 == 4 and
+    1 | 2 ~ 3 & 4 == 3 and 1 << 2 + 1 == 8 and "x" .. (1 | 2) == "x3"
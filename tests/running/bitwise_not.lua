@@ -0,0 +1,4 @@
+local i = 5
+local f = 2.0
+
+return ~i == -6 and ~f == -3 and ~~i == i and ~0 == -1 and ~i & 0xF == 10 and ~-1 == 0
@@ -0,0 +1,12 @@
+local t = {}
+t.inner = {}
+t.inner.value = 3
+t.inner.get = function(self)
+    return self.value
+end
+
+local function add(a, b)
+    return a + b
+end
+
+return t.inner.get(t.inner) == 3 and add(t.inner.get(t.inner), t.inner.value) == 6
@@ -0,0 +1,24 @@
+local one = 1
+local two = 2.0
+local a = "a"
+local ab = "ab"
+local b = "b"
+
+local function count(n)
+    local c = 0
+    local i = 0
+    while i < n do
+        i = i + 1
+        c = c + 1
+    end
+    return c
+end
+
+local less = one < two
+local greater = one > two
+
+return
+    less and not greater and one <= 1 and one >= 1 and two >= one and not (two <= one) and
+    a < ab and ab < b and a <= a and b > a and not (b < a) and b >= ab and
+    one < 3 and 3 > one and 1 < 2 and not (2 < 1) and 2.5 >= 2 and "x" >= "x" and
+    count(10) == 10 and 1 + 1 ~= 3 and not (2 ~= 2) and one ~= two == true
@@ -0,0 +1,27 @@
+local a = "a"
+local b = "b"
+local c = "c"
+
+local function f()
+    return "f"
+end
+
+local t = "x"
+for i = 1, 3 do
+    t = t .. i
+end
+
+return
+    a .. b .. c == "abc" and
+    a .. f() .. 1 + 2 .. b == "af3b" and
+    (a .. b) .. (c .. a) == "abca" and
+    t == "x123" and
+    1 .. "" == "1" and
+    10 .. 20 == "1020" and
+    1.5 .. "" == "1.5" and
+    1.0 .. "" == "1.0" and
+    0.1 .. "" == "0.1" and
+    1e100 .. "" == "1e+100" and
+    1e-5 .. "" == "1e-05" and
+    123456789012345.0 .. "" == "1.2345678901234e+14" and
+    "x" .. 1 .. 2.0 .. a .. "y" .. "z" .. f() == "x12.0ayzf"
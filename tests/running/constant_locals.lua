@@ -0,0 +1,75 @@
+local function test1()
+    local debug = false
+    local n = 1
+    if debug then
+        n = 2
+    end
+    return n == 1 and not debug
+end
+
+local function test2()
+    local a = 3
+    local function f()
+        return a + 1
+    end
+    return f() == 4
+end
+
+local function test3()
+    local b = 1
+    local r = b
+    do
+        local b = 2
+        r = r + b
+    end
+    return r == 3 and b == 1
+end
+
+local function test4()
+    -- `c` is assigned somewhere, so it must not be treated as constant
+    local c = 1
+    local function set()
+        c = 5
+    end
+    set()
+    return c == 5
+end
+
+local function test5()
+    local x, y, z = 1, 2
+    return x + y == 3 and z == nil
+end
+
+local function test6()
+    local s = 0
+    for i = 1, 3 do
+        local step = 2
+        s = s + step
+    end
+    return s == 6
+end
+
+local function test7()
+    local k = 10
+    local function f()
+        local k = k + 1
+        return function()
+            return k
+        end
+    end
+    local function g()
+        return function()
+            return k
+        end
+    end
+    return f()() == 11 and g()() == 10
+end
+
+return
+    test1() and
+    test2() and
+    test3() and
+    test4() and
+    test5() and
+    test6() and
+    test7()
@@ -0,0 +1,38 @@
+local function loop()
+    local n = 0
+    while true do
+        n = n + 1
+        if n == 3 then
+            break
+            n = 100
+        end
+    end
+    return n
+end
+
+local function skip()
+    local r = 1
+    goto skip
+    r = 2
+    ::skip::
+    r = r + 10
+    do
+        return r
+    end
+    r = 3
+end
+
+local function continue()
+    local s = 0
+    for i = 1, 5 do
+        if i % 2 == 0 then
+            goto continue
+            s = s + 100
+        end
+        s = s + i
+        ::continue::
+    end
+    return s
+end
+
+return loop() == 3 and skip() == 11 and continue() == 9
@@ -0,0 +1,13 @@
+-- Integer and float constants with equal values, and positive and negative zero, are all distinct
+local a, b = 0.0, -0.0
+local c, d = 1, 1.0
+local e, f = ...
+e, f = 0.0, -0.0
+
+return
+    1 / a > 0 and
+    1 / b < 0 and
+    1 / e > 0 and
+    1 / f < 0 and
+    d // 0 > 0 and
+    c + d == 2
@@ -0,0 +1,29 @@
+local a = {}
+a.b = {}
+a.b.c = {}
+
+function a.f()
+    return 1
+end
+
+function a.b.g(x)
+    return x + 1
+end
+
+function a.b.c.h(x, y)
+    return x + y
+end
+
+t = {}
+function t.i()
+    return a.f() + 1
+end
+
+local function upvalue()
+    function a.b.j()
+        return 4
+    end
+end
+upvalue()
+
+return a.f() == 1 and a.b.g(1) == 2 and a.b.c.h(1, 2) == 3 and t.i() == 2 and a.b.j() == 4
@@ -0,0 +1,66 @@
+counter = 0
+t = {a = 1, b = 2}
+
+local function bump()
+    counter = counter + 1
+    return counter
+end
+
+local function test1()
+    local s = t.a + t.b + t.a
+    return s == 4
+end
+
+local function test2()
+    -- The call changes the global between the two reads
+    local s = counter + bump() + counter
+    return s == 0 + 1 + 1
+end
+
+local function test3()
+    -- The first read happens only if the condition holds
+    local f = false
+    local r = f and t.a or t.b
+    return r == 2
+end
+
+local function test4()
+    t = {a = 5, b = t.b}
+    t.a = t.a + t.b
+    return t.a == 7
+end
+
+local function test5()
+    local function set(v)
+        t = v
+    end
+    local r = {t.a, set({a = 10}), t.a}
+    return r[1] == 7 and r[3] == 10
+end
+
+local function test6()
+    counter = 0
+    counter, t = counter + 1, {a = counter}
+    return counter == 1 and t.a == 0
+end
+
+local function test7()
+    -- The first read stays in use while the global changes before the second read
+    n = 1
+    local function g()
+        n = 2
+        return true
+    end
+    local r = n + (g() and n)
+    local s = 0 and (n + (g() and n))
+    return r == 3 and s == 4
+end
+
+return
+    test1() and
+    test2() and
+    test3() and
+    test4() and
+    test5() and
+    test6() and
+    test7()
@@ -0,0 +1,49 @@
+local function test1()
+    local s = 0
+    for i = 1, 10 do
+        s = s + i
+    end
+    return s == 55
+end
+
+local function test2()
+    local n = 0
+    for i = 10, 1, -3 do
+        n = n + 1
+    end
+    return n == 4
+end
+
+local function test3()
+    -- The loop ends instead of overflowing the counter
+    local n = 0
+    for i = 9223372036854775800, 9223372036854775807, 5 do
+        n = n + 1
+    end
+    return n == 2
+end
+
+local function test4()
+    local n = 0
+    for i = 1, 0 do
+        n = n + 1
+    end
+    return n == 0
+end
+
+local function test5()
+    -- Assigning to the loop variable does not affect the iteration
+    local s = 0
+    for i = 1, 3 do
+        i = i * 10
+        s = s + i
+    end
+    return s == 60
+end
+
+return
+    test1() and
+    test2() and
+    test3() and
+    test4() and
+    test5()
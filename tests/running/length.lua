@@ -0,0 +1,9 @@
+local s = "hello"
+local t = {1, 2, 3, x = 4}
+local empty = {}
+
+t[#t + 1] = 4
+empty[1] = "a"
+
+return #s == 5 and #"" == 0 and #t == 4 and #empty == 1 and #{} == 0 and
+    #(s .. s) == 10 and -#s == -5
@@ -0,0 +1,33 @@
+local counter = {count = 0}
+
+function counter:add(n)
+    self.count = self.count + n
+    return self
+end
+
+function counter:get()
+    return self.count
+end
+
+local function values(...)
+    return ...
+end
+
+counter:add(1)
+counter:add(2):add(3)
+
+local outer = {inner = counter}
+local a, b = outer.inner:get(), values(counter:get(), 10)
+
+local function tail()
+    return counter:get()
+end
+
+local function get_upvalue()
+    return counter:get() + outer.inner:get()
+end
+
+local t = {counter:get(), counter:get()}
+
+return counter:get() == 6 and a == 6 and b == 6 and tail() == 6 and get_upvalue() == 12 and
+    #t == 2 and t[2] == 6
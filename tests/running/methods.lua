@@ -0,0 +1,24 @@
+local t = {}
+t.value = 1
+t.inner = {}
+t.inner.value = 10
+
+function t:get()
+    return self.value
+end
+
+function t:add(x, y)
+    self.value = self.value + x + y
+    return self
+end
+
+function t.inner:get()
+    return self.value
+end
+
+local u = {}
+u.value = 5
+u.get = t.get
+
+local a = t.add(t, 2, 3)
+return a == t and t.get(t) == 6 and t.inner.get(t.inner) == 10 and u.get(u) == 5
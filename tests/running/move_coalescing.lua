@@ -0,0 +1,42 @@
+local function test1()
+    local a, b = 1, 2
+    local x = 0
+    x = a + b
+    x = x * 10
+    return x == 30
+end
+
+local function test2()
+    -- Both branches of a short circuit or a comparison must reach the assigned local
+    local a, b = false, 3
+    local x
+    x = a and b + 1
+    local y
+    y = b and b + 1
+    local z
+    z = a or b + 2
+    local w
+    w = b < 4
+    return x == false and y == 4 and z == 5 and w == true
+end
+
+local function test3()
+    local a, b = 1, 2
+    a, b = b + 1, a + 1
+    return a == 3 and b == 2
+end
+
+local function test4()
+    local t = {x = 5}
+    local v = 0
+    v = t.x
+    v = -v
+    v = #"abc" .. v
+    return v == "3-5"
+end
+
+return
+    test1() and
+    test2() and
+    test3() and
+    test4()
@@ -0,0 +1,46 @@
+local function f()
+    return 1, 2
+end
+
+local function g()
+    return 3, 4
+end
+
+local function pack(...)
+    return {...}
+end
+
+local function count(...)
+    return #{...}
+end
+
+local t = {f(), g()}
+local u = {f(), g(), 5}
+local v = pack(f(), g())
+local w = {(g())}
+
+local a, b, c = f(), g()
+local d, e, h
+d, e, h = g(), f()
+
+local i = 3
+local j = {}
+i, j[i] = i + 1, 20
+
+local k, l = 1, 2
+k, l = l, k
+
+local calls = 0
+local function call()
+    calls = calls + 1
+end
+local m
+m = 1, call()
+
+return #t == 3 and t[1] == 1 and t[2] == 3 and t[3] == 4 and
+    #u == 3 and u[1] == 1 and u[2] == 3 and u[3] == 5 and
+    #v == 3 and v[3] == 4 and #w == 1 and
+    count(f(), g()) == 3 and count(g(), 5) == 2 and
+    a == 1 and b == 3 and c == 4 and d == 3 and e == 1 and h == 2 and
+    i == 4 and j[3] == 20 and j[4] == nil and
+    k == 2 and l == 1 and m == 1 and calls == 1
@@ -0,0 +1,20 @@
+local function factorial(n)
+    if n == 0 then
+        return 1
+    end
+    local r = factorial(n - 1)
+    return n * r
+end
+
+local function outer()
+    local function even(n)
+        if n == 0 then
+            return true
+        end
+        local r = even(n - 2)
+        return r
+    end
+    return even(10)
+end
+
+return factorial(5) == 120 and outer()
@@ -0,0 +1,48 @@
+local function classify(x)
+    local r
+    if x == 1 then
+        r = "one"
+    elseif x == "two" then
+        r = "two"
+    elseif 3 == x then
+        r = "three"
+    elseif x == 1 then
+        r = "unreachable"
+    else
+        r = "other"
+    end
+    return r
+end
+
+local function no_default(x)
+    local r = "none"
+    if x == 1 then
+        r = "one"
+    elseif x == 2 then
+        r = "two"
+    elseif x == 3 then
+        r = "three"
+    end
+    return r
+end
+
+local function captures(x)
+    local f
+    if x == 1 then
+        local y = 10
+        f = function() return y end
+    elseif x == 2 then
+        local y = 20
+        f = function() return y end
+    elseif x == 3 then
+        local y = 30
+        f = function() return y end
+    end
+    return f
+end
+
+return classify(1) == "one" and classify(1.0) == "one" and classify("two") == "two" and
+    classify(3) == "three" and classify(4) == "other" and classify("1") == "other" and
+    classify(nil) == "other" and classify(0 / 0) == "other" and classify(1.5) == "other" and
+    no_default(2) == "two" and no_default(5) == "none" and
+    captures(1)() == 10 and captures(3)() == 30 and captures(4) == nil
@@ -0,0 +1,36 @@
+local function three()
+    return 1, 2, 3
+end
+
+local function varargs(...)
+    return {...}
+end
+
+local key = "k"
+local t = {10, 20, x = 1, ["y"] = 2, [key] = 3, 30, [1 + 1] = "ignored"; three()}
+
+local big = {
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
+    21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40,
+    41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60,
+    61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80,
+    81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95, 96, 97, 98, 99, 100,
+    101, 102, 103, 104, 105, 106, 107, 108, 109, 110, three(),
+}
+
+local sum = 0
+for i = 1, 113 do
+    sum = sum + big[i]
+end
+
+local v = varargs(4, 5, 6)
+local nested = {{1}, {a = {2}}}
+local truncated = {three(), three(), 4}
+
+return
+    t[1] == 10 and t[2] == 20 and t[3] == 30 and t[4] == 1 and t[5] == 2 and t[6] == 3 and
+    t[7] == nil and t.x == 1 and t.y == 2 and t.k == 3 and
+    sum == 6111 and big[114] == nil and
+    v[1] == 4 and v[2] == 5 and v[3] == 6 and v[4] == nil and
+    nested[1][1] == 1 and nested[2].a[1] == 2 and
+    truncated[1] == 1 and truncated[2] == 1 and truncated[3] == 4 and truncated[4] == nil
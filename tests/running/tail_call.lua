@@ -0,0 +1,32 @@
+local function count(n, acc)
+    if n == 0 then
+        return acc
+    end
+    return count(n - 1, acc + 1)
+end
+
+local function multiple(...)
+    return ...
+end
+
+local function forward(...)
+    return multiple(...)
+end
+
+local function truncated()
+    return (multiple(1, 2, 3))
+end
+
+local function capture(x)
+    local function get()
+        return x
+    end
+    return multiple(get)
+end
+
+local a, b, c = forward(1, 2, 3)
+local d, e = truncated()
+local t = {forward(4, 5, 6)}
+
+return count(100000, 0) == 100000 and a == 1 and b == 2 and c == 3 and d == 1 and e == nil and
+    t[3] == 6 and capture(7)() == 7
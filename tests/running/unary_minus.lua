@@ -0,0 +1,8 @@
+local i = 5
+local f = 2.5
+local m = -9223372036854775807 - 1
+
+return
+    -i == 0 - 5 and -f == 0 - 2.5 and - -i == 5 and -(i + 1) == -6 and
+    -2 ^ 2 == -4 and 2 ^ -1 == 0.5 and -m == m and
+    -i .. "" == "-5" and -0.0 .. "" == "-0.0" and -i < 0 and 1 - -1 == 2
@@ -20,6 +20,36 @@ local function test2()
         varargs(0, 1, 1, 2, 3, 5) == 4
 end
 
+local function test3()
+    local function pass(...)
+        return ...
+    end
+
+    local a, b, c = pass(1, 2, 3)
+    return a == 1 and b == 2 and c == 3
+end
+
+local function test4()
+    local function pass(...)
+        return ...
+    end
+
+    local function first(...)
+        return (pass(...))
+    end
+
+    local function first_vararg(...)
+        local a, b = (...)
+        return a, b
+    end
+
+    local a, b = first(1, 2)
+    local c, d = first_vararg(3, 4)
+    return a == 1 and b == nil and c == 3 and d == nil
+end
+
 return
     test1() and
-    test2()
\ No newline at end of file
+    test2() and
+    test3() and
+    test4()
\ No newline at end of file
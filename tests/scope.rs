@@ -0,0 +1,148 @@
+use luster::parser::parse_chunk;
+use luster::scope::{analyze_chunk, LocalKind, ReferenceKind, Resolution};
+use luster::types::LineNumber;
+
+#[test]
+fn test_locals_and_globals() {
+    let chunk = parse_chunk(
+        &br#"
+            local a = 1
+            b = a
+            local a = a
+        "#[..],
+    )
+    .unwrap();
+    let symbols = analyze_chunk(&chunk);
+
+    assert_eq!(symbols.locals.len(), 2);
+    assert_eq!(symbols.name(symbols.locals[0].name), b"a");
+    assert_eq!(symbols.name(symbols.locals[1].name), b"a");
+
+    let refs = &symbols.references;
+    assert_eq!(refs.len(), 3);
+    assert_eq!(symbols.name(refs[0].name), b"b");
+    assert_eq!(refs[0].kind, ReferenceKind::Write);
+    assert_eq!(refs[0].resolution, Resolution::Global(None));
+    // `b = a` and the initializer of the second `a` both refer to the first `a`
+    assert_eq!(
+        refs[1].resolution,
+        Resolution::Local(symbols.locals_named(b"a").next().unwrap())
+    );
+    assert_eq!(
+        refs[2].resolution,
+        Resolution::Local(symbols.locals_named(b"a").next().unwrap())
+    );
+}
+
+#[test]
+fn test_upvalues() {
+    let chunk = parse_chunk(
+        &br#"
+            local function f(x)
+                return function()
+                    return f(x), y
+                end
+            end
+        "#[..],
+    )
+    .unwrap();
+    let symbols = analyze_chunk(&chunk);
+
+    let f = symbols.locals_named(b"f").next().unwrap();
+    let x = symbols.locals_named(b"x").next().unwrap();
+    assert_eq!(symbols.local(f).kind, LocalKind::LocalFunction);
+    assert_eq!(symbols.local(x).kind, LocalKind::Parameter);
+    assert!(symbols.local(f).captured);
+    assert!(symbols.local(x).captured);
+
+    assert_eq!(symbols.references_to(f).count(), 1);
+    assert_eq!(symbols.references_to(x).count(), 1);
+    for r in symbols.references_to(x) {
+        assert_eq!(r.resolution, Resolution::UpValue(x));
+    }
+    let globals: Vec<_> = symbols
+        .global_references()
+        .map(|r| symbols.name(r.name))
+        .collect();
+    assert_eq!(globals, vec![b"y"]);
+}
+
+#[test]
+fn test_scopes() {
+    let chunk = parse_chunk(
+        &br#"
+            local _ENV = {}
+            do
+                local i = 1
+            end
+            repeat
+                local j = i
+            until j
+            for k = 1, 2 do end
+            return k
+        "#[..],
+    )
+    .unwrap();
+    let symbols = analyze_chunk(&chunk);
+
+    let env = symbols.locals_named(b"_ENV").next().unwrap();
+    let j = symbols.locals_named(b"j").next().unwrap();
+
+    let refs = &symbols.references;
+    assert_eq!(refs.len(), 3);
+    assert_eq!(symbols.name(refs[0].name), b"i");
+    assert_eq!(refs[0].resolution, Resolution::Global(Some(env)));
+    assert_eq!(symbols.name(refs[1].name), b"j");
+    assert_eq!(refs[1].resolution, Resolution::Local(j));
+    assert_eq!(symbols.name(refs[2].name), b"k");
+    assert_eq!(refs[2].resolution, Resolution::Global(Some(env)));
+}
+
+#[test]
+fn test_lines() {
+    let chunk = parse_chunk(
+        &br#"local a = 1
+function f(x)
+    local y = x
+    repeat
+        y = a
+    until y
+    return y
+end
+"#[..],
+    )
+    .unwrap();
+    let symbols = analyze_chunk(&chunk);
+
+    let local_lines = symbols
+        .locals
+        .iter()
+        .map(|l| (symbols.name(l.name), l.line))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        local_lines,
+        vec![
+            (&b"a"[..], Some(LineNumber(1))),
+            (&b"x"[..], Some(LineNumber(2))),
+            (&b"y"[..], Some(LineNumber(3))),
+        ]
+    );
+
+    let reference_lines = symbols
+        .references
+        .iter()
+        .map(|r| (symbols.name(r.name), r.line))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        reference_lines,
+        vec![
+            (&b"f"[..], Some(LineNumber(2))),
+            (&b"x"[..], Some(LineNumber(3))),
+            (&b"y"[..], Some(LineNumber(5))),
+            (&b"a"[..], Some(LineNumber(5))),
+            // The condition of a repeat statement is located at the statement
+            (&b"y"[..], Some(LineNumber(4))),
+            (&b"y"[..], Some(LineNumber(7))),
+        ]
+    );
+}
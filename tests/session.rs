@@ -0,0 +1,95 @@
+use failure::Error;
+
+use luster::compiler::{compile_session_chunk, CompilerOptions};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::parser::parse_chunk;
+use luster::sequence::{sequence_fn, SequenceExt};
+use luster::string::String;
+use luster::table::Table;
+use luster::value::Value;
+
+const SESSION_KEY: &[u8] = b"session";
+
+// Runs each chunk in turn in the same session, returning whether the last chunk returned true.
+fn run_session(chunks: &[&str]) -> Result<bool, Error> {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, lc| {
+        lc.registry.set(
+            mc,
+            Value::String(String::Static(SESSION_KEY)),
+            Value::Table(Table::new(mc)),
+        )?;
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })?;
+
+    let mut session_locals = Vec::new();
+    let mut result = false;
+    for source in chunks {
+        let chunk = parse_chunk(source.as_bytes())?;
+        let session_locals = &mut session_locals;
+        result = lua.sequence(move |mc, lc| {
+            let proto = compile_session_chunk(
+                mc,
+                b"test",
+                &chunk,
+                session_locals,
+                &CompilerOptions::default(),
+            )?;
+            let session = lc.registry.get(Value::String(String::Static(SESSION_KEY)));
+            Ok(Box::new(
+                lc.main_thread
+                    .call_function(
+                        mc,
+                        Closure::new(mc, proto, Some(lc.globals))?,
+                        &[session],
+                        64,
+                    )
+                    .map(|_, r| Ok(r == [Value::Boolean(true)])),
+            ))
+        })?;
+    }
+    Ok(result)
+}
+
+#[test]
+fn test_session_locals() {
+    assert!(run_session(&[
+        "local a, b, c = 1, 2",
+        "b = b + 1",
+        "return a == 1 and b == 3 and c == nil",
+    ])
+    .unwrap());
+}
+
+#[test]
+fn test_session_locals_are_not_globals() {
+    assert!(run_session(&[
+        "local a = 1",
+        "local b = 2 b = 3",
+        "return _ENV.a == nil and _ENV.b == nil and b == 3",
+    ])
+    .unwrap());
+}
+
+#[test]
+fn test_session_upvalues() {
+    assert!(run_session(&[
+        "local i = 0",
+        "local function inc() i = i + 1 return i end",
+        "inc() inc()",
+        "local get = function() return i end",
+        "return inc() == 3 and get() == 3",
+    ])
+    .unwrap());
+}
+
+#[test]
+fn test_session_nested_locals() {
+    assert!(run_session(&[
+        "local a = 1 do local a = 2 end",
+        "local b do local b = 5 end",
+        "return a == 1 and b == nil",
+    ])
+    .unwrap());
+}
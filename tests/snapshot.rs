@@ -0,0 +1,59 @@
+use failure::Error;
+
+use luster::compiler::{compile_chunk, CompilerOptions};
+use luster::function::Closure;
+use luster::lua::Lua;
+use luster::parser::parse_chunk;
+use luster::sequence::SequenceExt;
+use luster::snapshot::{ChangeKind, TableDiff, TableSnapshot, ValueSummary};
+
+fn run_with_diff(lua: &mut Lua, source: &str) -> Result<TableDiff, Error> {
+    let chunk = parse_chunk(source.as_bytes())?;
+    lua.sequence(move |mc, lc| {
+        let closure = Closure::new(
+            mc,
+            compile_chunk(mc, b"test", &chunk, &CompilerOptions::default())?,
+            Some(lc.globals),
+        )?;
+        let snapshot = TableSnapshot::new(mc, lc.globals);
+        Ok(Box::new(
+            lc.main_thread
+                .call_function(mc, closure, &[], 64)
+                .map_with(snapshot, |_, snapshot, _| Ok(snapshot.diff())),
+        ))
+    })
+}
+
+#[test]
+fn test_globals_diff() {
+    let mut lua = Lua::new();
+    let diff = run_with_diff(&mut lua, "b = 2 c = {} d = 'same'").unwrap();
+    assert_eq!(diff.created().count(), 3);
+
+    let diff = run_with_diff(&mut lua, "a = 1 b = 3 c = nil d = 'same' local e = 5").unwrap();
+    assert_eq!(diff.changes.len(), 3);
+
+    let modified: Vec<_> = diff.modified().collect();
+    assert_eq!(modified.len(), 1);
+    assert_eq!(modified[0].key, ValueSummary::String((*b"b").into()));
+    assert_eq!(modified[0].old, ValueSummary::Integer(2));
+    assert_eq!(modified[0].new, ValueSummary::Integer(3));
+
+    let deleted: Vec<_> = diff.deleted().collect();
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].key, ValueSummary::String((*b"c").into()));
+    assert_eq!(deleted[0].old.type_name(), "table");
+
+    let created: Vec<_> = diff.created().collect();
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].kind, ChangeKind::Created);
+    assert_eq!(created[0].key, ValueSummary::String((*b"a").into()));
+    assert_eq!(created[0].new, ValueSummary::Integer(1));
+
+    // Long enough to need several sequence steps
+    assert!(
+        run_with_diff(&mut lua, "local x = a for i = 1, 1000 do x = i end")
+            .unwrap()
+            .is_empty()
+    );
+}
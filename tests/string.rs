@@ -0,0 +1,71 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use luster::lua::Lua;
+use luster::sequence::sequence_fn;
+use luster::string::{String, StringBuilder};
+use luster::value::Value;
+
+#[test]
+fn test_string_builder() {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, _| {
+        let mut builder = StringBuilder::new();
+        assert!(builder.is_empty());
+        builder.append(b"hello");
+        builder.push(b',');
+        builder.append(b" world");
+        assert_eq!(builder.as_bytes(), b"hello, world");
+        let short = builder.clone().finish(mc);
+        assert_eq!(short, String::new(mc, b"hello, world"));
+        match short {
+            String::Short32(..) => {}
+            _ => panic!("short string not stored inline"),
+        }
+
+        for i in 0..100 {
+            write!(builder, " {}", i)?;
+        }
+        let len = builder.len();
+        let long = builder.finish(mc);
+        assert_eq!(long.len(), len);
+        assert!(long.starts_with(b"hello, world 0 1 2"));
+        assert!(long.ends_with(b"98 99"));
+
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_shared_strings() {
+    let buffer: Arc<[u8]> = Arc::from(&b"a host owned buffer"[..]);
+    let mut lua = Lua::new();
+
+    let shared = buffer.clone();
+    lua.sequence(move |mc, lc| {
+        let string = String::from_shared(mc, shared);
+        assert_eq!(string.as_bytes(), b"a host owned buffer");
+        assert_eq!(string, String::new(mc, b"a host owned buffer"));
+        lc.globals.set(
+            mc,
+            Value::String(String::from_static(b"shared")),
+            Value::String(string),
+        )?;
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+    assert_eq!(Arc::strong_count(&buffer), 2);
+
+    lua.sequence(|mc, lc| {
+        match lc.globals.get(Value::String(String::new(mc, b"shared"))) {
+            Value::String(String::Shared(b)) => assert!(Arc::ptr_eq(b.buffer(), &buffer)),
+            _ => panic!("shared string was copied"),
+        }
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+
+    drop(lua);
+    assert_eq!(Arc::strong_count(&buffer), 1);
+}
@@ -4,7 +4,7 @@ use std::path::PathBuf;
 
 use failure::Error;
 
-use luster::compiler::compile_chunk;
+use luster::compiler::{compile_chunk, CompilerOptions};
 use luster::function::Closure;
 use luster::io::buffered_read;
 use luster::lua::Lua;
@@ -34,6 +34,7 @@ fn test_dir(dir: &str, run_code: bool) {
                     }
                     Ok(chunk) => {
                         if run_code {
+                            let name = path.to_string_lossy().into_owned();
                             let mut lua = Lua::new();
                             let r = lua.sequence(move |mc, lc| {
                                 Ok(Box::new(
@@ -42,7 +43,12 @@ fn test_dir(dir: &str, run_code: bool) {
                                             mc,
                                             Closure::new(
                                                 mc,
-                                                compile_chunk(mc, &chunk)?,
+                                                compile_chunk(
+                                                    mc,
+                                                    name.as_bytes(),
+                                                    &chunk,
+                                                    &CompilerOptions::default(),
+                                                )?,
                                                 Some(lc.globals),
                                             )?,
                                             &[],
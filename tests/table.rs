@@ -0,0 +1,113 @@
+use luster::lua::Lua;
+use luster::sequence::sequence_fn;
+use luster::string::String;
+use luster::table::Table;
+use luster::value::Value;
+
+#[test]
+fn test_next() {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, _| {
+        let table = Table::new(mc);
+        for i in 1..=4 {
+            table.set(mc, Value::Integer(i), Value::Integer(i * 10))?;
+        }
+        for &name in &[&b"a"[..], b"b", b"c", b"d"] {
+            table.set(
+                mc,
+                Value::String(String::new(mc, name)),
+                Value::Boolean(true),
+            )?;
+        }
+
+        let mut count = 0;
+        let mut key = Value::Nil;
+        while let Some((k, v)) = table.next(key)? {
+            assert_eq!(table.get(k), v);
+            count += 1;
+            key = k;
+        }
+        assert_eq!(count, 8);
+
+        assert!(table.next(Value::Integer(100)).is_err());
+        assert!(table
+            .next(Value::String(String::new(mc, b"missing")))
+            .is_err());
+
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_next_clearing_fields() {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, _| {
+        let table = Table::new(mc);
+        for i in 0..32 {
+            table.set(mc, Value::Number(i as f64 + 0.5), Value::Integer(i))?;
+        }
+
+        // Clear every field as it is visited, and make sure each field is still visited once.
+        let mut seen = Vec::new();
+        let mut key = Value::Nil;
+        while let Some((k, v)) = table.next(key)? {
+            match v {
+                Value::Integer(i) => seen.push(i),
+                _ => panic!("unexpected value"),
+            }
+            table.set(mc, k, Value::Nil)?;
+            key = k;
+        }
+        seen.sort();
+        assert_eq!(seen, (0..32).collect::<Vec<_>>());
+        assert_eq!(table.next(Value::Nil)?, None);
+
+        // Cleared entries are dropped once the map part needs to grow, and inserting afterwards
+        // still works.
+        for i in 0..64 {
+            table.set(mc, Value::Number(i as f64 + 0.25), Value::Integer(i))?;
+        }
+        for i in 0..64 {
+            assert_eq!(table.get(Value::Number(i as f64 + 0.25)), Value::Integer(i));
+        }
+        assert_eq!(table.get(Value::Number(0.5)), Value::Nil);
+
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+}
+
+#[test]
+fn test_length() {
+    let mut lua = Lua::new();
+    lua.sequence(|mc, _| {
+        let table = Table::new(mc);
+        assert_eq!(table.length(), 0);
+
+        for i in 1..=10 {
+            table.set(mc, Value::Integer(i), Value::Integer(i))?;
+        }
+        assert_eq!(table.length(), 10);
+
+        table.set(mc, Value::Integer(10), Value::Nil)?;
+        assert_eq!(table.length(), 9);
+
+        // Keys set in reverse order start out in the map part
+        let sparse = Table::new(mc);
+        for i in (1..=5).rev() {
+            sparse.set(mc, Value::Integer(i), Value::Boolean(true))?;
+        }
+        assert_eq!(sparse.length(), 5);
+
+        // Any border is a valid length for a table which is not a sequence
+        let holes = Table::new(mc);
+        holes.set(mc, Value::Integer(1), Value::Boolean(true))?;
+        holes.set(mc, Value::Integer(3), Value::Boolean(true))?;
+        let length = holes.length();
+        assert!(length == 1 || length == 3);
+
+        Ok(Box::new(sequence_fn(|_| Ok(()))))
+    })
+    .unwrap();
+}